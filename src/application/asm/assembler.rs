@@ -0,0 +1,991 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use crate::application::asm::syntax::{lookup, parse_number, CMP_NAMES, EDGE_NAMES, FIXED_NAMES, FLOAT_NAMES, KEYWORDS, LANE_NAMES, PROGRAM_COUNTER_NAME, SIMD_NAMES};
+use crate::application::input::{DataSection, FamInput};
+use crate::application::simulation::cpu_registers::{CpuRegisterAddress, REGISTER_COUNT};
+use crate::application::simulation::instruction::Instruction;
+use crate::application::simulation::instruction_reader::CodeRegion;
+use crate::application::simulation::propagation::PropagationModel;
+use crate::application::simulation::talu::{FloatOp, OverflowMode, QFormat, SimdOp, TaluAddress, TaluOperation, TALU_COUNT};
+use crate::word::Word;
+use crate::PROGRAM_COUNTER_REGISTER_ADDR;
+
+/// An error in assembly source. Lines and columns start at 1.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AsmError {
+    pub line    : usize,
+    pub column  : usize,
+    pub message : String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// An error on the current line, at a column.
+type Fail = (usize, String);
+
+/// Assembles a program written in the syntax below into an input ready to
+/// run, reporting the first error on every line that has one.
+///
+/// ```text
+/// ; comments run to the end of the line
+/// .reg count = r2          ; names a register
+/// .data 1, 0x10, -3        ; appends words to main memory
+/// .section table addr = 0x40 len = 16
+/// .data 7, 8               ; after a `.section`, appends to the section
+/// .init count = 5          ; sets a register before the first step
+/// .latency Div = 4         ; per operation, or `default`
+/// .propagation combinational
+/// .code 0x100, 64          ; runs from main memory, see `CodeRegion`
+///
+///         set count, 5
+///         T0 <- LT r5, count -> r6 when r1
+///         T1 <- ADD r5, r1 -> r5, r7 when r8 then r9
+/// loop:   wait r9
+///         jump loop
+/// ```
+///
+/// A TALU is configured with `T<addr> <- MNEMONIC[.modifiers] inputs ->
+/// outputs when activation then activation_output`, using the mnemonics
+/// the visualizer shows. Trailing optional outputs and `then` may be left
+/// out. `LUT` and `DLY` take their table address, table size and step count
+/// as numbers among the inputs. `T[r1] <- r2` configures the TALU numbered
+/// by `r1` with the operation encoded in `r2` and `r3`, and `T[r1] <- [r2]`
+/// with the one encoded in main memory at the address in `r2`. A line may
+/// start with the address of its instruction, as in `disassemble` listings,
+/// e.g. `  4: jump loop`.
+pub fn assemble(source: &str) -> Result<FamInput, Vec<AsmError>> {
+    let mut assembler = Assembler::default();
+    for (ix, line) in source.lines().enumerate() {
+        let result = tokenize(line).and_then(|tokens| {
+            let mut cursor = Cursor { tokens: &tokens, pos: 0, end_column: line.chars().count() + 1 };
+            assembler.line(&mut cursor, ix + 1)
+        });
+        if let Err((column, message)) = result {
+            assembler.errors.push(AsmError { line: ix + 1, column, message });
+        }
+    }
+    assembler.resolve_jumps();
+
+    if assembler.errors.is_empty() {
+        Ok(assembler.input)
+    } else {
+        Err(assembler.errors)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum TokenKind {
+    Ident(String),
+    Number(i64),
+    Comma,
+    Colon,
+    Equals,
+    Arrow,
+    BackArrow,
+    LeftBracket,
+    RightBracket,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Ident(name) => write!(f, "`{name}`"),
+            TokenKind::Number(n) => write!(f, "`{n}`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Colon => write!(f, "`:`"),
+            TokenKind::Equals => write!(f, "`=`"),
+            TokenKind::Arrow => write!(f, "`->`"),
+            TokenKind::BackArrow => write!(f, "`<-`"),
+            TokenKind::LeftBracket => write!(f, "`[`"),
+            TokenKind::RightBracket => write!(f, "`]`"),
+        }
+    }
+}
+
+struct Token {
+    kind    : TokenKind,
+    column  : usize,
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, Fail> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut ix = 0;
+    while ix < chars.len() {
+        let column = ix + 1;
+        let c = chars[ix];
+        let next = chars.get(ix + 1).copied();
+        let kind = match c {
+            ';' | '#' => break,
+            '/' if next == Some('/') => break,
+            c if c.is_whitespace() => {
+                ix += 1;
+                continue;
+            }
+            ',' => TokenKind::Comma,
+            ':' => TokenKind::Colon,
+            '=' => TokenKind::Equals,
+            '[' => TokenKind::LeftBracket,
+            ']' => TokenKind::RightBracket,
+            '-' if next == Some('>') => {
+                ix += 1;
+                TokenKind::Arrow
+            }
+            '<' if next == Some('-') => {
+                ix += 1;
+                TokenKind::BackArrow
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = ix;
+                ix += 1;
+                while ix < chars.len() && (chars[ix].is_ascii_alphanumeric() || chars[ix] == '_') {
+                    ix += 1;
+                }
+                let text: String = chars[start..ix].iter().collect();
+                let value = parse_number(&text).ok_or((column, format!("invalid number `{text}`")))?;
+                tokens.push(Token { kind: TokenKind::Number(value), column });
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' || c == '.' => {
+                let start = ix;
+                while ix < chars.len() && (chars[ix].is_alphanumeric() || chars[ix] == '_' || chars[ix] == '.') {
+                    ix += 1;
+                }
+                tokens.push(Token { kind: TokenKind::Ident(chars[start..ix].iter().collect()), column });
+                continue;
+            }
+            c => return Err((column, format!("unexpected character `{c}`"))),
+        };
+        tokens.push(Token { kind, column });
+        ix += 1;
+    }
+    Ok(tokens)
+}
+
+struct Cursor<'a> {
+    tokens      : &'a [Token],
+    pos         : usize,
+    end_column  : usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<&'a TokenKind> {
+        self.tokens.get(self.pos).map(|token| &token.kind)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(TokenKind::Ident(name)) if name.eq_ignore_ascii_case(keyword))
+    }
+
+    /// Column of the next token, or just past the end of the line.
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end_column, |token| token.column)
+    }
+
+    fn unexpected(&self, expected: &str) -> Fail {
+        let found = match self.peek() {
+            Some(kind) => kind.to_string(),
+            None => "end of line".to_string(),
+        };
+        (self.column(), format!("expected {expected}, found {found}"))
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        let matched = self.peek() == Some(kind);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn expect(&mut self, kind: &TokenKind) -> Result<(), Fail> {
+        if self.eat(kind) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&kind.to_string()))
+        }
+    }
+
+    fn ident(&mut self, expected: &str) -> Result<(&'a str, usize), Fail> {
+        match self.peek() {
+            Some(TokenKind::Ident(name)) => {
+                let column = self.column();
+                self.pos += 1;
+                Ok((name, column))
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn number(&mut self, expected: &str) -> Result<(i64, usize), Fail> {
+        match self.peek() {
+            Some(TokenKind::Number(value)) => {
+                let column = self.column();
+                self.pos += 1;
+                Ok((*value, column))
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn finish(&self) -> Result<(), Fail> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.unexpected("end of line")),
+        }
+    }
+}
+
+/// Accepts any value that fits in a word, read as signed or unsigned.
+fn to_word(value: i64, column: usize) -> Result<Word, Fail> {
+    if (Word::MIN as i64..=u32::MAX as i64).contains(&value) {
+        Ok(value as u32 as Word)
+    } else {
+        Err((column, format!("{value} doesn't fit in a word")))
+    }
+}
+
+fn to_small<T: TryFrom<i64>>(value: i64, column: usize, what: &str) -> Result<T, Fail> {
+    T::try_from(value).map_err(|_| (column, format!("{value} is out of range for {what}")))
+}
+
+#[derive(Default)]
+struct Assembler {
+    input       : FamInput,
+    registers   : HashMap<String, CpuRegisterAddress>,
+    labels      : HashMap<String, usize>,
+    /// Jumps to a label, by instruction index, resolved once every label
+    /// is known.
+    jumps       : Vec<(usize, String, usize, usize)>,
+    errors      : Vec<AsmError>,
+}
+
+impl Assembler {
+    fn line(&mut self, cursor: &mut Cursor, line: usize) -> Result<(), Fail> {
+        // An address prefix, as in listings, has to match the next instruction.
+        if let [Token { kind: TokenKind::Number(addr), column }, Token { kind: TokenKind::Colon, .. }, ..] = cursor.tokens {
+            let expected = self.input.program.len();
+            if *addr != expected as i64 {
+                return Err((*column, format!("address {addr} is instruction {expected}")));
+            }
+            cursor.pos += 2;
+        }
+        while let [Token { kind: TokenKind::Ident(name), column }, Token { kind: TokenKind::Colon, .. }, ..] =
+            &cursor.tokens[cursor.pos..]
+        {
+            self.check_symbol(name, *column)?;
+            if self.labels.insert(name.clone(), self.input.program.len()).is_some() {
+                return Err((*column, format!("label `{name}` is defined twice")));
+            }
+            cursor.pos += 2;
+        }
+
+        let Some(TokenKind::Ident(word)) = cursor.peek() else {
+            return match cursor.peek() {
+                None => Ok(()),
+                Some(_) => Err(cursor.unexpected("an instruction or directive")),
+            };
+        };
+        if word.starts_with('.') {
+            self.directive(cursor)
+        } else {
+            let instruction = self.instruction(cursor, line)?;
+            self.input.program.push(instruction);
+            Ok(())
+        }
+    }
+
+    fn check_symbol(&self, name: &str, column: usize) -> Result<(), Fail> {
+        let reserved = KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(name))
+            || name.eq_ignore_ascii_case(PROGRAM_COUNTER_NAME)
+            || parse_indexed(name, 'r').is_some()
+            || parse_indexed(name, 'T').is_some()
+            || name.contains('.');
+        if reserved {
+            Err((column, format!("`{name}` can't be used as a name")))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn directive(&mut self, cursor: &mut Cursor) -> Result<(), Fail> {
+        let (name, column) = cursor.ident("a directive")?;
+        match name.to_ascii_lowercase().as_str() {
+            ".reg" => {
+                let (symbol, symbol_column) = cursor.ident("a register name")?;
+                self.check_symbol(symbol, symbol_column)?;
+                cursor.expect(&TokenKind::Equals)?;
+                let reg = match cursor.peek() {
+                    Some(TokenKind::Number(_)) => {
+                        let (value, column) = cursor.number("a register")?;
+                        check_register(value, column)?
+                    }
+                    _ => self.register(cursor)?,
+                };
+                self.registers.insert(symbol.to_string(), reg);
+            }
+            ".data" => loop {
+                let (value, column) = cursor.number("a number")?;
+                let words = match self.input.data.last_mut() {
+                    Some(section) => &mut section.words,
+                    None => &mut self.input.main_memory,
+                };
+                words.push(to_word(value, column)?);
+                if !cursor.eat(&TokenKind::Comma) {
+                    break;
+                }
+            },
+            ".section" => {
+                let (name, name_column) = cursor.ident("a section name")?;
+                if self.input.data.iter().any(|section| section.name == name) {
+                    return Err((name_column, format!("section `{name}` is defined twice")));
+                }
+                let mut section = DataSection { name: name.to_string(), ..Default::default() };
+                while let Some(TokenKind::Ident(_)) = cursor.peek() {
+                    let (key, key_column) = cursor.ident("`addr` or `len`")?;
+                    cursor.expect(&TokenKind::Equals)?;
+                    let (value, column) = cursor.number("a number")?;
+                    match key.to_ascii_lowercase().as_str() {
+                        "addr" => section.addr = Some(to_small(value, column, "a main memory address")?),
+                        "len" => section.len = Some(to_small(value, column, "a section length")?),
+                        _ => return Err((key_column, format!("expected `addr` or `len`, found `{key}`"))),
+                    }
+                }
+                self.input.data.push(section);
+            }
+            ".init" => {
+                let reg = self.register(cursor)?;
+                cursor.expect(&TokenKind::Equals)?;
+                let (value, column) = cursor.number("a number")?;
+                self.input.registers.insert(reg, to_word(value, column)?);
+            }
+            ".latency" => {
+                let (operation, name_column) = cursor.ident("an operation name or `default`")?;
+                cursor.expect(&TokenKind::Equals)?;
+                let (value, column) = cursor.number("a latency")?;
+                let latency = to_small(value, column, "a latency")?;
+                if operation.eq_ignore_ascii_case("default") {
+                    self.input.talu_latencies.default = latency;
+                } else if !TaluOperation::KIND_NAMES.contains(&operation) {
+                    return Err((name_column, format!("unknown operation `{operation}`")));
+                } else {
+                    self.input.talu_latencies.per_operation.insert(operation.to_string(), latency);
+                }
+            }
+            ".propagation" => {
+                let (model, column) = cursor.ident("`registered` or `combinational`")?;
+                self.input.propagation = match model.to_ascii_lowercase().as_str() {
+                    "registered" => PropagationModel::Registered,
+                    "combinational" => PropagationModel::Combinational,
+                    _ => return Err((column, format!("unknown propagation model `{model}`"))),
+                };
+            }
+            ".code" => {
+                let (base, column) = cursor.number("a main memory address")?;
+                let base = to_small(base, column, "a main memory address")?;
+                cursor.expect(&TokenKind::Comma)?;
+                let (len, column) = cursor.number("an instruction count")?;
+                let len = to_small(len, column, "an instruction count")?;
+                self.input.code_region = Some(CodeRegion { base, len });
+            }
+            _ => return Err((column, format!("unknown directive `{name}`"))),
+        }
+        cursor.finish()
+    }
+
+    fn instruction(&mut self, cursor: &mut Cursor, line: usize) -> Result<Instruction, Fail> {
+        let (word, column) = cursor.ident("an instruction")?;
+        let instruction = match word.to_ascii_lowercase().as_str() {
+            "set" => {
+                let reg_addr = self.register(cursor)?;
+                cursor.expect(&TokenKind::Comma)?;
+                let (value, column) = cursor.number("a literal")?;
+                Instruction::SetLiteral { literal: to_word(value, column)?, reg_addr }
+            }
+            "wait" => Instruction::WaitForActivationSignal { register_index: self.register(cursor)? },
+            "jump" => match cursor.peek() {
+                Some(TokenKind::Number(_)) => {
+                    let (value, column) = cursor.number("an address")?;
+                    Instruction::Jump { addr: to_small(value, column, "an address")? }
+                }
+                _ => {
+                    let (label, column) = cursor.ident("a label or address")?;
+                    self.jumps.push((self.input.program.len(), label.to_string(), line, column));
+                    Instruction::Jump { addr: 0 }
+                }
+            },
+            "reset" => Instruction::ResetAllTalus,
+            "nop" => Instruction::NoOp,
+            "t" if cursor.eat(&TokenKind::LeftBracket) => {
+                let talu_addr_reg = self.register(cursor)?;
+                cursor.expect(&TokenKind::RightBracket)?;
+                cursor.expect(&TokenKind::BackArrow)?;
+                if cursor.eat(&TokenKind::LeftBracket) {
+                    let mem_addr_reg = self.register(cursor)?;
+                    cursor.expect(&TokenKind::RightBracket)?;
+                    Instruction::LoadTaluConfigFromMemory { talu_addr_reg, mem_addr_reg }
+                } else {
+                    let column = cursor.column();
+                    let config_reg = self.register(cursor)?;
+                    if config_reg + 1 >= REGISTER_COUNT {
+                        return Err((column, format!("r{config_reg} is the last register, the configuration takes two")));
+                    }
+                    Instruction::LoadTaluConfigFromRegisters { talu_addr_reg, config_reg }
+                }
+            }
+            _ => match parse_indexed(word, 'T') {
+                Some(addr) => {
+                    let talu_addr: TaluAddress = addr;
+                    if talu_addr >= TALU_COUNT {
+                        return Err((column, format!("there are only {TALU_COUNT} TALUs")));
+                    }
+                    cursor.expect(&TokenKind::BackArrow)?;
+                    Instruction::SetTaluConfig { talu_addr, talu_config: self.operation(cursor)? }
+                }
+                None => return Err((column, format!("unknown instruction `{word}`"))),
+            },
+        };
+        cursor.finish()?;
+        Ok(instruction)
+    }
+
+    fn register(&self, cursor: &mut Cursor) -> Result<CpuRegisterAddress, Fail> {
+        let (name, column) = cursor.ident("a register")?;
+        if let Some(&reg) = self.registers.get(name) {
+            return Ok(reg);
+        }
+        if name.eq_ignore_ascii_case(PROGRAM_COUNTER_NAME) {
+            return Ok(PROGRAM_COUNTER_REGISTER_ADDR);
+        }
+        match parse_indexed(name, 'r') {
+            Some(reg) => check_register(reg as i64, column),
+            None => Err((column, format!("unknown register `{name}`"))),
+        }
+    }
+
+    fn operation(&self, cursor: &mut Cursor) -> Result<TaluOperation, Fail> {
+        let (mnemonic, column) = cursor.ident("a TALU operation")?;
+        let mut parts = mnemonic.split('.');
+        let head = parts.next().unwrap_or_default().to_ascii_uppercase();
+        let modifiers = Modifiers { names: parts.map(str::to_ascii_lowercase).collect(), column };
+
+        let mut inputs = Vec::new();
+        if !matches!(cursor.peek(), None | Some(TokenKind::Arrow))
+            && !cursor.peek_keyword("when")
+            && !cursor.peek_keyword("then")
+        {
+            loop {
+                let column = cursor.column();
+                let value = match cursor.peek() {
+                    Some(TokenKind::Number(_)) => Operand::Immediate(cursor.number("an input")?.0),
+                    _ => Operand::Register(self.register(cursor)?),
+                };
+                inputs.push((value, column));
+                if !cursor.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        let mut outputs = Vec::new();
+        if cursor.eat(&TokenKind::Arrow) {
+            loop {
+                let column = cursor.column();
+                outputs.push((self.register(cursor)?, column));
+                if !cursor.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        let activation_input = if cursor.peek_keyword("when") {
+            cursor.pos += 1;
+            Some(self.register(cursor)?)
+        } else {
+            None
+        };
+        let activation_output = if cursor.peek_keyword("then") {
+            cursor.pos += 1;
+            Some(self.register(cursor)?)
+        } else {
+            None
+        };
+
+        let args = OpArgs { head: &head, column, end_column: cursor.column(), inputs, outputs, activation_input, activation_output };
+        build_operation(&args, modifiers)
+    }
+
+    fn resolve_jumps(&mut self) {
+        for (ix, label, line, column) in self.jumps.drain(..) {
+            match self.labels.get(&label) {
+                Some(&target) => self.input.program[ix] = Instruction::Jump { addr: target as Word },
+                None => self.errors.push(AsmError { line, column, message: format!("unknown label `{label}`") }),
+            }
+        }
+        self.errors.sort_by_key(|err| (err.line, err.column));
+    }
+}
+
+/// Parses names like `r12` or `T3`.
+fn parse_indexed(name: &str, prefix: char) -> Option<usize> {
+    let digits = name.strip_prefix(prefix.to_ascii_lowercase())
+        .or_else(|| name.strip_prefix(prefix.to_ascii_uppercase()))?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+fn check_register(reg: i64, column: usize) -> Result<CpuRegisterAddress, Fail> {
+    if (0..REGISTER_COUNT as i64).contains(&reg) {
+        Ok(reg as CpuRegisterAddress)
+    } else {
+        Err((column, format!("there are only {REGISTER_COUNT} registers")))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Register(CpuRegisterAddress),
+    Immediate(i64),
+}
+
+/// The parts of a TALU configuration line, before they're checked against
+/// the operation.
+struct OpArgs<'a> {
+    head                : &'a str,
+    column              : usize,
+    end_column          : usize,
+    inputs              : Vec<(Operand, usize)>,
+    outputs             : Vec<(CpuRegisterAddress, usize)>,
+    activation_input    : Option<CpuRegisterAddress>,
+    activation_output   : Option<CpuRegisterAddress>,
+}
+
+impl OpArgs<'_> {
+    fn expect_inputs(&self, required: usize, optional: usize) -> Result<(), Fail> {
+        let count = self.inputs.len();
+        if (required..=required + optional).contains(&count) {
+            return Ok(());
+        }
+        let expected = match optional {
+            0 => required.to_string(),
+            _ => format!("{required} or {}", required + optional),
+        };
+        Err((self.column, format!("`{}` takes {expected} inputs, found {count}", self.head)))
+    }
+
+    fn reg(&self, ix: usize) -> Result<CpuRegisterAddress, Fail> {
+        match self.inputs.get(ix) {
+            Some((Operand::Register(reg), _)) => Ok(*reg),
+            Some((Operand::Immediate(_), column)) => Err((*column, "expected a register".to_string())),
+            None => Ok(0),
+        }
+    }
+
+    fn imm(&self, ix: usize) -> Result<(i64, usize), Fail> {
+        match self.inputs.get(ix) {
+            Some((Operand::Immediate(value), column)) => Ok((*value, *column)),
+            Some((Operand::Register(_), column)) => Err((*column, "expected a number".to_string())),
+            None => Err((self.end_column, "expected a number".to_string())),
+        }
+    }
+
+    /// Checks the output count and pads the optional outputs with `None`.
+    fn outputs(&self, required: usize, optional: usize) -> Result<Vec<Option<CpuRegisterAddress>>, Fail> {
+        let count = self.outputs.len();
+        if count < required {
+            return Err((self.end_column, format!("`{}` needs {required} outputs after `->`", self.head)));
+        }
+        if count > required + optional {
+            let column = self.outputs[required + optional].1;
+            return Err((column, format!("`{}` has at most {} outputs", self.head, required + optional)));
+        }
+        let mut outputs: Vec<_> = self.outputs.iter().map(|(reg, _)| Some(*reg)).collect();
+        outputs.resize(required + optional, None);
+        Ok(outputs)
+    }
+
+    fn when(&self) -> Result<CpuRegisterAddress, Fail> {
+        self.activation_input.ok_or((self.end_column, "expected `when <register>`".to_string()))
+    }
+
+    fn then(&self) -> Result<CpuRegisterAddress, Fail> {
+        self.activation_output.ok_or((self.end_column, "expected `then <register>`".to_string()))
+    }
+}
+
+/// The `.`-separated modifiers after a mnemonic, e.g. `.q16.sat`.
+struct Modifiers {
+    names   : Vec<String>,
+    column  : usize,
+}
+
+impl Modifiers {
+    fn flag(&mut self, name: &str) -> bool {
+        let position = self.names.iter().position(|n| n == name);
+        if let Some(ix) = position {
+            self.names.remove(ix);
+        }
+        position.is_some()
+    }
+
+    fn take<T: Copy>(&mut self, table: &[(T, &str)], what: &str) -> Result<T, Fail> {
+        let position = self.names.iter().position(|n| lookup(table, n).is_some());
+        match position {
+            Some(ix) => Ok(lookup(table, &self.names.remove(ix)).unwrap()),
+            None => Err((self.column, format!("expected a {what} modifier"))),
+        }
+    }
+
+    fn q_format(&mut self) -> Result<QFormat, Fail> {
+        let position = self.names.iter().position(|n| n.starts_with('q') && n[1..].parse::<u8>().is_ok());
+        match position {
+            Some(ix) => Ok(QFormat { frac_bits: self.names.remove(ix)[1..].parse().unwrap() }),
+            None => Err((self.column, "expected a fixed-point format modifier like `.q16`".to_string())),
+        }
+    }
+
+    fn overflow(&mut self) -> OverflowMode {
+        if self.flag("sat") { OverflowMode::Saturate } else { OverflowMode::Wrap }
+    }
+
+    fn finish(&self) -> Result<(), Fail> {
+        match self.names.first() {
+            None => Ok(()),
+            Some(name) => Err((self.column, format!("unexpected modifier `.{name}`"))),
+        }
+    }
+}
+
+fn build_operation(args: &OpArgs, mut modifiers: Modifiers) -> Result<TaluOperation, Fail> {
+    let head = args.head;
+    let operation = if head == "NOP" {
+        args.expect_inputs(0, 0)?;
+        args.outputs(0, 0)?;
+        if args.activation_input.is_some() || args.activation_output.is_some() {
+            return Err((args.column, "`NOP` has no activation".to_string()));
+        }
+        TaluOperation::NoOp
+    } else if let Some(op) = lookup(&CMP_NAMES, head) {
+        args.expect_inputs(2, 0)?;
+        let out = args.outputs(1, 0)?;
+        TaluOperation::Cmp {
+            op,
+            activation_input: args.when()?,
+            activation_output: args.activation_output,
+            data_input_0: args.reg(0)?,
+            data_input_1: args.reg(1)?,
+            data_output: out[0].unwrap(),
+        }
+    } else if let Some(op) = lookup(&FIXED_NAMES, head) {
+        args.expect_inputs(2, 0)?;
+        let out = args.outputs(1, 1)?;
+        TaluOperation::Fixed {
+            op,
+            format: modifiers.q_format()?,
+            overflow: modifiers.overflow(),
+            activation_input: args.when()?,
+            data_input_0: args.reg(0)?,
+            data_input_1: args.reg(1)?,
+            data_output_0: out[0].unwrap(),
+            flags_output: out[1],
+            activation_output: args.activation_output,
+        }
+    } else if let Some(edge) = lookup(&EDGE_NAMES, head) {
+        args.expect_inputs(0, 0)?;
+        args.outputs(0, 0)?;
+        TaluOperation::EdgeDetect { edge, activation_input: args.when()?, activation_output: args.then()? }
+    } else if head == "VCMP" || lookup(&SIMD_NAMES, head).is_some() {
+        let op = match lookup(&SIMD_NAMES, head) {
+            Some(op) => op,
+            None => SimdOp::Cmp(modifiers.take(&CMP_NAMES, "comparison")?),
+        };
+        args.expect_inputs(if op.is_unary() { 1 } else { 2 }, op.is_unary() as usize)?;
+        let out = args.outputs(1, 0)?;
+        TaluOperation::Simd {
+            op,
+            lanes: modifiers.take(&LANE_NAMES, "lane width (`.8` or `.16`)")?,
+            signed: !modifiers.flag("u"),
+            overflow: modifiers.overflow(),
+            activation_input: args.when()?,
+            data_input_0: args.reg(0)?,
+            data_input_1: args.reg(1)?,
+            data_output_0: out[0].unwrap(),
+            activation_output: args.activation_output,
+        }
+    } else if head == "FCMP" || lookup(&FLOAT_NAMES, head).is_some() {
+        let op = match lookup(&FLOAT_NAMES, head) {
+            Some(op) => op,
+            None => FloatOp::Cmp(modifiers.take(&CMP_NAMES, "comparison")?),
+        };
+        args.expect_inputs(if op.is_unary() { 1 } else { 2 }, op.is_unary() as usize)?;
+        let out = args.outputs(1, 0)?;
+        TaluOperation::Float {
+            op,
+            activation_input: args.when()?,
+            data_input_0: args.reg(0)?,
+            data_input_1: args.reg(1)?,
+            data_output_0: out[0].unwrap(),
+            activation_output: args.activation_output,
+        }
+    } else {
+        basic_operation(args)?
+    };
+    modifiers.finish()?;
+    if let Some(err) = operation.parameter_error() {
+        return Err((args.column, err));
+    }
+    Ok(operation)
+}
+
+/// Operations without modifiers.
+fn basic_operation(args: &OpArgs) -> Result<TaluOperation, Fail> {
+    let head = args.head;
+    let activation_output = args.activation_output;
+    let (required_inputs, required_outputs, optional_outputs) = match head {
+        "MOV" | "NOT" | "NEG" | "READ" => (1, 1, 0),
+        "LAT" | "AND" | "OR" | "XOR" | "SHL" | "SHR" | "SEL" | "CNT" | "DLY" => (2, 1, 0),
+        "ADD" | "SUB" | "MUL" | "DIV" | "REM" | "ACC" => (2, 1, 1),
+        "MUX" | "LUT" => (3, 1, 0),
+        "WRIT" => (2, 0, 0),
+        "TIM" => (2, 0, 1),
+        _ => return Err((args.column, format!("unknown TALU operation `{head}`"))),
+    };
+    args.expect_inputs(required_inputs, 0)?;
+    let out = args.outputs(required_outputs, optional_outputs)?;
+    let activation_input = args.when()?;
+
+    Ok(match head {
+        "MOV" => TaluOperation::Mov { activation_input, value_input: args.reg(0)?, data_output: out[0].unwrap(), activation_output },
+        "NOT" => TaluOperation::Not { activation_input, data_input: args.reg(0)?, data_output: out[0].unwrap(), activation_output },
+        "NEG" => TaluOperation::Neg { activation_input, input: args.reg(0)?, data_output_0: out[0].unwrap(), activation_output },
+        "READ" => TaluOperation::ReadFromMem { activation_input, address_input: args.reg(0)?, data_output: out[0].unwrap(), activation_output },
+        "LAT" => TaluOperation::Latch {
+            activation_input, data_input: args.reg(0)?, hold_input: args.reg(1)?, data_output: out[0].unwrap(), activation_output,
+        },
+        "AND" => TaluOperation::And {
+            activation_input, data_input_0: args.reg(0)?, data_input_1: args.reg(1)?, data_output_0: out[0].unwrap(), activation_output,
+        },
+        "OR" => TaluOperation::Or {
+            activation_input, data_input_0: args.reg(0)?, data_input_1: args.reg(1)?, data_output_0: out[0].unwrap(), activation_output,
+        },
+        "XOR" => TaluOperation::Xor {
+            activation_input, data_input_0: args.reg(0)?, data_input_1: args.reg(1)?, data_output_0: out[0].unwrap(), activation_output,
+        },
+        "SHL" => TaluOperation::ShiftLeft {
+            activation_input, value: args.reg(0)?, shift_count: args.reg(1)?, data_output_0: out[0].unwrap(), activation_output,
+        },
+        "SHR" => TaluOperation::ShiftRight {
+            activation_input, value: args.reg(0)?, shift_count: args.reg(1)?, data_output_0: out[0].unwrap(), activation_output,
+        },
+        "SEL" => TaluOperation::SelectPart {
+            activation_input, data_input: args.reg(0)?, selection_input: args.reg(1)?, data_output_0: out[0].unwrap(), activation_output,
+        },
+        "CNT" => TaluOperation::Counter {
+            activation_input, down_input: args.reg(0)?, reset_input: args.reg(1)?, data_output_0: out[0].unwrap(), activation_output,
+        },
+        "DLY" => {
+            let (steps, column) = args.imm(1)?;
+            TaluOperation::Delay {
+                activation_input, data_input: args.reg(0)?, steps: to_small(steps, column, "a step count")?,
+                data_output_0: out[0].unwrap(), activation_output,
+            }
+        }
+        "ADD" => TaluOperation::Add {
+            activation_input, data_input_0: args.reg(0)?, data_input_1: args.reg(1)?,
+            result_output: out[0].unwrap(), flags_output: out[1], activation_output,
+        },
+        "SUB" => TaluOperation::Sub {
+            activation_input, data_input_0: args.reg(0)?, data_input_1: args.reg(1)?,
+            data_output_0: out[0].unwrap(), flags_output: out[1], activation_output,
+        },
+        "MUL" => TaluOperation::Mul {
+            activation_input, data_input_0: args.reg(0)?, data_input_1: args.reg(1)?,
+            first_word_output: out[0].unwrap(), second_word_output: out[1], activation_output,
+        },
+        "DIV" => TaluOperation::Div {
+            activation_input, dividend: args.reg(0)?, divisor: args.reg(1)?,
+            data_output_0: out[0].unwrap(), div_by_zero_flag_output: out[1], activation_output,
+        },
+        "REM" => TaluOperation::Rem {
+            activation_input, dividend: args.reg(0)?, divisor: args.reg(1)?,
+            data_output_0: out[0].unwrap(), div_by_zero_flag_output: out[1], activation_output,
+        },
+        "ACC" => TaluOperation::Accumulator {
+            activation_input, data_input: args.reg(0)?, reset_input: args.reg(1)?,
+            data_output_0: out[0].unwrap(), flags_output: out[1], activation_output,
+        },
+        "MUX" => TaluOperation::Mux {
+            activation_input, condition: args.reg(0)?, if_true: args.reg(1)?, if_false: args.reg(2)?,
+            data_output_0: out[0].unwrap(), activation_output,
+        },
+        "LUT" => {
+            let (table_addr, addr_column) = args.imm(1)?;
+            let (size_log2, size_column) = args.imm(2)?;
+            TaluOperation::Lut {
+                activation_input, data_input: args.reg(0)?,
+                table_addr: to_small(table_addr, addr_column, "a table address")?,
+                size_log2: to_small(size_log2, size_column, "a table size")?,
+                data_output_0: out[0].unwrap(), activation_output,
+            }
+        }
+        "WRIT" => TaluOperation::WriteToMem {
+            activation_input, data_input: args.reg(0)?, address_input: args.reg(1)?, activation_output,
+        },
+        "TIM" => TaluOperation::Timer {
+            activation_input, period_input: args.reg(0)?, reset_input: args.reg(1)?,
+            count_output: out[0], activation_output: args.then()?,
+        },
+        _ => unreachable!(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::simulation::talu::{CmpOp, Edge, FixedOp, LaneWidth};
+
+    fn errors(source: &str) -> Vec<(usize, usize, String)> {
+        assemble(source).unwrap_err().into_iter().map(|err| (err.line, err.column, err.message)).collect()
+    }
+
+    fn error(line: usize, column: usize, message: &str) -> (usize, usize, String) {
+        (line, column, message.to_string())
+    }
+
+    /// The operation a single `T0 <- ...` line configures.
+    fn operation(line: &str) -> TaluOperation {
+        match assemble(&format!("T0 <- {line}")).unwrap().program[..] {
+            [Instruction::SetTaluConfig { talu_config, .. }] => talu_config,
+            ref program => panic!("{line} assembled to {program:?}"),
+        }
+    }
+
+    #[test]
+    fn jumps_to_labels_before_and_after_them() {
+        let input = assemble("start:  set r1, 5\n        jump end\n        jump start\nend:    wait r1").unwrap();
+        assert_eq!(input.program, [
+            Instruction::SetLiteral { literal: 5, reg_addr: 1 },
+            Instruction::Jump { addr: 3 },
+            Instruction::Jump { addr: 0 },
+            Instruction::WaitForActivationSignal { register_index: 1 },
+        ]);
+    }
+
+    #[test]
+    fn duplicate_and_unknown_labels_are_errors() {
+        assert_eq!(errors("again: nop\n  again: nop\n  jump nowhere"), [
+            error(2, 3, "label `again` is defined twice"),
+            error(3, 8, "unknown label `nowhere`"),
+        ]);
+    }
+
+    #[test]
+    fn registers_have_names() {
+        let input = assemble(".reg count = r2\n.reg flag = 40\nset count, 5\nwait flag\n.init count = 7").unwrap();
+        assert_eq!(input.program, [
+            Instruction::SetLiteral { literal: 5, reg_addr: 2 },
+            Instruction::WaitForActivationSignal { register_index: 40 },
+        ]);
+        assert_eq!(input.registers, [(2, 7)].into());
+    }
+
+    #[test]
+    fn register_names_cant_clash_with_the_syntax() {
+        assert_eq!(errors(".reg r3 = r4\n.reg when = r1\n.reg PC = r1\n.reg T2 = r1\n.reg a.b = r1\nset: nop\n.reg x = 64\nwait y"), [
+            error(1, 6, "`r3` can't be used as a name"),
+            error(2, 6, "`when` can't be used as a name"),
+            error(3, 6, "`PC` can't be used as a name"),
+            error(4, 6, "`T2` can't be used as a name"),
+            error(5, 6, "`a.b` can't be used as a name"),
+            error(6, 1, "`set` can't be used as a name"),
+            error(7, 10, "there are only 64 registers"),
+            error(8, 6, "unknown register `y`"),
+        ]);
+    }
+
+    #[test]
+    fn reads_numbers_in_every_base() {
+        let input = assemble(".data 0x10, 0b101, 1_000, -3, 0xffff_ffff, -0x8000_0000\nset r1, -0b11").unwrap();
+        assert_eq!(input.main_memory, [16, 5, 1000, -3, -1, Word::MIN]);
+        assert_eq!(input.program, [Instruction::SetLiteral { literal: -3, reg_addr: 1 }]);
+    }
+
+    #[test]
+    fn numbers_that_dont_fit_a_word_are_errors() {
+        assert_eq!(errors(".data 0x1_0000_0000\nset r1, -2147483649\n.data 0xZZ\n.data 1,"), [
+            error(1, 7, "4294967296 doesn't fit in a word"),
+            error(2, 9, "-2147483649 doesn't fit in a word"),
+            error(3, 7, "invalid number `0xZZ`"),
+            error(4, 9, "expected a number, found end of line"),
+        ]);
+    }
+
+    #[test]
+    fn configures_every_mnemonic_family() {
+        assert_eq!(operation("QMUL.q16.sat r1, r2 -> r3, r4 when r5"), TaluOperation::Fixed {
+            op: FixedOp::Mul, format: QFormat { frac_bits: 16 }, overflow: OverflowMode::Saturate,
+            activation_input: 5, data_input_0: 1, data_input_1: 2, data_output_0: 3, flags_output: Some(4), activation_output: None,
+        });
+        assert_eq!(operation("VCMP.lt.8.u r1, r2 -> r3 when r4 then r5"), TaluOperation::Simd {
+            op: SimdOp::Cmp(CmpOp::LessThan), lanes: LaneWidth::Bits8, signed: false, overflow: OverflowMode::Wrap,
+            activation_input: 4, data_input_0: 1, data_input_1: 2, data_output_0: 3, activation_output: Some(5),
+        });
+        assert_eq!(operation("FCMP.ge r1, r2 -> r3 when r4"), TaluOperation::Float {
+            op: FloatOp::Cmp(CmpOp::GreaterThanOrEq), activation_input: 4, data_input_0: 1, data_input_1: 2, data_output_0: 3, activation_output: None,
+        });
+        assert_eq!(operation("ITOF r1 -> r3 when r4"), TaluOperation::Float {
+            op: FloatOp::FromInt, activation_input: 4, data_input_0: 1, data_input_1: 0, data_output_0: 3, activation_output: None,
+        });
+        assert_eq!(operation("LUT r1, 0x40, 4 -> r2 when r3"), TaluOperation::Lut {
+            activation_input: 3, data_input: 1, table_addr: 0x40, size_log2: 4, data_output_0: 2, activation_output: None,
+        });
+        assert_eq!(operation("DLY r1, 5 -> r2 when r3"), TaluOperation::Delay {
+            activation_input: 3, data_input: 1, steps: 5, data_output_0: 2, activation_output: None,
+        });
+        assert_eq!(operation("EDGR when r1 then r2"), TaluOperation::EdgeDetect {
+            edge: Edge::Rising, activation_input: 1, activation_output: 2,
+        });
+        assert_eq!(operation("TIM r1, r2 -> r3 when r4 then r5"), TaluOperation::Timer {
+            activation_input: 4, period_input: 1, reset_input: 2, count_output: Some(3), activation_output: 5,
+        });
+        assert_eq!(operation("MUX r1, r2, r3 -> r4 when r5"), TaluOperation::Mux {
+            activation_input: 5, condition: 1, if_true: 2, if_false: 3, data_output_0: 4, activation_output: None,
+        });
+        assert_eq!(operation("add r1, pc -> r2 when r3"), TaluOperation::Add {
+            activation_input: 3, data_input_0: 1, data_input_1: PROGRAM_COUNTER_REGISTER_ADDR, result_output: 2, flags_output: None, activation_output: None,
+        });
+
+        let input = assemble("T[r1] <- [r2]\nT[r1] <- r2").unwrap();
+        assert_eq!(input.program, [
+            Instruction::LoadTaluConfigFromMemory { talu_addr_reg: 1, mem_addr_reg: 2 },
+            Instruction::LoadTaluConfigFromRegisters { talu_addr_reg: 1, config_reg: 2 },
+        ]);
+    }
+
+    #[test]
+    fn operation_errors_point_at_their_column() {
+        assert_eq!(errors("T0 <- QMUL r1, r2 -> r3 when r4\nT0 <- VADD.8.x r1, r2 -> r3 when r4\nT0 <- ADD r1 -> r2 when r3\nT0 <- ADD r1, r2 -> r3\nT32 <- NOP\nT[r1] <- r63"), [
+            error(1, 7, "expected a fixed-point format modifier like `.q16`"),
+            error(2, 7, "unexpected modifier `.x`"),
+            error(3, 7, "`ADD` takes 2 inputs, found 1"),
+            error(4, 23, "expected `when <register>`"),
+            error(5, 1, "there are only 32 TALUs"),
+            error(6, 10, "r63 is the last register, the configuration takes two"),
+        ]);
+    }
+
+    #[test]
+    fn parameters_past_their_limits_are_errors_at_the_mnemonic() {
+        assert_eq!(errors("T0 <- QMUL.q40 r1, r2 -> r3 when r4\nT1 <- DLY r1, 99 -> r2 when r3\n  T2 <- LUT r1, 0, 12 -> r2 when r3"), [
+            (1, 7, "40 fractional bits, more than the 31 a word holds".to_string()),
+            (2, 7, "a delay of 99 steps, more than the 32 a TALU holds".to_string()),
+            (3, 9, "a table of 2^12 words, more than the 2^8 a TALU holds".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use itertools::Itertools;
+use crate::application::asm::syntax::{name_of, CMP_NAMES, EDGE_NAMES, FIXED_NAMES, FLOAT_NAMES, LANE_NAMES, PROGRAM_COUNTER_NAME, SIMD_NAMES};
+use crate::application::input::{FamInput, MemoryLimitError};
+use crate::application::simulation::cpu_registers::CpuRegisterAddress;
+use crate::application::simulation::instruction::Instruction;
+use crate::application::simulation::propagation::PropagationModel;
+use crate::application::simulation::talu::{FloatOp, OverflowMode, SimdOp, TaluOperation};
+use crate::word::Word;
+use crate::PROGRAM_COUNTER_REGISTER_ADDR;
+
+/// Words of main memory per `.data` line.
+const DATA_WORDS_PER_LINE: usize = 8;
+
+/// Prints `input` as an assembler listing, one line per instruction with
+/// its address, and a label `L<addr>` at every jump target. Assembling the
+/// listing gives back `input`. Data sections keep their names, unless one
+/// of them isn't a name the assembler reads or two are named alike; then
+/// they are all written into main memory, which fails if one of them ends
+/// past `MAX_MAIN_MEMORY_LEN`.
+pub fn disassemble(input: &FamInput) -> Result<String, MemoryLimitError> {
+    let mut out = String::new();
+
+    let named_sections = has_named_sections(input);
+    let main_memory = match named_sections {
+        true => input.main_memory.clone(),
+        false => input.memory_with_data()?,
+    };
+    write_data(&mut out, &main_memory);
+    if named_sections {
+        for section in input.data.iter() {
+            write!(out, ".section {}", section.name).unwrap();
+            if let Some(addr) = section.addr {
+                write!(out, " addr = {addr}").unwrap();
+            }
+            if let Some(len) = section.len {
+                write!(out, " len = {len}").unwrap();
+            }
+            out.push('\n');
+            write_data(&mut out, &section.words);
+        }
+    }
+    for (reg_addr, value) in input.registers.iter() {
+        writeln!(out, ".init {} = {value}", reg(*reg_addr)).unwrap();
+    }
+    if input.talu_latencies.default != 0 {
+        writeln!(out, ".latency default = {}", input.talu_latencies.default).unwrap();
+    }
+    for (operation, latency) in input.talu_latencies.per_operation.iter() {
+        writeln!(out, ".latency {operation} = {latency}").unwrap();
+    }
+    if input.propagation == PropagationModel::Combinational {
+        writeln!(out, ".propagation combinational").unwrap();
+    }
+    if let Some(region) = input.code_region {
+        writeln!(out, ".code {}, {}", region.base, region.len).unwrap();
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+
+    let labels = jump_targets(&input.program);
+    let addr_width = input.program.len().saturating_sub(1).to_string().len();
+    let label_width = labels.iter().map(|addr| label(*addr).len() + 1).max().unwrap_or(0);
+    for (addr, instruction) in input.program.iter().enumerate() {
+        let mut line = format!("{addr:>addr_width$}: ");
+        if label_width > 0 {
+            let label = match labels.contains(&addr) {
+                true => format!("{}:", label(addr)),
+                false => String::new(),
+            };
+            write!(line, "{label:label_width$} ").unwrap();
+        }
+        writeln!(out, "{line}{}", format_instruction(instruction, &labels)).unwrap();
+    }
+    Ok(out)
+}
+
+fn write_data(out: &mut String, words: &[Word]) {
+    for chunk in words.chunks(DATA_WORDS_PER_LINE) {
+        writeln!(out, ".data {}", chunk.iter().join(", ")).unwrap();
+    }
+}
+
+/// Whether every data section has a distinct name `.section` reads back.
+fn has_named_sections(input: &FamInput) -> bool {
+    let is_name = |name: &str| {
+        let mut chars = name.chars();
+        chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    };
+    let names: BTreeSet<&str> = input.data.iter().map(|section| section.name.as_str()).collect();
+    names.len() == input.data.len() && names.iter().all(|name| is_name(name))
+}
+
+/// Addresses of the instructions a `Jump` in `program` lands on.
+fn jump_targets(program: &[Instruction]) -> BTreeSet<usize> {
+    program.iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Jump { addr } => usize::try_from(*addr).ok(),
+            _ => None,
+        })
+        .filter(|addr| *addr < program.len())
+        .collect()
+}
+
+fn label(addr: usize) -> String {
+    format!("L{addr}")
+}
+
+/// One instruction in assembler syntax. Jumps to an address in `labels`
+/// use its label.
+pub fn format_instruction(instruction: &Instruction, labels: &BTreeSet<usize>) -> String {
+    match instruction {
+        Instruction::SetTaluConfig { talu_addr, talu_config } => format!("T{talu_addr} <- {}", format_operation(talu_config)),
+        Instruction::ResetAllTalus => "reset".to_string(),
+        Instruction::SetLiteral { literal, reg_addr } => format!("set {}, {literal}", reg(*reg_addr)),
+        Instruction::WaitForActivationSignal { register_index } => format!("wait {}", reg(*register_index)),
+        Instruction::Jump { addr } => match usize::try_from(*addr) {
+            Ok(target) if labels.contains(&target) => format!("jump {}", label(target)),
+            _ => format!("jump {addr}"),
+        },
+        Instruction::LoadTaluConfigFromRegisters { talu_addr_reg, config_reg } =>
+            format!("T[{}] <- {}", reg(*talu_addr_reg), reg(*config_reg)),
+        Instruction::LoadTaluConfigFromMemory { talu_addr_reg, mem_addr_reg } =>
+            format!("T[{}] <- [{}]", reg(*talu_addr_reg), reg(*mem_addr_reg)),
+        Instruction::NoOp => "nop".to_string(),
+    }
+}
+
+fn reg(addr: CpuRegisterAddress) -> String {
+    if addr == PROGRAM_COUNTER_REGISTER_ADDR {
+        PROGRAM_COUNTER_NAME.to_string()
+    } else {
+        format!("r{addr}")
+    }
+}
+
+/// A TALU configuration, as in `ADD r5, r1 -> r5 when r8`.
+pub fn format_operation(operation: &TaluOperation) -> String {
+    // Each arm gives the mnemonic with its modifiers, the inputs, the
+    // outputs up to the last one set, the activation and the activation
+    // output.
+    let (mnemonic, inputs, outputs, activation_input, activation_output): (String, Vec<String>, Vec<Option<CpuRegisterAddress>>, _, _) = match *operation {
+        TaluOperation::NoOp => return "NOP".to_string(),
+        TaluOperation::Cmp { op, activation_input, activation_output, data_input_0, data_input_1, data_output } =>
+            (name_of(&CMP_NAMES, op).to_string(), vec![reg(data_input_0), reg(data_input_1)], vec![Some(data_output)], activation_input, activation_output),
+        TaluOperation::Mov { activation_input, value_input, data_output, activation_output } =>
+            ("MOV".to_string(), vec![reg(value_input)], vec![Some(data_output)], activation_input, activation_output),
+        TaluOperation::Latch { activation_input, data_input, hold_input, data_output, activation_output } =>
+            ("LAT".to_string(), vec![reg(data_input), reg(hold_input)], vec![Some(data_output)], activation_input, activation_output),
+        TaluOperation::Not { activation_input, data_input, data_output, activation_output } =>
+            ("NOT".to_string(), vec![reg(data_input)], vec![Some(data_output)], activation_input, activation_output),
+        TaluOperation::And { activation_input, data_input_0, data_input_1, data_output_0, activation_output } =>
+            ("AND".to_string(), vec![reg(data_input_0), reg(data_input_1)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::Or { activation_input, data_input_0, data_input_1, data_output_0, activation_output } =>
+            ("OR".to_string(), vec![reg(data_input_0), reg(data_input_1)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::Xor { activation_input, data_input_1, data_input_0, data_output_0, activation_output } =>
+            ("XOR".to_string(), vec![reg(data_input_0), reg(data_input_1)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::ShiftLeft { activation_input, value, shift_count, data_output_0, activation_output } =>
+            ("SHL".to_string(), vec![reg(value), reg(shift_count)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::ShiftRight { activation_input, value, shift_count, data_output_0, activation_output } =>
+            ("SHR".to_string(), vec![reg(value), reg(shift_count)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::SelectPart { activation_input, data_input, selection_input, data_output_0, activation_output } =>
+            ("SEL".to_string(), vec![reg(data_input), reg(selection_input)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::Add { activation_input, data_input_1, data_input_0, result_output, flags_output, activation_output } =>
+            ("ADD".to_string(), vec![reg(data_input_0), reg(data_input_1)], vec![Some(result_output), flags_output], activation_input, activation_output),
+        TaluOperation::Sub { activation_input, data_input_1, data_input_0, data_output_0, flags_output, activation_output } =>
+            ("SUB".to_string(), vec![reg(data_input_0), reg(data_input_1)], vec![Some(data_output_0), flags_output], activation_input, activation_output),
+        TaluOperation::Mul { activation_input, data_input_1, data_input_0, first_word_output, second_word_output, activation_output } =>
+            ("MUL".to_string(), vec![reg(data_input_0), reg(data_input_1)], vec![Some(first_word_output), second_word_output], activation_input, activation_output),
+        TaluOperation::Div { activation_input, dividend, divisor, data_output_0, div_by_zero_flag_output, activation_output } =>
+            ("DIV".to_string(), vec![reg(dividend), reg(divisor)], vec![Some(data_output_0), div_by_zero_flag_output], activation_input, activation_output),
+        TaluOperation::Rem { activation_input, dividend, divisor, data_output_0, div_by_zero_flag_output, activation_output } =>
+            ("REM".to_string(), vec![reg(dividend), reg(divisor)], vec![Some(data_output_0), div_by_zero_flag_output], activation_input, activation_output),
+        TaluOperation::Neg { activation_input, input, data_output_0, activation_output } =>
+            ("NEG".to_string(), vec![reg(input)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::ReadFromMem { activation_input, address_input, data_output, activation_output } =>
+            ("READ".to_string(), vec![reg(address_input)], vec![Some(data_output)], activation_input, activation_output),
+        TaluOperation::WriteToMem { data_input, address_input, activation_input, activation_output } =>
+            ("WRIT".to_string(), vec![reg(data_input), reg(address_input)], vec![], activation_input, activation_output),
+        TaluOperation::Fixed { op, format, overflow, activation_input, data_input_0, data_input_1, data_output_0, flags_output, activation_output } => (
+            format!("{}.q{}{}", name_of(&FIXED_NAMES, op), format.frac_bits, overflow_suffix(overflow)),
+            vec![reg(data_input_0), reg(data_input_1)], vec![Some(data_output_0), flags_output], activation_input, activation_output,
+        ),
+        TaluOperation::Mux { activation_input, condition, if_true, if_false, data_output_0, activation_output } =>
+            ("MUX".to_string(), vec![reg(condition), reg(if_true), reg(if_false)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::Counter { activation_input, down_input, reset_input, data_output_0, activation_output } =>
+            ("CNT".to_string(), vec![reg(down_input), reg(reset_input)], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::Accumulator { activation_input, data_input, reset_input, data_output_0, flags_output, activation_output } =>
+            ("ACC".to_string(), vec![reg(data_input), reg(reset_input)], vec![Some(data_output_0), flags_output], activation_input, activation_output),
+        TaluOperation::Timer { activation_input, period_input, reset_input, count_output, activation_output } =>
+            ("TIM".to_string(), vec![reg(period_input), reg(reset_input)], vec![count_output], activation_input, Some(activation_output)),
+        TaluOperation::Lut { activation_input, data_input, table_addr, size_log2, data_output_0, activation_output } =>
+            ("LUT".to_string(), vec![reg(data_input), table_addr.to_string(), size_log2.to_string()], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::Delay { activation_input, data_input, steps, data_output_0, activation_output } =>
+            ("DLY".to_string(), vec![reg(data_input), steps.to_string()], vec![Some(data_output_0)], activation_input, activation_output),
+        TaluOperation::EdgeDetect { edge, activation_input, activation_output } =>
+            (name_of(&EDGE_NAMES, edge).to_string(), vec![], vec![], activation_input, Some(activation_output)),
+        TaluOperation::Simd { op, lanes, signed, overflow, activation_input, data_input_0, data_input_1, data_output_0, activation_output } => {
+            let name = match op {
+                SimdOp::Cmp(cmp) => format!("VCMP.{}", name_of(&CMP_NAMES, cmp).to_ascii_lowercase()),
+                _ => name_of(&SIMD_NAMES, op).to_string(),
+            };
+            let unsigned = if signed { "" } else { ".u" };
+            (
+                format!("{name}.{}{unsigned}{}", name_of(&LANE_NAMES, lanes), overflow_suffix(overflow)),
+                binary_inputs(op.is_unary(), data_input_0, data_input_1), vec![Some(data_output_0)], activation_input, activation_output,
+            )
+        }
+        TaluOperation::Float { op, activation_input, data_input_0, data_input_1, data_output_0, activation_output } => {
+            let name = match op {
+                FloatOp::Cmp(cmp) => format!("FCMP.{}", name_of(&CMP_NAMES, cmp).to_ascii_lowercase()),
+                _ => name_of(&FLOAT_NAMES, op).to_string(),
+            };
+            (name, binary_inputs(op.is_unary(), data_input_0, data_input_1), vec![Some(data_output_0)], activation_input, activation_output)
+        }
+    };
+
+    let mut text = mnemonic;
+    if !inputs.is_empty() {
+        write!(text, " {}", inputs.join(", ")).unwrap();
+    }
+    let set_outputs = outputs.iter().rposition(Option::is_some).map_or(0, |last| last + 1);
+    if set_outputs > 0 {
+        let outputs = outputs[..set_outputs].iter()
+            .map(|output| reg(output.expect("only trailing outputs are optional")))
+            .join(", ");
+        write!(text, " -> {outputs}").unwrap();
+    }
+    write!(text, " when {}", reg(activation_input)).unwrap();
+    if let Some(activation_output) = activation_output {
+        write!(text, " then {}", reg(activation_output)).unwrap();
+    }
+    text
+}
+
+fn overflow_suffix(overflow: OverflowMode) -> &'static str {
+    match overflow {
+        OverflowMode::Wrap => "",
+        OverflowMode::Saturate => ".sat",
+    }
+}
+
+/// Leaves out the unused second input of a unary operation, unless it was
+/// set to something other than `r0`.
+fn binary_inputs(unary: bool, data_input_0: CpuRegisterAddress, data_input_1: CpuRegisterAddress) -> Vec<String> {
+    if unary && data_input_1 == 0 {
+        vec![reg(data_input_0)]
+    } else {
+        vec![reg(data_input_0), reg(data_input_1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::asm::assemble;
+    use crate::application::examples::make_loop_program;
+    use crate::application::input::DataSection;
+    use crate::application::simulation::instruction_reader::CodeRegion;
+
+    fn examples() -> Vec<FamInput> {
+        let sample: FamInput = serde_json::from_str(include_str!("../../../sample.json")).unwrap();
+
+        let mut everything = make_loop_program();
+        everything.propagation = PropagationModel::Combinational;
+        everything.registers.insert(7, -5);
+        everything.talu_latencies.default = 1;
+        everything.talu_latencies.per_operation.insert("Div".to_string(), 4);
+        everything.data.push(DataSection { name: "table".to_string(), addr: Some(40), words: (0..20).collect(), len: None });
+        everything.program.push(Instruction::Jump { addr: 3 });
+
+        let von_neumann = FamInput {
+            code_region: Some(CodeRegion { base: 64, len: everything.program.len() }),
+            ..everything.clone()
+        };
+        vec![sample, make_loop_program(), everything, von_neumann]
+    }
+
+    #[test]
+    fn listings_assemble_back() {
+        for input in examples() {
+            let listing = disassemble(&input).unwrap();
+            assert_eq!(assemble(&listing), Ok(input), "{listing}");
+        }
+    }
+
+    #[test]
+    fn sections_keep_their_names() {
+        let listing = disassemble(&make_loop_program()).unwrap();
+        assert!(listing.contains(".section source\n.data 1, 2, 3, 4, 5\n"), "{listing}");
+        assert!(listing.contains(".section target len = 6\n"), "{listing}");
+    }
+
+    #[test]
+    fn sections_without_a_usable_name_go_into_main_memory() {
+        for name in ["two words", "source"] {
+            let mut input = make_loop_program();
+            input.data.push(DataSection { name: name.to_string(), words: vec![9], ..Default::default() });
+            let flattened = FamInput { main_memory: input.memory_with_data().unwrap(), data: Vec::new(), ..input.clone() };
+
+            let listing = disassemble(&input).unwrap();
+            assert!(!listing.contains(".section"), "{listing}");
+            assert_eq!(assemble(&listing), Ok(flattened));
+        }
+    }
+}
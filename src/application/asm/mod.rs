@@ -0,0 +1,6 @@
+pub mod syntax;
+pub mod assembler;
+pub mod disassembler;
+
+pub use assembler::{assemble, AsmError};
+pub use disassembler::disassemble;
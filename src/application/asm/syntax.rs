@@ -0,0 +1,85 @@
+use crate::application::simulation::talu::{CmpOp, Edge, FixedOp, FloatOp, LaneWidth, SimdOp};
+
+/// Register name for the program counter, next to `r0`..`r63`.
+pub const PROGRAM_COUNTER_NAME: &str = "pc";
+
+/// Words that can't be used as `.reg` symbols or labels.
+pub const KEYWORDS: [&str; 7] = ["when", "then", "set", "wait", "jump", "reset", "nop"];
+
+pub const CMP_NAMES: [(CmpOp, &str); 6] = [
+    (CmpOp::LessThan, "LT"),
+    (CmpOp::LessThanOrEq, "LE"),
+    (CmpOp::GreaterThan, "GT"),
+    (CmpOp::GreaterThanOrEq, "GE"),
+    (CmpOp::Eq, "EQ"),
+    (CmpOp::NotEq, "NEQ"),
+];
+
+pub const FIXED_NAMES: [(FixedOp, &str); 3] = [
+    (FixedOp::Mul, "QMUL"),
+    (FixedOp::Div, "QDIV"),
+    (FixedOp::RoundingShift, "QSHR"),
+];
+
+pub const EDGE_NAMES: [(Edge, &str); 3] = [
+    (Edge::Rising, "EDGR"),
+    (Edge::Falling, "EDGF"),
+    (Edge::Both, "EDGE"),
+];
+
+/// `SimdOp::Cmp` is `VCMP` with a comparison modifier, e.g. `VCMP.lt.8`.
+pub const SIMD_NAMES: [(SimdOp, &str); 3] = [
+    (SimdOp::Add, "VADD"),
+    (SimdOp::Sub, "VSUB"),
+    (SimdOp::HorizontalSum, "VSUM"),
+];
+
+/// `FloatOp::Cmp` is `FCMP` with a comparison modifier, e.g. `FCMP.ge`.
+pub const FLOAT_NAMES: [(FloatOp, &str); 6] = [
+    (FloatOp::Add, "FADD"),
+    (FloatOp::Sub, "FSUB"),
+    (FloatOp::Mul, "FMUL"),
+    (FloatOp::Div, "FDIV"),
+    (FloatOp::FromInt, "ITOF"),
+    (FloatOp::ToInt, "FTOI"),
+];
+
+pub const LANE_NAMES: [(LaneWidth, &str); 2] = [
+    (LaneWidth::Bits8, "8"),
+    (LaneWidth::Bits16, "16"),
+];
+
+/// Looks `name` up in one of the tables above, ignoring case.
+pub fn lookup<T: Copy>(table: &[(T, &str)], name: &str) -> Option<T> {
+    table.iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|(value, _)| *value)
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary number, with an
+/// optional leading `-` and `_` separators.
+pub fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let digits = digits.replace('_', "");
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x" | "0X") => (16, &digits[2..]),
+        Some("0b" | "0B") => (2, &digits[2..]),
+        _ => (10, &digits[..]),
+    };
+    if digits.is_empty() || digits.starts_with(['+', '-']) {
+        return None;
+    }
+    let magnitude = i64::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// The name of `value` in one of the tables above.
+pub fn name_of<T: PartialEq>(table: &[(T, &'static str)], value: T) -> &'static str {
+    table.iter()
+        .find(|(v, _)| *v == value)
+        .map(|(_, name)| *name)
+        .expect("every value has a name")
+}
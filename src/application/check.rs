@@ -0,0 +1,316 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use crate::application::input::FamInput;
+use crate::application::simulation::cpu_registers::{CpuRegisterAddress, REGISTER_COUNT};
+use crate::application::simulation::encoding::encode_instruction;
+use crate::application::simulation::instruction::Instruction;
+use crate::application::simulation::propagation::{evaluation_order_of, CombinationalLoop, PropagationModel};
+use crate::application::simulation::talu::{TALU_COUNT, TaluOperation, TaluPortsConfig};
+use crate::PROGRAM_COUNTER_REGISTER_ADDR;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, serde::Serialize)]
+pub enum Severity {
+    Warning,
+    /// The simulation would panic or can't behave as written.
+    Error,
+}
+
+/// A problem found by `check`, at the index of the instruction causing it,
+/// or `None` for the registers and data sections of the input.
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize)]
+pub struct Diagnostic {
+    pub severity    : Severity,
+    pub instruction : Option<usize>,
+    pub message     : String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.instruction {
+            Some(instruction) => write!(f, "{severity}: instruction {instruction}: {}", self.message),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+/// Finds mistakes in a program without running it: first in its
+/// registers and data sections, then in instruction order.
+pub fn check(input: &FamInput) -> Vec<Diagnostic> {
+    let mut diagnostics = check_data(input);
+    let mut report = |severity, instruction, message| diagnostics.push(Diagnostic { severity, instruction: Some(instruction), message });
+
+    let written_by_talus: BTreeSet<CpuRegisterAddress> = input.program.iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::SetTaluConfig { talu_config, .. } => Some(outputs(&talu_config.get_ports_config())),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    // Registers written by TALUs configured at run time aren't known here.
+    let loads_configs = input.program.iter().any(|instruction| matches!(instruction,
+        Instruction::LoadTaluConfigFromRegisters { .. } | Instruction::LoadTaluConfigFromMemory { .. }
+    ));
+
+    // In von Neumann mode jumps may land on any instruction of the code
+    // region, past the end of the program loaded into it.
+    let instruction_count = match input.code_region {
+        Some(region) => region.len,
+        None => input.program.len(),
+    };
+    if let Some(region) = input.code_region
+        && input.program.len() > region.len
+    {
+        report(Severity::Error, region.len, format!(
+            "the program of {} instructions doesn't fit its code region of {}",
+            input.program.len(),
+            region.len,
+        ));
+    }
+
+    // `check_data` reports a data section or code region too far out.
+    let memory_len = input.memory_len().unwrap_or(input.main_memory.len());
+
+    // TALU configurations as the program sets them, ignoring jumps, for
+    // finding combinational loops.
+    let mut operations = [TaluOperation::NoOp; TALU_COUNT];
+    let mut combinational_loop: Option<CombinationalLoop> = None;
+
+    for (ix, instruction) in input.program.iter().enumerate() {
+        if input.code_region.is_some()
+            && let Err(err) = encode_instruction(instruction)
+        {
+            report(Severity::Error, ix, format!("can't be loaded into main memory: {err}"));
+        }
+        match *instruction {
+            Instruction::SetTaluConfig { talu_addr, talu_config } => {
+                if talu_addr >= TALU_COUNT {
+                    report(Severity::Error, ix, format!("TALU {talu_addr} doesn't exist, there are {TALU_COUNT}"));
+                }
+                if let Some(err) = talu_config.parameter_error() {
+                    report(Severity::Error, ix, format!("{} on TALU {talu_addr}: {err}", talu_config.kind_name()));
+                } else if let TaluOperation::Lut { table_addr, size_log2, .. } = talu_config
+                    && table_addr.saturating_add(1 << size_log2) > memory_len
+                {
+                    report(Severity::Warning, ix, format!(
+                        "Lut on TALU {talu_addr} reads its table from {table_addr} to {}, past the end of main memory at {memory_len}, as zeros",
+                        table_addr.saturating_add(1 << size_log2),
+                    ));
+                }
+                let ports = talu_config.get_ports_config();
+                for reg in inputs(&ports).chain(outputs(&ports)) {
+                    if reg >= REGISTER_COUNT {
+                        report(Severity::Error, ix, format!("register {reg} doesn't exist, there are {REGISTER_COUNT}"));
+                    }
+                }
+                if outputs(&ports).any(|reg| reg == PROGRAM_COUNTER_REGISTER_ADDR) {
+                    report(Severity::Warning, ix, format!(
+                        "{} on TALU {talu_addr} writes the program counter r{PROGRAM_COUNTER_REGISTER_ADDR}",
+                        talu_config.kind_name(),
+                    ));
+                }
+                if input.propagation == PropagationModel::Combinational && talu_addr < TALU_COUNT {
+                    operations[talu_addr] = talu_config;
+                    let (_, new_loop) = evaluation_order_of(&operations);
+                    if let Some(new_loop) = &new_loop
+                        && combinational_loop.as_ref() != Some(new_loop)
+                    {
+                        report(Severity::Warning, ix, format!("{new_loop}: they run in address order after the other TALUs"));
+                    }
+                    combinational_loop = new_loop;
+                }
+            }
+            Instruction::SetLiteral { reg_addr, .. } => {
+                if reg_addr >= REGISTER_COUNT {
+                    report(Severity::Error, ix, format!("register {reg_addr} doesn't exist, there are {REGISTER_COUNT}"));
+                }
+            }
+            Instruction::WaitForActivationSignal { register_index } => {
+                if register_index >= REGISTER_COUNT {
+                    report(Severity::Error, ix, format!("register {register_index} doesn't exist, there are {REGISTER_COUNT}"));
+                } else if !loads_configs && !written_by_talus.contains(&register_index) {
+                    report(Severity::Warning, ix, format!("waits on r{register_index}, which no TALU writes"));
+                }
+            }
+            Instruction::Jump { addr } => {
+                if usize::try_from(addr).is_err() || addr as usize >= instruction_count {
+                    report(Severity::Error, ix, format!(
+                        "jumps to {addr}, outside the program of {instruction_count} instructions",
+                    ));
+                }
+            }
+            Instruction::LoadTaluConfigFromRegisters { talu_addr_reg, config_reg } => {
+                for reg in [talu_addr_reg, config_reg] {
+                    if reg >= REGISTER_COUNT {
+                        report(Severity::Error, ix, format!("register {reg} doesn't exist, there are {REGISTER_COUNT}"));
+                    }
+                }
+                if config_reg == REGISTER_COUNT - 1 {
+                    report(Severity::Error, ix, format!("no register after r{config_reg} for the configuration's high word"));
+                }
+            }
+            Instruction::LoadTaluConfigFromMemory { talu_addr_reg, mem_addr_reg } => {
+                for reg in [talu_addr_reg, mem_addr_reg] {
+                    if reg >= REGISTER_COUNT {
+                        report(Severity::Error, ix, format!("register {reg} doesn't exist, there are {REGISTER_COUNT}"));
+                    }
+                }
+            }
+            Instruction::ResetAllTalus => {
+                operations = [TaluOperation::NoOp; TALU_COUNT];
+                combinational_loop = None;
+            }
+            Instruction::NoOp => {}
+        }
+    }
+    diagnostics
+}
+
+fn check_data(input: &FamInput) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |message| diagnostics.push(Diagnostic { severity: Severity::Error, instruction: None, message });
+
+    for name in input.talu_latencies.unknown_operations() {
+        report(format!("latency for `{name}`, which isn't a TALU operation"));
+    }
+
+    for reg in input.registers.keys() {
+        if *reg >= REGISTER_COUNT {
+            report(format!("initial value for register {reg}, which doesn't exist, there are {REGISTER_COUNT}"));
+        }
+    }
+
+    let addresses = input.data_addresses();
+    for (ix, (section, addr)) in input.data.iter().zip(addresses.iter()).enumerate() {
+        let name = &section.name;
+        if input.data[..ix].iter().any(|other| other.name == *name) {
+            report(format!("two data sections are named `{name}`"));
+        }
+        if let Some(len) = section.len
+            && len < section.words.len()
+        {
+            report(format!("data section `{name}` has {} words, more than its length of {len}", section.words.len()));
+        }
+        let end = addr.saturating_add(section.size());
+        for (other, other_addr) in input.data[..ix].iter().zip(addresses.iter()) {
+            if *addr < other_addr.saturating_add(other.size()) && *other_addr < end {
+                report(format!("data sections `{}` and `{name}` overlap", other.name));
+            }
+        }
+        if let Some(region) = input.code_region
+            && *addr < region.end()
+            && region.base < end
+        {
+            report(format!("data section `{name}` overlaps the code region"));
+        }
+    }
+    if let Err(err) = input.memory_len() {
+        report(err.to_string());
+    }
+    diagnostics
+}
+
+fn inputs(ports: &TaluPortsConfig) -> impl Iterator<Item = CpuRegisterAddress> + use<> {
+    [ports.data_input_0, ports.data_input_1, ports.data_input_2, ports.activation_input].into_iter().flatten()
+}
+
+fn outputs(ports: &TaluPortsConfig) -> impl Iterator<Item = CpuRegisterAddress> + use<> {
+    [ports.data_output_0, ports.data_output_1, ports.activation_output].into_iter().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::input::DataSection;
+    use crate::application::simulation::instruction_reader::CodeRegion;
+
+    fn at(severity: Severity, instruction: usize, message: &str) -> Diagnostic {
+        Diagnostic { severity, instruction: Some(instruction), message: message.to_string() }
+    }
+
+    fn error(message: &str) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, instruction: None, message: message.to_string() }
+    }
+
+    fn mov(data_output: CpuRegisterAddress) -> TaluOperation {
+        TaluOperation::Mov { activation_input: 1, value_input: 2, data_output, activation_output: None }
+    }
+
+    #[test]
+    fn finds_mistakes_in_instructions() {
+        let input = FamInput {
+            program: vec![
+                Instruction::SetTaluConfig { talu_addr: TALU_COUNT, talu_config: mov(3) },
+                Instruction::SetLiteral { literal: 1, reg_addr: REGISTER_COUNT },
+                Instruction::SetTaluConfig { talu_addr: 0, talu_config: mov(PROGRAM_COUNTER_REGISTER_ADDR) },
+                Instruction::Jump { addr: 7 },
+                Instruction::WaitForActivationSignal { register_index: 20 },
+                Instruction::WaitForActivationSignal { register_index: 3 },
+                Instruction::Jump { addr: -1 },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(check(&input), [
+            at(Severity::Error, 0, "TALU 32 doesn't exist, there are 32"),
+            at(Severity::Error, 1, "register 64 doesn't exist, there are 64"),
+            at(Severity::Warning, 2, "Mov on TALU 0 writes the program counter r63"),
+            at(Severity::Error, 3, "jumps to 7, outside the program of 7 instructions"),
+            at(Severity::Warning, 4, "waits on r20, which no TALU writes"),
+            at(Severity::Error, 6, "jumps to -1, outside the program of 7 instructions"),
+        ]);
+    }
+
+    #[test]
+    fn finds_registers_past_the_last_in_talus() {
+        let input = FamInput {
+            program: vec![
+                Instruction::SetTaluConfig { talu_addr: 1, talu_config: mov(REGISTER_COUNT) },
+                Instruction::LoadTaluConfigFromRegisters { talu_addr_reg: 1, config_reg: REGISTER_COUNT - 1 },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(check(&input), [
+            at(Severity::Error, 0, "register 64 doesn't exist, there are 64"),
+            at(Severity::Error, 1, "no register after r63 for the configuration's high word"),
+        ]);
+    }
+
+    #[test]
+    fn jumps_may_land_anywhere_in_the_code_region() {
+        let input = FamInput {
+            program: vec![Instruction::Jump { addr: 5 }],
+            code_region: Some(CodeRegion { base: 0, len: 6 }),
+            ..Default::default()
+        };
+        assert_eq!(check(&input), []);
+    }
+
+    #[test]
+    fn finds_mistakes_in_data_sections() {
+        let section = |name: &str, addr, words: Vec<i32>, len| DataSection { name: name.to_string(), addr, words, len };
+        let mut input = FamInput {
+            program: vec![Instruction::NoOp],
+            code_region: Some(CodeRegion { base: 100, len: 4 }),
+            data: vec![
+                section("table", Some(10), vec![1, 2, 3], Some(2)),
+                section("other", Some(12), vec![4], None),
+                section("table", Some(50), vec![], Some(1)),
+                section("code", Some(104), vec![5], None),
+            ],
+            ..Default::default()
+        };
+        input.registers.insert(REGISTER_COUNT, 1);
+        input.talu_latencies.per_operation.insert("Divide".to_string(), 2);
+        assert_eq!(check(&input), [
+            error("latency for `Divide`, which isn't a TALU operation"),
+            error("initial value for register 64, which doesn't exist, there are 64"),
+            error("data section `table` has 3 words, more than its length of 2"),
+            error("data sections `table` and `other` overlap"),
+            error("two data sections are named `table`"),
+            error("data section `code` overlaps the code region"),
+        ]);
+    }
+}
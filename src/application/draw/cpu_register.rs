@@ -19,6 +19,8 @@ use crate::application::grid::blocked_point::BlockedPoints;
 use crate::application::grid::component::{SimpleComponentGridData, DrawableComponent};
 use crate::application::grid::rect::{grid_rect, GridRect};
 use crate::application::simulation::cpu_registers::{CpuRegister, CpuRegisterBank, CpuRegisterPortName, CpuRegisterPortsData, REGISTER_COUNT};
+use crate::application::simulation::talu::float;
+use crate::word::Word;
 
 #[derive(Clone, PartialEq, Eq, Debug, Hash,)]
 pub struct CpuRegisterDrawingDefn {
@@ -29,11 +31,35 @@ impl Default for CpuRegisterDrawingDefn {
         Self{size: size(60, 30),}
     }
 }
+/// How register contents are printed, toggled from the application.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub enum RegisterValueFormat {
+    #[default]
+    Hex,
+    /// The word read as an IEEE-754 single.
+    Float,
+}
+
+impl RegisterValueFormat {
+    pub fn toggled(&self) -> Self {
+        match self {
+            Self::Hex   => Self::Float,
+            Self::Float => Self::Hex,
+        }
+    }
+    pub fn format(&self, value: Word) -> String {
+        match self {
+            Self::Hex   => format!("{:X}", value),
+            Self::Float => format!("{:.2e}", float::from_word(value)),
+        }
+    }
+}
+
 pub type CpuRegisterBankDrawingDefns = ComponentBankDrawingDefn<CpuRegisterDrawingDefn>;
 pub type CpuRegisterBankPortName    = ComponentBankPortName<CpuRegisterPortName, REGISTER_COUNT>;
 
 impl DrawableComponent for CpuRegister{
-    type DrawingState = ();
+    type DrawingState = RegisterValueFormat;
     type DrawingDefn = CpuRegisterDrawingDefn;
     type PortName = CpuRegisterPortName;
     type PortDataContainer = CpuRegisterPortsData;
@@ -91,7 +117,7 @@ impl DrawableComponent for CpuRegister{
 
     fn draw(
         &self,
-        _           : &Self::DrawingState,
+        value_format: &Self::DrawingState,
         grid_data   : &SimpleComponentGridData<Self::PortName, Self::PortDataContainer, Self::PortGridDataContainer>,
         drawing_data        : &Self::DrawingDefn,
         port_drawing_info   : &PortDrawingDefns,
@@ -169,7 +195,7 @@ impl DrawableComponent for CpuRegister{
                 let cursor = cursor.after_advancing(cursor.remaining_size().with_x(0)/2);
 
                 draw_text_line_normal(
-                    &format!("{:X}", self.value),
+                    &value_format.format(self.value),
                     (cursor.top_left() - dist(0, normal_font::DIMS.full_height() as i32 / 2) ),
                     1,
                     WHITE
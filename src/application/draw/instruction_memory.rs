@@ -12,8 +12,10 @@ use crate::application::grid::blocked_point::BlockedPoints;
 use crate::application::grid::component::{DrawableComponent, PortName, SimpleComponentGridData};
 use crate::application::grid::pos::GridPos;
 use crate::application::grid::rect::grid_rect;
-use crate::application::simulation::instruction_reader::InstructionMemory;
-use macroquad::color::{BLACK, DARKGRAY, GRAY, WHITE};
+use crate::application::simulation::encoding::{decode_instruction, from_words};
+use crate::application::simulation::instruction_reader::{CodeRegion, InstructionMemory, WORDS_PER_INSTRUCTION};
+use crate::word::Word;
+use macroquad::color::{BLACK, DARKBLUE, DARKGRAY, GRAY, WHITE};
 use std::collections::HashMap;
 use std::marker::PhantomData;
 use wgpu::naga::FastHashMap;
@@ -33,8 +35,17 @@ impl PortName for Never {
 }
 pub type InstructionMemoryCurrentPosition = usize;
 
+/// What the panel shows, from the instruction being executed on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionMemoryDrawingState {
+    pub current_pos : InstructionMemoryCurrentPosition,
+    /// In von Neumann mode, the code region and the words in it as they
+    /// are now, drawn in place of the program.
+    pub code        : Option<(CodeRegion, Vec<Word>)>,
+}
+
 impl DrawableComponent for InstructionMemory {
-    type DrawingState = InstructionMemoryCurrentPosition;
+    type DrawingState = InstructionMemoryDrawingState;
     type DrawingDefn = InstructionMemoryDrawingDefns;
     type PortName = InstructionMemoryPortName;
     type PortDataContainer = FastHashMap<Never, PortDefns>;
@@ -82,15 +93,37 @@ impl DrawableComponent for InstructionMemory {
         let title_dims = draw_title("Instruction Memory", top_left, 2, BLACK);
 
         cursor.advance(Dist::new(0, title_dims.height as ScreenUnit * 2 + 2));
+        if let Some((region, _)) = &drawing_state.code {
+            draw_text_pos(
+                &format!("from main memory {}..{}", region.base, region.end()),
+                cursor.top_left(),
+                TextStyle::Normal,
+                1,
+                DARKBLUE,
+            );
+            cursor.advance(dist(0, normal_font::DIMS.full_height() + 4));
+        }
         let initial_cursor = cursor.clone();
         {
-            let mut current_cell_ix = *drawing_state;
+            let mut current_cell_ix = drawing_state.current_pos;
             loop {
-                let Some(current_instruction) = self.0.get(current_cell_ix).cloned() else {
-                    break;
+                let instruction_value_text = match &drawing_state.code {
+                    None => match self.0.get(current_cell_ix) {
+                        Some(instruction) => format!("{:#?}", instruction),
+                        None => break,
+                    },
+                    Some((region, words)) => {
+                        let Some(addr) = region.address_of(current_cell_ix as Word) else {
+                            break;
+                        };
+                        let offset = addr - region.base;
+                        let encoded = from_words([words[offset], words[offset + 1]]);
+                        match decode_instruction(encoded) {
+                            Ok(instruction) => format!("{:#?}", instruction),
+                            Err(err) => format!("{encoded:#018x}\n{err}"),
+                        }
+                    }
                 };
-
-                let instruction_value_text = format!("{:#?}", current_instruction);
                 let font_dims = normal_font::DIMS;
 
                 // draw top line
@@ -105,12 +138,20 @@ impl DrawableComponent for InstructionMemory {
                     // draw address
                     let mut cursor = cursor.split(font_dims.full_height() + 4, Axis::Vertical);
 
-                    draw_rectangle_pos(cursor.top_left(), cursor.remaining_size(), DARKGRAY);
+                    let (color, addr_text) = match &drawing_state.code {
+                        None => (DARKGRAY, format!("ADDR {:}", current_cell_ix)),
+                        Some((region, _)) => (DARKBLUE, format!(
+                            "ADDR {:} @ {}",
+                            current_cell_ix,
+                            region.base + current_cell_ix * WORDS_PER_INSTRUCTION,
+                        )),
+                    };
+                    draw_rectangle_pos(cursor.top_left(), cursor.remaining_size(), color);
 
                     cursor.pad(0, 2);
 
                     draw_text_pos(
-                        &format!("ADDR {:}", current_cell_ix),
+                        &addr_text,
                         cursor.top_left() + dist(2, 2),
                         TextStyle::Normal,
                         1,
@@ -1,4 +1,4 @@
-use crate::application::simulation::talu::{CmpOp, TALU_COUNT, TaluAddress, TaluState};
+use crate::application::simulation::talu::{CmpOp, Edge, FixedOp, FloatOp, SimdOp, TALU_COUNT, TaluAddress, TaluBank, TaluState};
 use crate::application::direction::Direction;
 use crate::application::direction::Axis::Vertical;
 use crate::application::draw::component_bank::{ComponentBankDrawingDefn, ComponentBankGridData};
@@ -6,7 +6,8 @@ use crate::application::draw::grid_to_screen::GridScreenTransformer;
 use crate::application::draw::port::{draw_port, PortDrawingDefns, PortGridDefns};
 use crate::application::draw::pos::{dist, pos, size, ScreenUnit, Size};
 use crate::application::draw::shapes::{draw_circle_pos, draw_rectangle_pos};
-use crate::application::draw::text::{draw_text_line_tiny, draw_title};
+use crate::application::draw::cursor::RectCursor;
+use crate::application::draw::text::{draw_text_line_tiny, draw_title, tiny_font};
 use crate::application::grid::talu::{TaluGridDefns, TaluPortsGridDefns};
 use crate::application::grid::blocked_point::BlockedPoints;
 use crate::application::grid::component::{DrawableComponent, PortDataContainer, PortName, SimpleComponentGridData};
@@ -15,7 +16,9 @@ use crate::application::grid::rect::grid_rect;
 use crate::application::simulation::talu::{TaluCore, TaluOperation, TaluPortName, TaluPortsDefns};
 use crate::tools::used_in::UsedIn;
 use itertools::Itertools;
-use macroquad::color::{BLACK, Color, GRAY, GREEN, LIGHTGRAY, WHITE, YELLOW};
+use macroquad::color::{BLACK, Color, GRAY, GREEN, LIGHTGRAY, ORANGE, WHITE, YELLOW};
+use macroquad::input::mouse_position;
+use macroquad::window::{screen_height, screen_width};
 use wgpu::naga::FastHashMap;
 use std::marker::PhantomData;
 use std::ops::Index;
@@ -110,6 +113,11 @@ impl DrawableComponent for TaluCore {
                         direction: Direction::Left,
                     },
 
+                    data_in_2: PortGridDefns {
+                        position: grid_pos(left_x, ys[4]),
+                        direction: Direction::Left,
+                    },
+
                     activation_in: PortGridDefns {
                         position: grid_pos(left_x, ys[3]),
                         direction: Direction::Left,
@@ -227,6 +235,7 @@ impl DrawableComponent for TaluCore {
                 TaluState::JustProcessed => GREEN,
                 TaluState::Closing => YELLOW,
                 TaluState::Done => GRAY,
+                TaluState::Busy { .. } => ORANGE,
             };
 
             let center =cursor.top_left() +  Direction::Right * radius + Direction::Down * radius ; 
@@ -234,6 +243,15 @@ impl DrawableComponent for TaluCore {
                 &center , 
                 radius as f32, circle_color
             );
+
+            if let TaluState::Busy { remaining } = self.state {
+                draw_text_line_tiny(
+                    &format!("{}", remaining),
+                    center + Direction::Right * (radius * 2) - Direction::Down * radius,
+                    1,
+                    BLACK,
+                );
+            }
         }
 
         { // draw operation text
@@ -271,6 +289,37 @@ impl DrawableComponent for TaluCore {
                     TaluOperation::Neg { .. } => { "NEG" }
                     TaluOperation::ReadFromMem { .. } => { "READ" }
                     TaluOperation::WriteToMem { .. } => { "WRIT" }
+                    TaluOperation::Fixed { op, .. } => match op {
+                        FixedOp::Mul => "QMUL",
+                        FixedOp::Div => "QDIV",
+                        FixedOp::RoundingShift => "QSHR",
+                    },
+                    TaluOperation::Mux { .. } => { "MUX" }
+                    TaluOperation::Counter { .. } => { "CNT" }
+                    TaluOperation::Accumulator { .. } => { "ACC" }
+                    TaluOperation::Timer { .. } => { "TIM" }
+                    TaluOperation::Lut { .. } => { "LUT" }
+                    TaluOperation::Delay { .. } => { "DLY" }
+                    TaluOperation::EdgeDetect { edge, .. } => match edge {
+                        Edge::Rising => "EDGR",
+                        Edge::Falling => "EDGF",
+                        Edge::Both => "EDGE",
+                    },
+                    TaluOperation::Simd { op, .. } => match op {
+                        SimdOp::Add => "VADD",
+                        SimdOp::Sub => "VSUB",
+                        SimdOp::Cmp(_) => "VCMP",
+                        SimdOp::HorizontalSum => "VSUM",
+                    },
+                    TaluOperation::Float { op, .. } => match op {
+                        FloatOp::Add => "FADD",
+                        FloatOp::Sub => "FSUB",
+                        FloatOp::Mul => "FMUL",
+                        FloatOp::Div => "FDIV",
+                        FloatOp::Cmp(_) => "FCMP",
+                        FloatOp::FromInt => "ITOF",
+                        FloatOp::ToInt => "FTOI",
+                    },
                 }
             };
 
@@ -294,6 +343,57 @@ impl DrawableComponent for TaluCore {
     }
 }
 
+/// Draws the lookup table of the TALU under the mouse, if it holds one.
+pub fn draw_talu_tooltip(
+    talu_bank       : &TaluBank,
+    grid_defns      : &TaluBankGridDefns,
+    grid_to_screen  : &GridScreenTransformer,
+) {
+    const ROWS_PER_COLUMN: usize = 32;
+    const COLUMN_WIDTH: ScreenUnit = 56;
+    let line_height = tiny_font::DIMS.full_height() + 1;
+
+    let (mouse_x, mouse_y) = mouse_position();
+    let mouse = pos(mouse_x as ScreenUnit, mouse_y as ScreenUnit);
+
+    let hovered = talu_bank.components.iter().zip(grid_defns.comp_grid_datas.iter())
+        .find(|(_, talu_grid)| {
+            let cursor = grid_to_screen.get_cursor_for_region(
+                talu_grid.grid_rect.top_left,
+                talu_grid.grid_rect.size,
+            );
+            let bottom_right = cursor.bottom_right();
+            cursor.top_left().cmple(mouse).all() && mouse.cmplt(bottom_right).all()
+        });
+
+    let Some((talu, _)) = hovered else { return };
+    if talu.table.is_empty() { return; }
+
+    let rows = talu.table.len().min(ROWS_PER_COLUMN);
+    let columns = talu.table.len().div_ceil(ROWS_PER_COLUMN);
+    let panel_size = size(
+        columns as ScreenUnit * COLUMN_WIDTH + 4,
+        rows as ScreenUnit * line_height + 4,
+    );
+    let top_left = pos(
+        (mouse.x + 12).min(screen_width() as ScreenUnit - panel_size.x),
+        (mouse.y + 12).min(screen_height() as ScreenUnit - panel_size.y),
+    );
+
+    let mut cursor = RectCursor::new(top_left, panel_size);
+    cursor.draw_rect(WHITE);
+    cursor.draw_rect_lines(BLACK, 1.);
+    cursor.pad(2, 2);
+
+    for (ix, value) in talu.table.iter().enumerate() {
+        let line_pos = cursor.top_left() + dist(
+            (ix / ROWS_PER_COLUMN) as ScreenUnit * COLUMN_WIDTH,
+            (ix % ROWS_PER_COLUMN) as ScreenUnit * line_height,
+        );
+        draw_text_line_tiny(&format!("{:3}: {:X}", ix, value), line_pos, 1, BLACK);
+    }
+}
+
 pub type TaluBankDrawingDefns = ComponentBankDrawingDefn<TaluDrawingDefns>;
 pub type TaluBankGridDefns = ComponentBankGridData<TaluCore, { TALU_COUNT }>;
 
@@ -0,0 +1,188 @@
+//! Example programs, run by the tests.
+
+use crate::application::input::{DataSection, FamInput};
+use crate::application::simulation::instruction::Instruction;
+use crate::application::simulation::talu::{CmpOp, TaluOperation};
+use crate::word::Word;
+
+/// Copies the five words of the `source` section to `target`, one per
+/// loop iteration, then waits for the loop to finish.
+pub fn make_loop_program() -> FamInput{
+    let mut input = FamInput {
+        data: vec![
+            DataSection { name: "source".to_string(), words: vec![1, 2, 3, 4, 5], ..Default::default() },
+            DataSection { name: "target".to_string(), len: Some(6), ..Default::default() },
+        ],
+        ..Default::default()
+    };
+    let source_addr = input.section_address("source").unwrap() as Word;
+    let target_addr = input.section_address("target").unwrap() as Word;
+
+    let loop_count_reg = 2;
+    let start_reg = 3;
+    let finished_reg = 30;
+    let ix_reg = 5;
+    let ix_lt_loop_reg = 6;
+
+    let start_inner_reg = 8;
+    let source_addr_reg = 9;
+    let target_addr_reg = 10;
+
+    let word_reg = 11;
+    let word_ready_reg = 12;
+    let write_finished_reg = 13;
+    let is_final_reg = 14;
+
+    let program = Vec::from_iter([
+        Instruction::SetLiteral {
+            literal: 0,
+            reg_addr: 0,
+        },
+        Instruction::SetLiteral {
+            literal: 1,
+            reg_addr: 1,
+        },
+        Instruction::SetLiteral {
+            literal: 5,
+            reg_addr: 2,
+        }, // loop_count
+        Instruction::SetLiteral {
+            literal: 0,
+            reg_addr: 3,
+        }, // start
+        Instruction::SetLiteral {
+            literal: 0,
+            reg_addr: 4,
+        }, // finished
+        Instruction::SetLiteral {
+            literal: 0,
+            reg_addr: 5,
+        }, // i
+        Instruction::SetTaluConfig {
+            talu_addr: 0,
+            talu_config: TaluOperation::Cmp {
+                op: CmpOp::LessThan,
+                activation_input: 1,
+                activation_output: None,
+                data_input_0: ix_reg,
+                data_input_1: loop_count_reg,
+                data_output: ix_lt_loop_reg,
+            },
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 1,
+            talu_config: TaluOperation::And {
+                data_input_0: start_reg,
+                data_input_1: ix_lt_loop_reg,
+                activation_input: 1,
+                data_output_0: start_inner_reg,
+                activation_output: None,
+            },
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 2,
+            talu_config: TaluOperation::Add {
+                data_input_0: ix_reg,
+                data_input_1: 1,
+                activation_input: start_inner_reg,
+
+                result_output: ix_reg,
+                flags_output: None,
+                activation_output: None,
+            },
+        },
+        Instruction::SetLiteral {
+            literal: source_addr,
+            reg_addr: source_addr_reg,
+        },
+        Instruction::SetLiteral {
+            literal: target_addr,
+            reg_addr: target_addr_reg,
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 3,
+            talu_config: TaluOperation::ReadFromMem {
+                activation_input: start_inner_reg,
+                address_input: source_addr_reg,
+                data_output: word_reg,
+                activation_output: Some(word_ready_reg),
+            },
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 4,
+            talu_config: TaluOperation::WriteToMem {
+                address_input: target_addr_reg,
+                data_input: word_reg,
+
+                activation_input: word_ready_reg,
+                activation_output: Some(write_finished_reg),
+            },
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 5,
+            talu_config: TaluOperation::Add {
+                activation_input: write_finished_reg,
+                data_input_0: source_addr_reg,
+                data_input_1: 1,
+                result_output: source_addr_reg,
+                flags_output: None,
+                activation_output: None,
+            },
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 6,
+            talu_config: TaluOperation::Add {
+                activation_input: write_finished_reg,
+                data_input_0: target_addr_reg,
+                data_input_1: 1,
+                result_output: target_addr_reg,
+                flags_output: None,
+                activation_output: None,
+            },
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 7,
+            talu_config: TaluOperation::Mov {
+                activation_input: 1,
+                value_input: write_finished_reg,
+                data_output: start_reg,
+                activation_output: None,
+            },
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 8,
+            talu_config: TaluOperation::Cmp {
+                op: CmpOp::Eq,
+                data_input_0: ix_reg,
+                data_input_1: loop_count_reg,
+                activation_input: 1,
+
+                data_output: is_final_reg,
+                activation_output: None,
+            },
+        },
+        Instruction::SetTaluConfig {
+            talu_addr: 9,
+            talu_config: TaluOperation::And {
+                data_input_0: is_final_reg,
+                data_input_1: start_reg,
+                activation_input: 1,
+
+                data_output_0: finished_reg,
+                activation_output: None,
+            },
+        },
+        Instruction::SetLiteral {
+            literal: 1,
+            reg_addr: start_reg,
+        },
+        Instruction::SetLiteral {
+            literal: 0,
+            reg_addr: start_reg,
+        },
+        Instruction::WaitForActivationSignal { register_index: finished_reg }, // 128
+    ]);
+
+    input.program = program;
+    input
+}
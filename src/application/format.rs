@@ -0,0 +1,217 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A text format inputs and outputs are read and written in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DataFormat {
+    #[default]
+    Json,
+    Ron,
+}
+
+impl DataFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(DataFormat::Json),
+            "ron" => Some(DataFormat::Ron),
+            _ => None,
+        }
+    }
+
+    /// The format a file is in, by its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_name(path.extension()?.to_str()?)
+    }
+
+    /// The format given on the command line, else the one `path`'s
+    /// extension names, else JSON.
+    pub fn choose(flag: Option<Self>, path: Option<&Path>) -> Self {
+        flag.or_else(|| path.and_then(Self::from_path)).unwrap_or_default()
+    }
+
+    /// Reads a whole document, failing on anything after it.
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, ParseError> {
+        match self {
+            DataFormat::Json => {
+                let mut de = serde_json::Deserializer::from_str(text);
+                let value = serde_path_to_error::deserialize(&mut de).map_err(|err| ParseError {
+                    path: err.path().to_string(),
+                    line: err.inner().line(),
+                    column: err.inner().column(),
+                    message: strip_json_position(&err.inner().to_string()),
+                })?;
+                de.end().map_err(|err| ParseError::at_root(err.line(), err.column(), &err.to_string()))?;
+                Ok(value)
+            }
+            DataFormat::Ron => {
+                let spanned = |err: ron::error::SpannedError, path: String| ParseError {
+                    path,
+                    line: err.span.start.line,
+                    column: err.span.start.col,
+                    message: err.code.to_string(),
+                };
+                let mut de = ron::Deserializer::from_str(text).map_err(|err| spanned(err, ".".to_string()))?;
+                let value = serde_path_to_error::deserialize(&mut de).map_err(|err| {
+                    let path = err.path().to_string();
+                    spanned(de.span_error(err.into_inner()), path)
+                })?;
+                // The parser's span ends with the value; point at what's after it.
+                de.end().map_err(|err| {
+                    let before = &text[..text.len() - de.remainder().len()];
+                    let line = before.lines().count().max(1) + usize::from(before.ends_with('\n'));
+                    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+                    ParseError::at_root(line, column, &err.to_string())
+                })?;
+                Ok(value)
+            }
+        }
+    }
+
+    /// Writes `value` the way a person would lay it out.
+    pub fn write<T: Serialize>(self, value: &T) -> String {
+        match self {
+            DataFormat::Json => serde_json::to_string_pretty(value).unwrap(),
+            DataFormat::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()).unwrap(),
+        }
+    }
+}
+
+/// Takes `--format <name>` out of command line arguments.
+pub fn take_format_arg(args: &mut Vec<String>) -> Result<Option<DataFormat>, String> {
+    let Some(ix) = args.iter().position(|arg| arg == "--format") else {
+        return Ok(None);
+    };
+    args.remove(ix);
+    if ix == args.len() {
+        return Err("`--format` needs a format, `json` or `ron`".to_string());
+    }
+    let name = args.remove(ix);
+    DataFormat::from_name(&name)
+        .map(Some)
+        .ok_or_else(|| format!("unknown format `{name}`, expected `json` or `ron`"))
+}
+
+/// A document that isn't a valid value, with where in the text and in the
+/// value the problem is. Lines and columns start at 1, or are 0 when the
+/// problem isn't at any one place.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    /// Fields and indices from the root to the value, e.g.
+    /// `program[12].SetTaluConfig.talu_config`, or `.` at the root.
+    pub path    : String,
+    pub line    : usize,
+    pub column  : usize,
+    pub message : String,
+}
+
+impl ParseError {
+    fn at_root(line: usize, column: usize, message: &str) -> Self {
+        ParseError { path: ".".to_string(), line, column, message: strip_json_position(message) }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Errors found once a whole value is read, like an unknown data
+        // section, have no position.
+        match self.line {
+            0 => write!(f, " ")?,
+            line => write!(f, "{line}:{}: ", self.column)?,
+        }
+        if self.path != "." {
+            write!(f, "in `{}`: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// `serde_json` ends its messages with the position, which `ParseError`
+/// keeps separately.
+fn strip_json_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(ix) => message[..ix].to_string(),
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::input::FamInput;
+
+    fn error(path: &str, line: usize, column: usize) -> (String, usize, usize) {
+        (path.to_string(), line, column)
+    }
+
+    fn parse_error(format: DataFormat, text: &str) -> ParseError {
+        format.parse::<FamInput>(text).unwrap_err()
+    }
+
+    fn position(err: &ParseError) -> (String, usize, usize) {
+        (err.path.clone(), err.line, err.column)
+    }
+
+    const JSON: &str = r#"{
+  "program": [
+    { "NoOp": null },
+    { "SetTaluConfig": { "talu_addr": 1, "talu_config": { "Not": {
+      "activation_input": 1, "data_input": "r2", "data_output": 3, "activation_output": null
+    } } } }
+  ],
+  "main_memory": []
+}"#;
+
+    const RON: &str = r#"(
+  program: [
+    NoOp,
+    SetTaluConfig(talu_addr: 1, talu_config: Not(
+      activation_input: 1, data_input: "r2", data_output: 3, activation_output: None,
+    )),
+  ],
+  main_memory: [],
+)"#;
+
+    #[test]
+    fn json_errors_have_a_path_and_position() {
+        let err = parse_error(DataFormat::Json, JSON);
+        assert_eq!(position(&err), error("program[1].SetTaluConfig.talu_config.Not.data_input", 5, 47));
+        assert!(err.message.starts_with("invalid type: string \"r2\""), "{}", err.message);
+        assert!(!err.message.contains(" at line "), "{}", err.message);
+    }
+
+    #[test]
+    fn ron_errors_have_a_path_and_position() {
+        let err = parse_error(DataFormat::Ron, RON);
+        assert_eq!(position(&err), error("program[1].SetTaluConfig.talu_config.Not.data_input", 5, 39));
+        assert_eq!(err.message, "Expected integer");
+    }
+
+    #[test]
+    fn trailing_characters_are_errors() {
+        let err = parse_error(DataFormat::Json, "{ \"program\": [], \"main_memory\": [] }\n  x");
+        assert_eq!(position(&err), error(".", 2, 3));
+        assert_eq!(err.message, "trailing characters");
+
+        let err = parse_error(DataFormat::Ron, "(program: [], main_memory: [])\n  x");
+        assert_eq!(position(&err), error(".", 2, 3));
+        assert_eq!(err.message, "Non-whitespace trailing characters");
+    }
+
+    #[test]
+    fn errors_without_a_position_say_so() {
+        let err = parse_error(DataFormat::Json, r#"{ "program": [{ "SetLiteral": { "literal": "nowhere", "reg_addr": 1 } }], "main_memory": [] }"#);
+        assert_eq!((err.line, err.column), (0, 0));
+        assert_eq!(err.to_string(), format!(" {}", err.message));
+        assert!(err.message.contains("there is no data section named `nowhere`"), "{}", err.message);
+    }
+
+    #[test]
+    fn displays_position_then_path() {
+        let err = ParseError { path: "program[12]".to_string(), line: 3, column: 7, message: "oops".to_string() };
+        assert_eq!(err.to_string(), "3:7: in `program[12]`: oops");
+    }
+}
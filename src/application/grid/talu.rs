@@ -12,6 +12,7 @@ use crate::application::grid::pos::{grid_pos, GridPos, GridDist};
 pub struct TaluPortsGridDefns {
     pub data_in_0       : PortGridDefns,
     pub data_in_1       : PortGridDefns,
+    pub data_in_2       : PortGridDefns,
     pub activation_in   : PortGridDefns,
 
     pub data_out_0      : PortGridDefns,
@@ -34,6 +35,7 @@ impl PortDataContainer<TaluPortName, PortGridDefns> for TaluPortsGridDefns {
         match port_name{
             DataIn0 => {&self.data_in_0}
             DataIn1 => {&self.data_in_1}
+            DataIn2 => {&self.data_in_2}
             ActivationIn => {&self.activation_in}
             DataOut0 => {&self.data_out_0}
             DataOut1 => {&self.data_out_1}
@@ -0,0 +1,407 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use serde::de::{self, Unexpected, Visitor};
+use crate::application::simulation::cpu_registers::{CpuRegisterAddress, REGISTER_COUNT};
+use crate::application::simulation::encoding::{encode_instruction, to_words, EncodeError};
+use crate::application::simulation::instruction::Instruction;
+use crate::application::simulation::instruction_reader::{CodeRegion, WORDS_PER_INSTRUCTION};
+use crate::application::simulation::propagation::PropagationModel;
+use crate::application::simulation::simulation::Cpu;
+use crate::application::simulation::talu::LatencyTable;
+use crate::word::Word;
+
+/// The final contents of main memory.
+pub type FamOutput = Vec<Word>;
+
+/// Most words of main memory the data sections and code region of an
+/// input may reach, so that a mistyped address is reported instead of
+/// allocated.
+pub const MAX_MAIN_MEMORY_LEN: usize = 1 << 24;
+
+/// A program together with everything needed to run it. When read, the
+/// data sections `SetLiteral`s and initial registers name are resolved to
+/// their addresses.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(try_from = "FamInputSource")]
+pub struct FamInput{
+    pub program: Vec<Instruction>,
+    pub main_memory: Vec<Word>,
+    #[serde(default)]
+    pub talu_latencies: LatencyTable,
+    #[serde(default)]
+    pub propagation: PropagationModel,
+    /// Runs the program in von Neumann mode from this region of main
+    /// memory, which `program` is loaded into before the first step.
+    #[serde(default)]
+    pub code_region: Option<CodeRegion>,
+    /// Register values before the first step, by register address.
+    #[serde(default)]
+    pub registers: BTreeMap<CpuRegisterAddress, Word>,
+    /// Named blocks of main memory, laid out as `data_addresses` says.
+    #[serde(default)]
+    pub data: Vec<DataSection>,
+}
+
+/// A `FamInput` as written, before the data sections it names are
+/// resolved.
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename = "FamInput")]
+struct FamInputSource {
+    program: Vec<Instruction<Literal>>,
+    main_memory: Vec<Word>,
+    #[serde(default)]
+    talu_latencies: LatencyTable,
+    #[serde(default)]
+    propagation: PropagationModel,
+    #[serde(default)]
+    code_region: Option<CodeRegion>,
+    #[serde(default)]
+    registers: BTreeMap<CpuRegisterAddress, Literal>,
+    #[serde(default)]
+    data: Vec<DataSection>,
+}
+
+impl TryFrom<FamInputSource> for FamInput {
+    type Error = String;
+
+    fn try_from(source: FamInputSource) -> Result<Self, String> {
+        let FamInputSource { program, main_memory, talu_latencies, propagation, code_region, registers, data } = source;
+        let mut input = FamInput {
+            program: Vec::with_capacity(program.len()),
+            main_memory,
+            talu_latencies,
+            propagation,
+            code_region,
+            registers: BTreeMap::new(),
+            data,
+        };
+        let addresses = input.data_addresses();
+        let resolve = |literal: Literal| literal.resolve(&input.data, &addresses);
+        let program = program.into_iter().enumerate()
+            .map(|(ix, instruction)| instruction.try_map_literal(resolve).map_err(|err| format!("program[{ix}]: {err}")))
+            .collect::<Result<_, _>>()?;
+        let registers = registers.into_iter()
+            .map(|(reg, literal)| Ok((reg, resolve(literal).map_err(|err| format!("registers[{reg}]: {err}"))?)))
+            .collect::<Result<_, String>>()?;
+        input.program = program;
+        input.registers = registers;
+        Ok(input)
+    }
+}
+
+/// A named block of main memory. Its words are written when the program
+/// starts, after `main_memory` and before the program in von Neumann mode.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema, PartialEq, Eq)]
+pub struct DataSection {
+    pub name: String,
+    /// Main memory address of the first word, or `None` to place the
+    /// section after everything with a fixed place, see `data_addresses`.
+    #[serde(default)]
+    pub addr: Option<usize>,
+    #[serde(default)]
+    pub words: Vec<Word>,
+    /// Words the section takes, zeros after `words`. Defaults to the
+    /// number of `words`.
+    #[serde(default)]
+    pub len: Option<usize>,
+}
+
+impl DataSection {
+    /// Words of main memory the section takes.
+    pub fn size(&self) -> usize {
+        self.len.unwrap_or(0).max(self.words.len())
+    }
+}
+
+/// What an input's `SetLiteral` or initial register holds: a word, or the
+/// address of a data section written as its name and an optional offset,
+/// `"table"` or `"table+3"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Literal {
+    Word(Word),
+    Address {
+        section : String,
+        offset  : usize,
+    },
+}
+
+impl Literal {
+    fn resolve(self, data: &[DataSection], addresses: &[usize]) -> Result<Word, String> {
+        let (section, offset) = match self {
+            Literal::Word(word) => return Ok(word),
+            Literal::Address { section, offset } => (section, offset),
+        };
+        let ix = data.iter().position(|candidate| candidate.name == section)
+            .ok_or_else(|| format!("there is no data section named `{section}`"))?;
+        addresses[ix].checked_add(offset)
+            .and_then(|addr| Word::try_from(addr).ok())
+            .ok_or_else(|| format!("the address of `{section}+{offset}` doesn't fit in a word"))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Literal {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LiteralVisitor;
+
+        impl Visitor<'_> for LiteralVisitor {
+            type Value = Literal;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a word, or a data section name like `table` or `table+3`")
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Literal, E> {
+                Word::try_from(value).map(Literal::Word).map_err(|_| E::invalid_value(Unexpected::Signed(value), &self))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Literal, E> {
+                Word::try_from(value).map(Literal::Word).map_err(|_| E::invalid_value(Unexpected::Unsigned(value), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Literal, E> {
+                let (section, offset) = match value.split_once('+') {
+                    Some((section, offset)) => {
+                        let offset = offset.trim().parse().map_err(|_| E::invalid_value(Unexpected::Str(value), &self))?;
+                        (section.trim(), offset)
+                    }
+                    None => (value.trim(), 0),
+                };
+                if section.is_empty() {
+                    return Err(E::invalid_value(Unexpected::Str(value), &self));
+                }
+                Ok(Literal::Address { section: section.to_string(), offset })
+            }
+        }
+
+        deserializer.deserialize_any(LiteralVisitor)
+    }
+}
+
+impl schemars::JsonSchema for Literal {
+    fn schema_name() -> Cow<'static, str> {
+        "Literal".into()
+    }
+
+    fn json_schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "description": "A word, or the address of a data section as its name and an optional offset, like `table` or `table+3`.",
+            "oneOf": [
+                { "type": "integer", "format": "int32", "minimum": Word::MIN, "maximum": Word::MAX },
+                { "type": "string", "pattern": "^\\s*[^+\\s][^+]*(\\+\\s*[0-9]+\\s*)?$" },
+            ],
+        })
+    }
+}
+
+/// Why an input can't be built into a `Cpu`. `check` reports both.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BuildError {
+    /// An instruction von Neumann mode can't load into main memory.
+    Encode(EncodeError),
+    /// An initial value for a register that doesn't exist.
+    NoSuchRegister(CpuRegisterAddress),
+    MemoryLimit(MemoryLimitError),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::Encode(err) => write!(f, "can't load the program into main memory: {err}"),
+            BuildError::NoSuchRegister(reg) => write!(f, "initial value for register {reg}, which doesn't exist, there are {REGISTER_COUNT}"),
+            BuildError::MemoryLimit(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// A data section or code region that ends past `MAX_MAIN_MEMORY_LEN`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MemoryLimitError {
+    /// What ends there, e.g. "data section `table`".
+    pub what: String,
+    /// Where it ends, or `usize::MAX` if that doesn't fit in a `usize`.
+    pub end: usize,
+}
+
+impl Display for MemoryLimitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ends past the largest main memory of {MAX_MAIN_MEMORY_LEN} words", self.what)
+    }
+}
+
+impl std::error::Error for MemoryLimitError {}
+
+impl FamInput {
+    pub fn build_cpu(&self) -> Result<Cpu, BuildError> {
+        let main_memory = self.initial_main_memory()?;
+        if let Some(&reg) = self.registers.keys().find(|reg| **reg >= REGISTER_COUNT) {
+            return Err(BuildError::NoSuchRegister(reg));
+        }
+        let mut cpu = Cpu::new(
+            self.program.clone(),
+            main_memory,
+            self.talu_latencies.clone(),
+        );
+        cpu.propagation = self.propagation;
+        cpu.controller.instruction_reader.code_region = self.code_region;
+        for (reg, value) in self.registers.iter() {
+            cpu.register_bank.components[*reg].write(*value);
+        }
+        Ok(cpu)
+    }
+
+    /// Main memory address of each of `data`, in order. Sections with an
+    /// `addr` go there; the others follow each other from the first word
+    /// past `main_memory`, the code region and every placed section.
+    pub fn data_addresses(&self) -> Vec<usize> {
+        let mut next = self.data.iter()
+            .filter_map(|section| Some(section.addr?.saturating_add(section.size())))
+            .chain(self.code_region.map(|region| region.end()))
+            .fold(self.main_memory.len(), usize::max);
+        self.data.iter()
+            .map(|section| section.addr.unwrap_or_else(|| {
+                let addr = next;
+                next = next.saturating_add(section.size());
+                addr
+            }))
+            .collect()
+    }
+
+    /// Words of main memory the program starts with: `main_memory` grown
+    /// to hold the data sections and the code region, unless one of them
+    /// ends past `MAX_MAIN_MEMORY_LEN`.
+    pub fn memory_len(&self) -> Result<usize, MemoryLimitError> {
+        let ends = self.data.iter().zip(self.data_addresses())
+            .map(|(section, addr)| (format!("data section `{}`", section.name), addr.checked_add(section.size())))
+            .chain(self.code_region.map(|region| {
+                let end = self.program.len().max(region.len)
+                    .checked_mul(WORDS_PER_INSTRUCTION)
+                    .and_then(|size| region.base.checked_add(size));
+                ("the code region".to_string(), end)
+            }));
+        let mut len = self.main_memory.len();
+        for (what, end) in ends {
+            match end {
+                Some(end) if end <= MAX_MAIN_MEMORY_LEN => len = len.max(end),
+                _ => return Err(MemoryLimitError { what, end: end.unwrap_or(usize::MAX) }),
+            }
+        }
+        Ok(len)
+    }
+
+    /// The address `data_addresses` gives the section named `name`.
+    pub fn section_address(&self, name: &str) -> Option<usize> {
+        let ix = self.data.iter().position(|section| section.name == name)?;
+        Some(self.data_addresses()[ix])
+    }
+
+    /// `main_memory` with the data sections written into it, grown to hold
+    /// them. Fails as `memory_len` does.
+    pub fn memory_with_data(&self) -> Result<Vec<Word>, MemoryLimitError> {
+        self.memory_len()?;
+        let mut memory = self.main_memory.clone();
+        for (section, addr) in self.data.iter().zip(self.data_addresses()) {
+            let end = addr + section.size();
+            if memory.len() < end {
+                memory.resize(end, 0);
+            }
+            memory[addr..end].fill(0);
+            memory[addr..addr + section.words.len()].copy_from_slice(&section.words);
+        }
+        Ok(memory)
+    }
+
+    /// Main memory as the program starts: `memory_with_data`, and in von
+    /// Neumann mode with `program` encoded into the code region, grown to
+    /// hold it.
+    pub fn initial_main_memory(&self) -> Result<Vec<Word>, BuildError> {
+        let mut memory = self.memory_with_data().map_err(BuildError::MemoryLimit)?;
+        let Some(region) = self.code_region else {
+            return Ok(memory);
+        };
+        let end = region.end().max(region.base + self.program.len() * WORDS_PER_INSTRUCTION);
+        if memory.len() < end {
+            memory.resize(end, 0);
+        }
+        for (ix, instruction) in self.program.iter().enumerate() {
+            let addr = region.base + ix * WORDS_PER_INSTRUCTION;
+            let words = to_words(encode_instruction(instruction).map_err(BuildError::Encode)?);
+            memory[addr..addr + WORDS_PER_INSTRUCTION].copy_from_slice(&words);
+        }
+        Ok(memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::check::{check, Severity};
+
+    fn with_section(addr: usize, offset: usize) -> Result<FamInput, serde_json::Error> {
+        serde_json::from_value(serde_json::json!({
+            "program": [{ "SetLiteral": { "literal": format!("far+{offset}"), "reg_addr": 1 } }],
+            "main_memory": [],
+            "data": [{ "name": "far", "addr": addr, "words": [1, 2] }],
+        }))
+    }
+
+    #[test]
+    fn sections_past_the_memory_limit_are_errors() {
+        for addr in [1 << 40, usize::MAX - 1] {
+            let input = FamInput {
+                data: vec![DataSection { name: "far".to_string(), addr: Some(addr), words: vec![1, 2], len: None }],
+                ..Default::default()
+            };
+            let expected = MemoryLimitError { what: "data section `far`".to_string(), end: addr.checked_add(2).unwrap_or(usize::MAX) };
+            assert_eq!(input.memory_len(), Err(expected.clone()));
+            assert_eq!(input.memory_with_data(), Err(expected.clone()));
+            assert_eq!(input.build_cpu().err(), Some(BuildError::MemoryLimit(expected.clone())));
+            assert!(check(&input).iter().any(|diagnostic|
+                diagnostic.severity == Severity::Error && diagnostic.message == expected.to_string()
+            ));
+        }
+    }
+
+    #[test]
+    fn code_region_past_the_memory_limit_is_an_error() {
+        let input = FamInput {
+            code_region: Some(CodeRegion { base: MAX_MAIN_MEMORY_LEN, len: 1 }),
+            ..Default::default()
+        };
+        assert_eq!(input.memory_len(), Err(MemoryLimitError {
+            what: "the code region".to_string(),
+            end: MAX_MAIN_MEMORY_LEN + WORDS_PER_INSTRUCTION,
+        }));
+    }
+
+    #[test]
+    fn memory_grows_to_hold_sections_up_to_the_limit() {
+        let input = FamInput {
+            main_memory: vec![7; 3],
+            data: vec![
+                DataSection { name: "last".to_string(), addr: Some(MAX_MAIN_MEMORY_LEN - 1), words: vec![9], len: None },
+                DataSection { name: "next".to_string(), words: vec![5], ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        // `next` is placed after `last`, past the limit.
+        assert_eq!(input.data_addresses(), [MAX_MAIN_MEMORY_LEN - 1, MAX_MAIN_MEMORY_LEN]);
+        assert!(input.memory_len().is_err());
+
+        let input = FamInput { data: input.data[..1].to_vec(), ..input };
+        assert_eq!(input.memory_len(), Ok(MAX_MAIN_MEMORY_LEN));
+        let memory = input.memory_with_data().unwrap();
+        assert_eq!(memory.len(), MAX_MAIN_MEMORY_LEN);
+        assert_eq!((memory[0], memory[MAX_MAIN_MEMORY_LEN - 1]), (7, 9));
+    }
+
+    #[test]
+    fn addresses_past_a_word_are_errors() {
+        let err = with_section(usize::MAX - 1, 5).unwrap_err().to_string();
+        assert!(err.contains("the address of `far+5` doesn't fit in a word"), "{err}");
+        let err = with_section(Word::MAX as usize, 1).unwrap_err().to_string();
+        assert!(err.contains("the address of `far+1` doesn't fit in a word"), "{err}");
+    }
+}
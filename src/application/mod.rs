@@ -11,6 +11,15 @@ pub mod draw;
 pub mod direction;
 pub mod simulation;
 pub mod connection;
+pub mod input;
+pub mod runner;
+pub mod asm;
+pub mod check;
+pub mod program_file;
+pub mod format;
+pub mod output;
+pub mod schema;
+pub mod examples;
 
 // pub async fn run_simulation(program: Vec<Instruction>, data: Vec<Word>){
 //     let cpu = Cpu::new(program, data);
@@ -0,0 +1,257 @@
+use crate::application::input::{FamInput, FamOutput};
+use crate::application::simulation::controller::ControllerExecutionState;
+use crate::application::simulation::simulation::Cpu;
+use crate::application::simulation::talu::{TaluAddress, TaluOperation, TaluState};
+use crate::word::Word;
+use crate::Step;
+
+/// What a run reports besides main memory. With everything off the output
+/// is just main memory, as a plain array.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct OutputOptions {
+    /// Final value of every register.
+    pub registers   : bool,
+    /// Final operation and state of every TALU, and the TALUs of a
+    /// combinational loop between them.
+    pub talus       : bool,
+    /// The state the controller was left in.
+    pub controller  : bool,
+    /// Steps taken and instructions executed.
+    pub statistics  : bool,
+    /// Main memory words that differ from how the program started.
+    pub memory_diff : bool,
+}
+
+impl OutputOptions {
+    pub const NAMES: [&str; 5] = ["registers", "talus", "controller", "statistics", "memory_diff"];
+
+    pub fn all() -> Self {
+        OutputOptions { registers: true, talus: true, controller: true, statistics: true, memory_diff: true }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Parses a comma separated list of `NAMES`, or `all`.
+    pub fn from_list(list: &str) -> Result<Self, String> {
+        let mut options = Self::default();
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match name {
+                "all" => options = Self::all(),
+                "registers" => options.registers = true,
+                "talus" => options.talus = true,
+                "controller" => options.controller = true,
+                "statistics" => options.statistics = true,
+                "memory_diff" => options.memory_diff = true,
+                _ => return Err(format!(
+                    "unknown output `{name}`, expected `all` or some of {}",
+                    Self::NAMES.map(|name| format!("`{name}`")).join(", "),
+                )),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Takes `--output <list>` out of command line arguments.
+pub fn take_output_arg(args: &mut Vec<String>) -> Result<OutputOptions, String> {
+    let Some(ix) = args.iter().position(|arg| arg == "--output") else {
+        return Ok(OutputOptions::default());
+    };
+    args.remove(ix);
+    if ix == args.len() {
+        return Err("`--output` needs a comma separated list, or `all`".to_string());
+    }
+    OutputOptions::from_list(&args.remove(ix))
+}
+
+/// The result of a finished run: main memory alone, or a report with
+/// whatever else `OutputOptions` asked for.
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum RunOutput {
+    Memory(FamOutput),
+    Report(Box<RunReport>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct RunReport {
+    pub main_memory : FamOutput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registers   : Option<Vec<Word>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub talus       : Option<Vec<TaluReport>>,
+    /// TALUs reading each other's outputs in a cycle under combinational
+    /// propagation, see `CombinationalLoop`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub combinational_loop: Option<Vec<TaluAddress>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub controller  : Option<ControllerExecutionState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steps       : Option<Step>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_diff : Option<Vec<MemoryChange>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct TaluReport {
+    pub operation   : TaluOperation,
+    pub state       : TaluState,
+}
+
+/// A main memory word that changed. A word past the end of the memory on
+/// either side counts as 0.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct MemoryChange {
+    pub addr    : usize,
+    pub before  : Word,
+    pub after   : Word,
+}
+
+/// Everything `options` asks for about `cpu`, which ran `input` for
+/// `steps` steps.
+pub fn collect_output(cpu: &Cpu, input: &FamInput, options: &OutputOptions, steps: Step) -> RunOutput {
+    let main_memory = cpu.main_memory.0.read().unwrap().clone();
+    if options.is_plain() {
+        return RunOutput::Memory(main_memory);
+    }
+
+    let memory_diff = options.memory_diff.then(|| {
+        // Von Neumann programs start with their code in memory.
+        let before = input.initial_main_memory().unwrap_or_else(|_| input.main_memory.clone());
+        (0..before.len().max(main_memory.len()))
+            .filter_map(|addr| {
+                let before = before.get(addr).copied().unwrap_or(0);
+                let after = main_memory.get(addr).copied().unwrap_or(0);
+                (before != after).then_some(MemoryChange { addr, before, after })
+            })
+            .collect()
+    });
+
+    RunOutput::Report(Box::new(RunReport {
+        registers: options.registers.then(|| {
+            cpu.register_bank.components.iter().map(|register| register.value).collect()
+        }),
+        talus: options.talus.then(|| {
+            cpu.talu_bank.components.iter()
+                .map(|talu| TaluReport { operation: talu.operation, state: talu.state.clone() })
+                .collect()
+        }),
+        combinational_loop: cpu.combinational_loop.as_ref()
+            .filter(|_| options.talus)
+            .map(|combinational_loop| combinational_loop.talus.clone()),
+        controller: options.controller.then_some(cpu.controller.state),
+        steps: options.statistics.then_some(steps),
+        instructions: options.statistics.then_some(cpu.controller.instructions_executed),
+        memory_diff,
+        main_memory,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::runner::run;
+    use crate::application::simulation::instruction::Instruction;
+    use crate::application::simulation::instruction_reader::CodeRegion;
+    use crate::application::simulation::talu::TALU_COUNT;
+
+    /// Writes r1 to main memory at r2, then idles a few steps.
+    fn writes(main_memory: Vec<Word>, code_region: Option<CodeRegion>) -> FamInput {
+        let write = TaluOperation::WriteToMem { data_input: 1, address_input: 2, activation_input: 3, activation_output: None };
+        FamInput {
+            program: vec![
+                Instruction::SetTaluConfig { talu_addr: 0, talu_config: write },
+                Instruction::NoOp,
+                Instruction::NoOp,
+            ],
+            main_memory,
+            code_region,
+            registers: [(1, 9), (2, 1), (3, 1)].into(),
+            ..Default::default()
+        }
+    }
+
+    fn report(input: &FamInput, options: OutputOptions) -> RunReport {
+        match run(input, 100, &options).unwrap() {
+            RunOutput::Report(report) => *report,
+            RunOutput::Memory(memory) => panic!("got plain memory {memory:?}"),
+        }
+    }
+
+    fn json(input: &FamInput, options: OutputOptions) -> serde_json::Value {
+        serde_json::to_value(run(input, 100, &options).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn plain_output_is_main_memory() {
+        let input = writes(vec![1, 2, 3], None);
+        assert_eq!(json(&input, OutputOptions::default()), serde_json::json!([1, 9, 3]));
+    }
+
+    #[test]
+    fn reports_hold_what_was_asked_for() {
+        let input = writes(vec![1, 2, 3], None);
+        let fields = |options| {
+            let json = json(&input, options);
+            json.as_object().unwrap().keys().cloned().collect::<Vec<_>>()
+        };
+        assert_eq!(fields(OutputOptions { statistics: true, ..Default::default() }), ["instructions", "main_memory", "steps"]);
+        assert_eq!(fields(OutputOptions { registers: true, controller: true, ..Default::default() }), ["controller", "main_memory", "registers"]);
+        assert_eq!(fields(OutputOptions::all()), ["controller", "instructions", "main_memory", "memory_diff", "registers", "steps", "talus"]);
+
+        let report = report(&input, OutputOptions::all());
+        assert_eq!(report.main_memory, [1, 9, 3]);
+        assert_eq!(report.registers.unwrap()[..4], [0, 9, 1, 1]);
+        let talus = report.talus.unwrap();
+        assert_eq!(talus.len(), TALU_COUNT);
+        assert_eq!(talus[0].operation.kind_name(), "WriteToMem");
+        assert_eq!(talus[1].operation, TaluOperation::NoOp);
+        assert_eq!(report.combinational_loop, None);
+        assert_eq!(report.instructions, Some(3));
+        assert!(report.steps.unwrap() > 3);
+    }
+
+    #[test]
+    fn memory_diff_lists_changed_words() {
+        let options = OutputOptions { memory_diff: true, ..Default::default() };
+        let input = writes(vec![1, 2, 3], None);
+        assert_eq!(report(&input, options).memory_diff, Some(vec![MemoryChange { addr: 1, before: 2, after: 9 }]));
+
+        let unchanged = writes(vec![1, 9, 3], None);
+        assert_eq!(report(&unchanged, options).memory_diff, Some(Vec::new()));
+
+        // Code loaded into memory isn't a change.
+        let von_neumann = writes(vec![1, 2, 3], Some(CodeRegion { base: 4, len: 3 }));
+        let report = report(&von_neumann, options);
+        assert_eq!(report.main_memory.len(), 10);
+        assert_eq!(report.memory_diff, Some(vec![MemoryChange { addr: 1, before: 2, after: 9 }]));
+    }
+
+    #[test]
+    fn parses_lists_of_outputs() {
+        assert_eq!(OutputOptions::from_list(""), Ok(OutputOptions::default()));
+        assert_eq!(OutputOptions::from_list("all"), Ok(OutputOptions::all()));
+        assert_eq!(
+            OutputOptions::from_list(" registers, memory_diff ,"),
+            Ok(OutputOptions { registers: true, memory_diff: true, ..Default::default() }),
+        );
+        assert_eq!(
+            OutputOptions::from_list("registers,regs"),
+            Err("unknown output `regs`, expected `all` or some of `registers`, `talus`, `controller`, `statistics`, `memory_diff`".to_string()),
+        );
+    }
+
+    #[test]
+    fn takes_the_output_argument() {
+        let mut args = vec!["in.json".to_string(), "--output".to_string(), "talus".to_string(), "out.json".to_string()];
+        assert_eq!(take_output_arg(&mut args), Ok(OutputOptions { talus: true, ..Default::default() }));
+        assert_eq!(args, ["in.json", "out.json"]);
+        assert_eq!(take_output_arg(&mut args), Ok(OutputOptions::default()));
+        assert!(take_output_arg(&mut vec!["--output".to_string()]).is_err());
+    }
+}
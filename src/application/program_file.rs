@@ -0,0 +1,292 @@
+use std::fmt::{Display, Formatter};
+use crate::application::input::FamInput;
+use crate::application::simulation::encoding::{decode_instruction, encode_instruction, DecodeError, EncodeError, EncodedInstruction};
+use crate::application::simulation::instruction_reader::CodeRegion;
+use crate::application::simulation::propagation::PropagationModel;
+use crate::word::Word;
+
+/// First bytes of every binary program file.
+pub const PROGRAM_FILE_MAGIC: [u8; 4] = *b"FAMP";
+/// The version `write_program_file` writes. `read_program_file` reads
+/// version 1 too, which is the same but for having no initial registers.
+pub const PROGRAM_FILE_VERSION: u16 = 2;
+
+const FLAG_CODE_REGION: u8 = 1;
+const FLAG_REGISTERS: u8 = 2;
+
+/// An input stored in the binary program format, little-endian:
+///
+/// | bytes         | field                                                  |
+/// |---------------|--------------------------------------------------------|
+/// | 4             | `PROGRAM_FILE_MAGIC`                                   |
+/// | 2             | version                                                |
+/// | 1             | propagation model, 0 registered, 1 combinational       |
+/// | 1             | flags, bit 0 set if a code region follows, bit 1 if    |
+/// |               | initial registers do, from version 2                   |
+/// | 8, if flagged | code region base and length, see `CodeRegion`          |
+/// | 1, if flagged | initial register count                                 |
+/// | 5 each        | register address (1) and value (4)                     |
+/// | 4             | default TALU latency                                   |
+/// | 2             | per-operation latency count                            |
+/// | per latency   | name length (1), name in UTF-8, latency (4)            |
+/// | 4             | instruction count                                      |
+/// | 4             | main memory length in words                            |
+/// | 8 each        | instructions, as `encode_instruction` gives them       |
+/// | 4 each        | main memory words                                      |
+///
+/// Data sections are stored as the main memory they make, without their
+/// names.
+pub fn write_program_file(input: &FamInput) -> Result<Vec<u8>, EncodeError> {
+    let mut bytes = Vec::new();
+    bytes.extend(PROGRAM_FILE_MAGIC);
+    bytes.extend(PROGRAM_FILE_VERSION.to_le_bytes());
+    bytes.push(match input.propagation {
+        PropagationModel::Registered => 0,
+        PropagationModel::Combinational => 1,
+    });
+    let mut flags = 0;
+    if input.code_region.is_some() {
+        flags |= FLAG_CODE_REGION;
+    }
+    if !input.registers.is_empty() {
+        flags |= FLAG_REGISTERS;
+    }
+    bytes.push(flags);
+    if let Some(region) = input.code_region {
+        bytes.extend(length::<u32>("code region base", region.base)?.to_le_bytes());
+        bytes.extend(length::<u32>("code region length", region.len)?.to_le_bytes());
+    }
+    if !input.registers.is_empty() {
+        bytes.push(length::<u8>("register count", input.registers.len())?);
+        for (reg, value) in input.registers.iter() {
+            bytes.push(length::<u8>("register address", *reg)?);
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+
+    let latencies = &input.talu_latencies;
+    bytes.extend(latencies.default.to_le_bytes());
+    bytes.extend(length::<u16>("latency count", latencies.per_operation.len())?.to_le_bytes());
+    for (operation, latency) in latencies.per_operation.iter() {
+        bytes.push(length::<u8>("operation name length", operation.len())?);
+        bytes.extend(operation.as_bytes());
+        bytes.extend(latency.to_le_bytes());
+    }
+
+    bytes.extend(length::<u32>("instruction count", input.program.len())?.to_le_bytes());
+    let main_memory = input.memory_with_data().map_err(|err| EncodeError::OutOfRange {
+        field: "main memory length",
+        value: i64::try_from(err.end).unwrap_or(i64::MAX),
+    })?;
+    bytes.extend(length::<u32>("main memory length", main_memory.len())?.to_le_bytes());
+    for instruction in input.program.iter() {
+        bytes.extend(encode_instruction(instruction)?.to_le_bytes());
+    }
+    for word in main_memory.iter() {
+        bytes.extend(word.to_le_bytes());
+    }
+    Ok(bytes)
+}
+
+fn length<T: TryFrom<usize>>(field: &'static str, len: usize) -> Result<T, EncodeError> {
+    T::try_from(len).map_err(|_| EncodeError::OutOfRange { field, value: len as i64 })
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ProgramFileError {
+    /// The file doesn't start with `PROGRAM_FILE_MAGIC`.
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// The file ends before its header says it does.
+    Truncated,
+    /// Bytes left over after main memory.
+    TrailingBytes(usize),
+    UnknownPropagation(u8),
+    /// Flags this version doesn't know of.
+    UnknownFlags(u8),
+    InvalidOperationName,
+    Instruction { index: usize, error: DecodeError },
+}
+
+impl Display for ProgramFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramFileError::BadMagic => write!(f, "not a program file"),
+            ProgramFileError::UnsupportedVersion(version) => write!(f, "unsupported program file version {version}"),
+            ProgramFileError::Truncated => write!(f, "program file is truncated"),
+            ProgramFileError::TrailingBytes(count) => write!(f, "{count} bytes after the end of the program file"),
+            ProgramFileError::UnknownPropagation(tag) => write!(f, "unknown propagation model {tag}"),
+            ProgramFileError::UnknownFlags(flags) => write!(f, "unknown flags {flags:#04x}"),
+            ProgramFileError::InvalidOperationName => write!(f, "latency table has an operation name that isn't UTF-8"),
+            ProgramFileError::Instruction { index, error } => write!(f, "instruction {index}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ProgramFileError {}
+
+/// Reads a file written by `write_program_file`.
+pub fn read_program_file(bytes: &[u8]) -> Result<FamInput, ProgramFileError> {
+    let mut r = ByteReader { bytes };
+    if r.take::<4>()? != PROGRAM_FILE_MAGIC {
+        return Err(ProgramFileError::BadMagic);
+    }
+    let known_flags = match u16::from_le_bytes(r.take()?) {
+        1 => FLAG_CODE_REGION,
+        PROGRAM_FILE_VERSION => FLAG_CODE_REGION | FLAG_REGISTERS,
+        version => return Err(ProgramFileError::UnsupportedVersion(version)),
+    };
+    let [propagation, flags] = r.take()?;
+    if flags & !known_flags != 0 {
+        return Err(ProgramFileError::UnknownFlags(flags));
+    }
+    let mut input = FamInput {
+        propagation: match propagation {
+            0 => PropagationModel::Registered,
+            1 => PropagationModel::Combinational,
+            tag => return Err(ProgramFileError::UnknownPropagation(tag)),
+        },
+        ..Default::default()
+    };
+    if flags & FLAG_CODE_REGION != 0 {
+        input.code_region = Some(CodeRegion {
+            base: u32::from_le_bytes(r.take()?) as usize,
+            len: u32::from_le_bytes(r.take()?) as usize,
+        });
+    }
+    if flags & FLAG_REGISTERS != 0 {
+        let [count] = r.take()?;
+        for _ in 0..count {
+            let [reg] = r.take()?;
+            input.registers.insert(reg as usize, Word::from_le_bytes(r.take()?));
+        }
+    }
+
+    input.talu_latencies.default = u32::from_le_bytes(r.take()?);
+    for _ in 0..u16::from_le_bytes(r.take()?) {
+        let [name_len] = r.take()?;
+        let name = String::from_utf8(r.slice(name_len as usize)?.to_vec())
+            .map_err(|_| ProgramFileError::InvalidOperationName)?;
+        input.talu_latencies.per_operation.insert(name, u32::from_le_bytes(r.take()?));
+    }
+
+    let instruction_count = u32::from_le_bytes(r.take()?) as usize;
+    let memory_len = u32::from_le_bytes(r.take()?) as usize;
+    for index in 0..instruction_count {
+        let encoded = EncodedInstruction::from_le_bytes(r.take()?);
+        let instruction = decode_instruction(encoded)
+            .map_err(|error| ProgramFileError::Instruction { index, error })?;
+        input.program.push(instruction);
+    }
+    for _ in 0..memory_len {
+        input.main_memory.push(Word::from_le_bytes(r.take()?));
+    }
+
+    match r.bytes.len() {
+        0 => Ok(input),
+        count => Err(ProgramFileError::TrailingBytes(count)),
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn slice(&mut self, len: usize) -> Result<&'a [u8], ProgramFileError> {
+        let (head, rest) = self.bytes.split_at_checked(len).ok_or(ProgramFileError::Truncated)?;
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ProgramFileError> {
+        Ok(self.slice(N)?.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::examples::make_loop_program;
+    use crate::application::simulation::instruction::Instruction;
+
+    fn input() -> FamInput {
+        let mut input = make_loop_program();
+        input.propagation = PropagationModel::Combinational;
+        input.registers.insert(7, -5);
+        input.registers.insert(20, Word::MAX);
+        input.talu_latencies.default = 2;
+        input.talu_latencies.per_operation.insert("Div".to_string(), 9);
+        input
+    }
+
+    /// `input` as a file gives it back: data sections become main memory.
+    fn read_back(input: &FamInput) -> FamInput {
+        FamInput { main_memory: input.memory_with_data().unwrap(), data: Vec::new(), ..input.clone() }
+    }
+
+    #[test]
+    fn starts_with_magic_and_version() {
+        let bytes = write_program_file(&input()).unwrap();
+        assert_eq!(bytes[..4], PROGRAM_FILE_MAGIC);
+        assert_eq!(bytes[4..6], PROGRAM_FILE_VERSION.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trips() {
+        let input = input();
+        assert_eq!(read_program_file(&write_program_file(&input).unwrap()), Ok(read_back(&input)));
+
+        let von_neumann = FamInput {
+            code_region: Some(CodeRegion { base: 64, len: input.program.len() }),
+            registers: Default::default(),
+            ..input
+        };
+        assert_eq!(read_program_file(&write_program_file(&von_neumann).unwrap()), Ok(read_back(&von_neumann)));
+    }
+
+    #[test]
+    fn reads_version_1_without_registers() {
+        let without_registers = FamInput { registers: Default::default(), ..input() };
+        let mut bytes = write_program_file(&without_registers).unwrap();
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(read_program_file(&bytes), Ok(read_back(&without_registers)));
+
+        let mut bytes = write_program_file(&input()).unwrap();
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(read_program_file(&bytes), Err(ProgramFileError::UnknownFlags(FLAG_REGISTERS)));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let bytes = write_program_file(&input()).unwrap();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(read_program_file(&bad_magic), Err(ProgramFileError::BadMagic));
+
+        let mut future = bytes.clone();
+        future[4..6].copy_from_slice(&(PROGRAM_FILE_VERSION + 1).to_le_bytes());
+        assert_eq!(read_program_file(&future), Err(ProgramFileError::UnsupportedVersion(PROGRAM_FILE_VERSION + 1)));
+
+        let mut unknown_flags = bytes.clone();
+        unknown_flags[7] |= 0x80;
+        assert!(matches!(read_program_file(&unknown_flags), Err(ProgramFileError::UnknownFlags(_))));
+
+        assert_eq!(read_program_file(&bytes[..bytes.len() - 1]), Err(ProgramFileError::Truncated));
+        assert_eq!(read_program_file(&[bytes.as_slice(), &[0; 3]].concat()), Err(ProgramFileError::TrailingBytes(3)));
+    }
+
+    #[test]
+    fn rejects_undecodable_instructions() {
+        let input = FamInput { program: vec![Instruction::NoOp, Instruction::NoOp], ..Default::default() };
+        let mut bytes = write_program_file(&input).unwrap();
+        // The second instruction, the last 8 bytes with no main memory.
+        let last = bytes.len() - 8;
+        bytes[last + 5] = 1;
+        assert_eq!(
+            read_program_file(&bytes),
+            Err(ProgramFileError::Instruction { index: 1, error: DecodeError::TrailingBits(1 << 40) }),
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::{Display, Formatter};
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use crate::application::check::{check, Severity};
+use crate::application::input::FamInput;
+use crate::application::output::{collect_output, OutputOptions, RunOutput};
+use crate::Step;
+
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, schemars::JsonSchema)]
+pub enum RunError {
+    /// The program was still running after this many steps.
+    StepLimit(Step),
+    /// The input can't be run: the errors `check` finds, one per line, or
+    /// why `FamInput::build_cpu` failed.
+    Invalid(String),
+    /// The program halted on an error, see `Controller::fault`.
+    Halted(String),
+    /// The simulation panicked, with the panic message.
+    Panicked(String),
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::StepLimit(steps) => write!(f, "still running after {steps} steps"),
+            RunError::Invalid(message) => write!(f, "invalid input: {message}"),
+            RunError::Halted(message) => write!(f, "halted: {message}"),
+            RunError::Panicked(message) => write!(f, "simulation panicked: {message}"),
+        }
+    }
+}
+
+pub type RunResult = Result<RunOutput, RunError>;
+
+/// Checks a single input and runs it headless until its program finishes,
+/// reporting what `options` asks for.
+pub fn run(input: &FamInput, max_steps: Step, options: &OutputOptions) -> RunResult {
+    let errors: Vec<String> = check(input).into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(RunError::Invalid(errors.join("\n")));
+    }
+
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut cpu = input.build_cpu().map_err(|err| RunError::Invalid(err.to_string()))?;
+        cpu.track_connections = false;
+
+        // The step that finishes the program counts too.
+        let mut steps = 1;
+        while cpu.step() {
+            if steps >= max_steps {
+                return Err(RunError::StepLimit(max_steps));
+            }
+            steps += 1;
+        }
+        if let Some(fault) = cpu.fault() {
+            return Err(RunError::Halted(fault.to_string()));
+        }
+        Ok(collect_output(&cpu, input, options, steps))
+    }));
+    match outcome {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(RunError::Panicked(message))
+        }
+    }
+}
+
+/// Runs every input on a pool of `threads` worker threads. Results are in
+/// the order of `inputs`, however the work was spread.
+pub fn run_all(
+    inputs      : &[FamInput],
+    threads     : NonZeroUsize,
+    max_steps   : Step,
+    options     : &OutputOptions,
+) -> Vec<RunResult> {
+    let next = AtomicUsize::new(0);
+    let results: Vec<OnceLock<RunResult>> = inputs.iter().map(|_| OnceLock::new()).collect();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.get().min(inputs.len()) {
+            scope.spawn(|| loop {
+                let ix = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(ix) else { break };
+                results[ix].set(run(input, max_steps, options)).unwrap();
+            });
+        }
+    });
+
+    results.into_iter()
+        .map(|result| result.into_inner().expect("every input is run"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::simulation::instruction::Instruction;
+    use crate::application::simulation::talu::TaluOperation;
+    use crate::word::Word;
+
+    const MAX_STEPS: Step = 10_000;
+
+    /// A program of `len` no-ops, with main memory holding `id`.
+    fn no_ops(id: Word, len: usize) -> FamInput {
+        FamInput { program: vec![Instruction::NoOp; len], main_memory: vec![id], ..Default::default() }
+    }
+
+    fn forever() -> FamInput {
+        FamInput { program: vec![Instruction::Jump { addr: 0 }], ..Default::default() }
+    }
+
+    /// Runs an operation `TaluCore::execute` doesn't have yet.
+    fn unimplemented() -> FamInput {
+        let select_part = TaluOperation::SelectPart {
+            activation_input: 1, data_input: 2, selection_input: 3, data_output_0: 4, activation_output: None,
+        };
+        FamInput {
+            program: vec![Instruction::SetTaluConfig { talu_addr: 0, talu_config: select_part }, Instruction::NoOp],
+            registers: [(1, 1)].into(),
+            ..Default::default()
+        }
+    }
+
+    fn threads(n: usize) -> NonZeroUsize {
+        NonZeroUsize::new(n).unwrap()
+    }
+
+    #[test]
+    fn results_are_in_input_order() {
+        let lengths = [3000, 1, 500, 0, 2000, 2, 40, 1000];
+        let inputs: Vec<FamInput> = lengths.iter().enumerate()
+            .map(|(id, len)| no_ops(id as Word, *len))
+            .collect();
+        let options = OutputOptions { statistics: true, ..Default::default() };
+        for n in [1, 3, 16] {
+            let results = run_all(&inputs, threads(n), MAX_STEPS, &options);
+            let reports: Vec<(Vec<Word>, Option<u64>)> = results.into_iter().map(|result| match result {
+                Ok(RunOutput::Report(report)) => (report.main_memory, report.instructions),
+                other => panic!("{other:?}"),
+            }).collect();
+            let expected: Vec<(Vec<Word>, Option<u64>)> = lengths.iter().enumerate()
+                .map(|(id, len)| (vec![id as Word], Some(*len as u64)))
+                .collect();
+            assert_eq!(reports, expected, "on {n} threads");
+        }
+    }
+
+    #[test]
+    fn stops_programs_at_the_step_limit() {
+        assert_eq!(run(&forever(), 50, &OutputOptions::default()), Err(RunError::StepLimit(50)));
+        // The step that finishes the program counts.
+        let steps = |max_steps| run(&no_ops(0, 3), max_steps, &OutputOptions::default()).err();
+        let needed = (1..100).find(|max_steps| steps(*max_steps).is_none()).unwrap();
+        assert_eq!(steps(needed - 1), Some(RunError::StepLimit(needed - 1)));
+        let options = OutputOptions { statistics: true, ..Default::default() };
+        let Ok(RunOutput::Report(report)) = run(&no_ops(0, 3), needed, &options) else { panic!() };
+        assert_eq!(report.steps, Some(needed));
+    }
+
+    #[test]
+    fn panics_are_results() {
+        assert_eq!(
+            run(&unimplemented(), MAX_STEPS, &OutputOptions::default()),
+            Err(RunError::Panicked("not yet implemented".to_string())),
+        );
+    }
+
+    #[test]
+    fn failed_runs_dont_stop_the_others() {
+        let inputs = [no_ops(7, 5), forever(), unimplemented(), no_ops(8, 5)];
+        let results = run_all(&inputs, threads(2), 200, &OutputOptions::default());
+        assert_eq!(results, [
+            Ok(RunOutput::Memory(vec![7])),
+            Err(RunError::StepLimit(200)),
+            Err(RunError::Panicked("not yet implemented".to_string())),
+            Ok(RunOutput::Memory(vec![8])),
+        ]);
+    }
+}
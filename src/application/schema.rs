@@ -0,0 +1,325 @@
+use std::fmt::{Display, Formatter};
+use jsonschema::error::{TypeKind, ValidationErrorKind};
+use jsonschema::{JsonType, ValidationError};
+use itertools::Itertools;
+use schemars::generate::SchemaSettings;
+use schemars::transform::RecursiveTransform;
+use schemars::{JsonSchema, Schema};
+use serde_json::Value;
+use crate::application::format::DataFormat;
+use crate::application::input::FamInput;
+use crate::application::output::RunOutput;
+use crate::application::runner::RunResult;
+
+/// A kind of JSON document the binaries read or write, each described by a
+/// JSON Schema generated from the Rust types that read or write it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Document {
+    /// A `FamInput`, as `application` and `fam_check` read.
+    Input,
+    /// An array of inputs, as `run_all` reads.
+    Inputs,
+    /// What `application` writes, see `RunOutput`.
+    Output,
+    /// An array with a result per input, as `run_all` writes.
+    Results,
+}
+
+impl Document {
+    pub const ALL: [Document; 4] = [Document::Input, Document::Inputs, Document::Output, Document::Results];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Document::Input => "input",
+            Document::Inputs => "inputs",
+            Document::Output => "output",
+            Document::Results => "results",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|document| document.name() == name)
+    }
+
+    /// The schema of the document. Inputs are described as they're read,
+    /// outputs as they're written, so e.g. defaulted input fields aren't
+    /// required and output fields left out when empty aren't either.
+    pub fn schema(self) -> Schema {
+        let settings = SchemaSettings::draft2020_12();
+        let input_settings = settings.clone().for_deserialize().with_transform(RecursiveTransform(deny_unknown_fields));
+        match self {
+            Document::Input => schema_for::<FamInput>(input_settings),
+            Document::Inputs => schema_for::<Vec<FamInput>>(input_settings),
+            Document::Output => schema_for::<RunOutput>(settings.for_serialize()),
+            Document::Results => schema_for::<Vec<RunResult>>(settings.for_serialize()),
+        }
+    }
+
+    /// `schema` as the JSON text `fam_schema` prints, and the files in
+    /// `schema/` hold.
+    pub fn schema_text(self) -> String {
+        DataFormat::Json.write(&self.schema()) + "\n"
+    }
+
+    /// Name of the file in `schema/` holding `schema_text`.
+    pub fn file_name(self) -> String {
+        format!("{}.schema.json", self.name())
+    }
+
+    /// Checks a JSON document against `schema`, giving the problems found,
+    /// or none if it's valid.
+    pub fn validate(self, document: &Value) -> Vec<SchemaError> {
+        let schema = self.schema();
+        let validator = jsonschema::validator_for(schema.as_value())
+            .expect("generated schemas are valid JSON Schemas");
+        let mut errors: Vec<SchemaError> = Vec::new();
+        for error in validator.iter_errors(document).flat_map(|error| SchemaError::from_validation(&error)) {
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+        errors
+    }
+}
+
+fn schema_for<T: JsonSchema>(settings: SchemaSettings) -> Schema {
+    settings.into_generator().into_root_schema_for::<T>()
+}
+
+/// Inputs are read ignoring fields nothing reads, which in a generated
+/// input is nearly always a typo, like a misspelled optional
+/// `activation_output` that leaves it unset. Their schemas don't allow any.
+fn deny_unknown_fields(schema: &mut Schema) {
+    if schema.get("properties").is_some() && schema.get("additionalProperties").is_none() {
+        schema.insert("additionalProperties".to_string(), false.into());
+    }
+}
+
+/// A way a document doesn't fit its schema, at a path written as
+/// `ParseError` writes them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SchemaError {
+    /// Fields and indices from the root to the value, e.g.
+    /// `program[12].SetTaluConfig`, or `.` at the root.
+    pub path    : String,
+    pub message : String,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.path != "." {
+            write!(f, "in `{}`: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl SchemaError {
+    /// `error` as something a person can act on. A value matching none of
+    /// the shapes it may take, like an instruction with a typo in one of
+    /// its fields, is reported by where the closest shape stops matching
+    /// rather than as a mismatch with all of them.
+    fn from_validation(error: &ValidationError) -> Vec<SchemaError> {
+        let branches = match error.kind() {
+            ValidationErrorKind::OneOfNotValid { context } | ValidationErrorKind::AnyOf { context } => context,
+            _ => return vec![SchemaError::at(error, message(error))],
+        };
+        let depth = |error: &ValidationError| error.instance_path().as_str().matches('/').count();
+        let branch_depth = |branch: &Vec<ValidationError>| branch.iter().map(depth).max().unwrap_or(0);
+        let deepest = branches.iter().max_by_key(|branch| branch_depth(branch));
+        if let Some(branch) = deepest
+            && branch_depth(branch) > depth(error)
+        {
+            return branch.iter().flat_map(SchemaError::from_validation).collect();
+        }
+
+        // Nothing got further than the value itself: say what it could be.
+        let mut expected: Vec<String> = Vec::new();
+        for branch in branches {
+            // A branch's type only matters if nothing more precise failed.
+            let mut shapes: Vec<String> = branch.iter().filter_map(|error| match error.kind() {
+                ValidationErrorKind::Required { property } => Some(format!("an object with `{}`", unquoted(property))),
+                ValidationErrorKind::Constant { expected_value } => Some(format!("`{}`", unquoted(expected_value))),
+                ValidationErrorKind::Enum { options } => Some(one_of(options)),
+                ValidationErrorKind::Pattern { pattern } => Some(format!("a string matching `{pattern}`")),
+                _ => None,
+            }).collect();
+            if shapes.is_empty() {
+                shapes = branch.iter().filter_map(|error| match error.kind() {
+                    ValidationErrorKind::Type { kind } => Some(of_type(kind)),
+                    _ => None,
+                }).collect();
+            }
+            for shape in shapes {
+                if !expected.contains(&shape) {
+                    expected.push(shape);
+                }
+            }
+        }
+        let message = match expected.is_empty() {
+            true => message(error),
+            false => format!("{} isn't any of: {}", describe(error.instance()), expected.join(", ")),
+        };
+        vec![SchemaError::at(error, message)]
+    }
+
+    fn at(error: &ValidationError, message: String) -> SchemaError {
+        SchemaError { path: path_of_pointer(error.instance_path().as_str()), message }
+    }
+}
+
+/// `/program/12/SetTaluConfig` as `program[12].SetTaluConfig`.
+fn path_of_pointer(pointer: &str) -> String {
+    let mut path = String::new();
+    for segment in pointer.split('/').skip(1) {
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+            path += &format!("[{segment}]");
+        } else {
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path += &segment;
+        }
+    }
+    if path.is_empty() {
+        path.push('.');
+    }
+    path
+}
+
+/// The message of `error`, in the words `ParseError`s use where there are
+/// some.
+fn message(error: &ValidationError) -> String {
+    match error.kind() {
+        ValidationErrorKind::Required { property } => format!("missing field `{}`", unquoted(property)),
+        ValidationErrorKind::AdditionalProperties { unexpected } => {
+            unexpected.iter().map(|field| format!("unknown field `{field}`")).join(", ")
+        }
+        ValidationErrorKind::Type { kind } => format!("{} isn't {}", describe(error.instance()), of_type(kind)),
+        ValidationErrorKind::Enum { options } => format!("{} isn't any of: {}", describe(error.instance()), one_of(options)),
+        _ => error.to_string(),
+    }
+}
+
+fn of_type(kind: &TypeKind) -> String {
+    match kind {
+        TypeKind::Single(ty) => a(*ty),
+        TypeKind::Multiple(types) => types.iter().map(a).join(" or "),
+    }
+}
+
+fn one_of(options: &Value) -> String {
+    match options.as_array() {
+        Some(options) => options.iter().map(|option| format!("`{}`", unquoted(option))).join(", "),
+        None => unquoted(options),
+    }
+}
+
+/// `ty` with an article, e.g. `an integer`.
+fn a(ty: JsonType) -> String {
+    match ty {
+        JsonType::Array | JsonType::Integer | JsonType::Object => format!("an {ty}"),
+        _ => format!("a {ty}"),
+    }
+}
+
+fn unquoted(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// A short description of a value, for messages.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Object(object) if object.len() == 1 => {
+            format!("an object with `{}`", object.keys().next().unwrap())
+        }
+        Value::Object(_) => "the object".to_string(),
+        Value::Array(_) => "the array".to_string(),
+        other => format!("`{}`", unquoted(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn sample() -> Value {
+        serde_json::from_str(include_str!("../../sample.json")).unwrap()
+    }
+
+    fn error(path: &str, message: &str) -> SchemaError {
+        SchemaError { path: path.to_string(), message: message.to_string() }
+    }
+
+    #[test]
+    fn sample_is_a_valid_input() {
+        assert_eq!(Document::Input.validate(&sample()), []);
+        assert_eq!(Document::Inputs.validate(&Value::Array(vec![sample(), sample()])), []);
+    }
+
+    #[test]
+    fn misspelled_fields_are_reported_where_they_are() {
+        let mut document = sample();
+        let cmp = &mut document["program"][6]["SetTaluConfig"]["talu_config"]["Cmp"];
+        let activation_output = cmp.as_object_mut().unwrap().remove("activation_output").unwrap();
+        cmp["activation_ouput"] = activation_output;
+        assert_eq!(Document::Input.validate(&document), [
+            error("program[6].SetTaluConfig.talu_config.Cmp", "unknown field `activation_ouput`"),
+        ]);
+    }
+
+    #[test]
+    fn fields_of_the_wrong_type_are_reported_where_they_are() {
+        let mut document = sample();
+        document["program"][8]["SetTaluConfig"]["talu_config"]["Add"]["data_input_1"] = "r1".into();
+        document["main_memory"][0] = 1.5.into();
+        assert_eq!(Document::Input.validate(&document), [
+            error("main_memory[0]", "`1.5` isn't an integer"),
+            error("program[8].SetTaluConfig.talu_config.Add.data_input_1", "`r1` isn't an integer"),
+        ]);
+    }
+
+    #[test]
+    fn values_matching_no_shape_say_what_they_could_be() {
+        let mut document = sample();
+        document["program"][0] = serde_json::json!({ "Wait": { "register_index": 3 } });
+        document["propagation"] = "Instant".into();
+        let errors = Document::Input.validate(&document);
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(errors[0].path, "program[0]");
+        assert!(errors[0].message.starts_with("an object with `Wait` isn't any of: "), "{}", errors[0].message);
+        assert!(errors[0].message.contains("an object with `WaitForActivationSignal`"), "{}", errors[0].message);
+        assert_eq!(errors[1].path, "propagation");
+        assert!(errors[1].message.starts_with("`Instant` isn't any of: "), "{}", errors[1].message);
+    }
+
+    #[test]
+    fn paths_escape_like_json_pointers() {
+        assert_eq!(path_of_pointer(""), ".");
+        assert_eq!(path_of_pointer("/program/12/SetTaluConfig"), "program[12].SetTaluConfig");
+        assert_eq!(path_of_pointer("/a~1b/c~0d"), "a/b.c~d");
+    }
+
+    /// The schemas in `schema/` are regenerated with `fam_schema --write
+    /// schema` whenever the types they describe change.
+    #[test]
+    fn committed_schemas_are_up_to_date() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schema");
+        for document in Document::ALL {
+            let path = dir.join(document.file_name());
+            let committed = std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+            assert!(
+                committed == document.schema_text(),
+                "{} is out of date, run `fam_schema --write schema`",
+                path.display(),
+            );
+        }
+    }
+}
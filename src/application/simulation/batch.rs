@@ -0,0 +1,464 @@
+use std::array;
+use std::fmt::{Display, Formatter};
+use crate::application::input::{BuildError, FamInput};
+use crate::application::simulation::controller::{Controller, ControllerExecutionState};
+use crate::application::simulation::cpu_registers::{CpuRegisterBank, CpuRegisterDataReader, REGISTER_COUNT};
+use crate::application::simulation::instruction_reader::{InstructionMemory, WORDS_PER_INSTRUCTION};
+use crate::application::simulation::main_memory::{MainMemory, MainMemoryDataReader};
+use crate::application::simulation::plan::{ExecutionPlan, SimulationBackend};
+use crate::application::simulation::propagation::{evaluation_order_of, CombinationalLoop, PropagationModel};
+use crate::application::simulation::simulation::Cpu;
+use crate::application::simulation::talu::{TALU_COUNT, TaluAddress, TaluBank, TaluCore};
+use crate::word::{ToActivation, Word};
+use crate::PROGRAM_COUNTER_REGISTER_ADDR;
+
+/// Runs one program over many inputs in lockstep. While the lanes agree on
+/// every register the controller reads, they share one controller and one
+/// compiled TALU configuration, with registers and TALUs stored per lane
+/// side by side. A lane that disagrees leaves the lockstep group and
+/// carries on as its own `Cpu`. Every lane runs every TALU on every step,
+/// which gives the same results as either `TaluScheduling`.
+pub struct BatchCpu {
+    propagation         : PropagationModel,
+
+    controller          : Controller,
+    instruction_memory  : InstructionMemory,
+    is_done             : bool,
+    plan                : Option<ExecutionPlan>,
+    talu_order          : Option<Vec<TaluAddress>>,
+    combinational_loop  : Option<CombinationalLoop>,
+
+    /// Lane index of each column still in lockstep.
+    lanes               : Vec<usize>,
+    /// `registers[reg][column]`.
+    registers           : Box<[Vec<Word>; REGISTER_COUNT]>,
+    /// `talus[addr][column]`.
+    talus               : Box<[Vec<TaluCore>; TALU_COUNT]>,
+    main_memories       : Vec<MainMemory>,
+
+    diverged            : Vec<(usize, Cpu)>,
+}
+
+/// Why inputs can't be run as one batch.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BatchError {
+    /// The input of a lane can't be built into a `Cpu`.
+    Build { lane: usize, error: BuildError },
+    /// A lane has another program, latency table, propagation model or
+    /// code region than the first. Only main memory, data sections and
+    /// registers may differ, and in von Neumann mode the program too, as
+    /// it's loaded into main memory.
+    Differs { lane: usize },
+}
+
+impl Display for BatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchError::Build { lane, error } => write!(f, "lane {lane}: {error}"),
+            BatchError::Differs { lane } => write!(f, "lane {lane} runs another program than lane 0"),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+impl BatchCpu {
+    /// A lane per input, each starting as `FamInput::build_cpu` would.
+    pub fn new(inputs: &[FamInput]) -> Result<Self, BatchError> {
+        let shared = |input: &FamInput| FamInput {
+            program: if input.code_region.is_some() { Vec::new() } else { input.program.clone() },
+            main_memory: Vec::new(),
+            data: Vec::new(),
+            registers: Default::default(),
+            ..input.clone()
+        };
+        let lead = inputs.first().map(shared);
+        let mut cpus = Vec::with_capacity(inputs.len());
+        for (lane, input) in inputs.iter().enumerate() {
+            if lead.as_ref() != Some(&shared(input)) {
+                return Err(BatchError::Differs { lane });
+            }
+            cpus.push(input.build_cpu().map_err(|error| BatchError::Build { lane, error })?);
+        }
+        Ok(Self::from_cpus(cpus))
+    }
+
+    /// Lanes from machines that have yet to take a step, all built from
+    /// the same program.
+    fn from_cpus(cpus: Vec<Cpu>) -> Self {
+        let lane_count = cpus.len();
+        let mut cpus = cpus.into_iter();
+        let lead = cpus.next();
+        let propagation = lead.as_ref().map_or_else(Default::default, |cpu| cpu.propagation);
+        let (controller, instruction_memory) = match &lead {
+            Some(cpu) => (cpu.controller.clone(), cpu.instruction_memory.clone()),
+            None => {
+                let instruction_memory = InstructionMemory::new(Vec::new());
+                (Controller::new(&instruction_memory), instruction_memory)
+            }
+        };
+
+        let mut registers: Box<[Vec<Word>; REGISTER_COUNT]> =
+            Box::new(array::from_fn(|_| Vec::with_capacity(lane_count)));
+        let mut talus: Box<[Vec<TaluCore>; TALU_COUNT]> =
+            Box::new(array::from_fn(|_| Vec::with_capacity(lane_count)));
+        let mut main_memories = Vec::with_capacity(lane_count);
+        for cpu in lead.into_iter().chain(cpus) {
+            for (reg, register) in cpu.register_bank.components.iter().enumerate() {
+                registers[reg].push(register.value);
+            }
+            for (addr, talu) in (*cpu.talu_bank.components).into_iter().enumerate() {
+                talus[addr].push(talu);
+            }
+            main_memories.push(cpu.main_memory);
+        }
+
+        BatchCpu {
+            propagation,
+            controller,
+            instruction_memory,
+            is_done             : false,
+            plan                : None,
+            talu_order          : None,
+            combinational_loop  : None,
+            lanes               : (0..lane_count).collect(),
+            registers,
+            talus,
+            main_memories,
+            diverged            : Vec::new(),
+        }
+    }
+
+    pub fn lane_count(&self) -> usize {
+        self.lanes.len() + self.diverged.len()
+    }
+
+    /// Lanes still sharing the controller.
+    pub fn lockstep_lanes(&self) -> &[usize] {
+        &self.lanes
+    }
+
+    pub fn registers(&self, lane: usize) -> [Word; REGISTER_COUNT] {
+        match self.column(lane) {
+            Some(column) => array::from_fn(|reg| self.registers[reg][column]),
+            None => {
+                let cpu = self.diverged_cpu(lane);
+                array::from_fn(|reg| cpu.register_bank.components[reg].value)
+            }
+        }
+    }
+
+    pub fn main_memory(&self, lane: usize) -> &MainMemory {
+        match self.column(lane) {
+            Some(column) => &self.main_memories[column],
+            None => &self.diverged_cpu(lane).main_memory,
+        }
+    }
+
+    pub fn is_done(&self, lane: usize) -> bool {
+        match self.column(lane) {
+            Some(_) => self.is_done,
+            None => self.diverged_cpu(lane).is_done,
+        }
+    }
+
+    /// TALUs of `lane` in a combinational loop, see `Cpu::combinational_loop`.
+    pub fn combinational_loop(&self, lane: usize) -> Option<&CombinationalLoop> {
+        match self.column(lane) {
+            Some(_) => self.combinational_loop.as_ref(),
+            None => self.diverged_cpu(lane).combinational_loop.as_ref(),
+        }
+    }
+
+    fn column(&self, lane: usize) -> Option<usize> {
+        self.lanes.iter().position(|&l| l == lane)
+    }
+
+    fn diverged_cpu(&self, lane: usize) -> &Cpu {
+        self.diverged.iter()
+            .find(|(l, _)| *l == lane)
+            .map(|(_, cpu)| cpu)
+            .expect("lane out of range")
+    }
+
+    /// Steps every lane, returning whether any of them is still running.
+    #[must_use]
+    pub fn step(&mut self) -> bool {
+        let lockstep_running = !self.is_done && !self.lanes.is_empty();
+        if lockstep_running {
+            self.split_diverging_lanes();
+        }
+        let mut running = false;
+        for (_, cpu) in self.diverged.iter_mut() {
+            running |= cpu.step();
+        }
+        if lockstep_running {
+            running |= self.step_lockstep();
+        }
+        running
+    }
+
+    /// Moves every lane that disagrees with the first one on a register or
+    /// memory word the controller is about to read out of the lockstep group.
+    fn split_diverging_lanes(&mut self) {
+        let mut watched = [None; 2];
+        watched[0] = Some((PROGRAM_COUNTER_REGISTER_ADDR, false));
+        if let CpuRegisterDataReader::Active { source, .. } = self.controller.cpu_registers_reader {
+            match self.controller.state {
+                ControllerExecutionState::WaitingForActivation => watched[1] = Some((source, true)),
+                ControllerExecutionState::LoadingTaluConfig => watched[1] = Some((source, false)),
+                _ => {}
+            }
+        }
+        // In von Neumann mode every lane runs the code in its own memory.
+        let watched_code = self.controller.instruction_reader.code_region
+            .filter(|_| self.controller.state == ControllerExecutionState::Processing)
+            .and_then(|region| region.address_of(self.registers[PROGRAM_COUNTER_REGISTER_ADDR][0]))
+            .map(|addr| addr..addr + WORDS_PER_INSTRUCTION);
+        let watched_memory = match self.controller.main_memory_reader {
+            MainMemoryDataReader::Active { addr, .. } => Some(addr..addr + 1),
+            MainMemoryDataReader::Deactivated => watched_code,
+        };
+
+        for column in (1..self.lanes.len()).rev() {
+            let diverges = watched.iter().flatten().any(|&(reg, as_activation)| {
+                let (lead, own) = (self.registers[reg][0], self.registers[reg][column]);
+                if as_activation {
+                    lead.to_activation() != own.to_activation()
+                } else {
+                    lead != own
+                }
+            }) || watched_memory.clone().is_some_and(|range| {
+                let lead = self.main_memories[0].0.read().unwrap();
+                let own = self.main_memories[column].0.read().unwrap();
+                lead.get(range.clone()) != own.get(range)
+            });
+            if diverges {
+                self.split_lane(column);
+            }
+        }
+    }
+
+    fn split_lane(&mut self, column: usize) {
+        let lane = self.lanes.remove(column);
+
+        let mut register_bank = CpuRegisterBank::new();
+        for (reg, values) in self.registers.iter_mut().enumerate() {
+            register_bank.components[reg].value = values.remove(column);
+        }
+        let talu_bank = TaluBank {
+            components: Box::new(array::from_fn(|addr| self.talus[addr].remove(column))),
+        };
+
+        let mut cpu = Cpu::from_components(
+            talu_bank,
+            register_bank,
+            self.controller.clone(),
+            self.instruction_memory.clone(),
+            self.main_memories.remove(column),
+        );
+        cpu.propagation = self.propagation;
+        cpu.combinational_loop = self.combinational_loop.clone();
+        cpu.backend = SimulationBackend::Compiled;
+        cpu.track_connections = false;
+        self.diverged.push((lane, cpu));
+    }
+
+    /// `Cpu::step` for the lockstep group.
+    fn step_lockstep(&mut self) -> bool {
+        if let Some(req) = self.controller.cpu_registers_reader.get_read_request() {
+            let value = self.registers[*req.addr()][0];
+            req.satisfy_with(value);
+        }
+        if let Some(req) = self.controller.main_memory_reader.get_read_request() {
+            req.satisfy(&self.main_memories[0]);
+        }
+        if let Some(req) = self.controller.instruction_reader.program_counter_reader.get_read_request() {
+            let value = self.registers[*req.addr()][0];
+            req.satisfy_with(value);
+        }
+        if let Some(req) = self.controller.get_instruction_fetch_request() {
+            req.satisfy(&self.main_memories[0]);
+        }
+
+        if let Some(config_write_request) = self.controller.talu_config_writer.get_config_write_request() {
+            let operation = *config_write_request.operation();
+            let targets = match config_write_request.address() {
+                Some(addr) => *addr..*addr + 1,
+                None => 0..TALU_COUNT,
+            };
+            for addr in targets {
+                for talu in self.talus[addr].iter_mut() {
+                    talu.set_new_operation(operation);
+                }
+            }
+            self.plan = None;
+            self.talu_order = None;
+        }
+
+        if !self.controller.execute() {
+            self.is_done = true;
+        }
+
+        let plan = self.plan.take().unwrap_or_else(||
+            ExecutionPlan::compile_talus(self.talus.iter().map(|column| &column[0]))
+        );
+        match self.propagation {
+            PropagationModel::Registered => {
+                for addr in 0..TALU_COUNT {
+                    self.read_talu_inputs(addr);
+                }
+                for column in self.talus.iter_mut() {
+                    for talu in column.iter_mut() {
+                        plan.execute(talu);
+                    }
+                }
+                for addr in 0..TALU_COUNT {
+                    self.write_talu_outputs(addr);
+                }
+            }
+            PropagationModel::Combinational => {
+                let order = match self.talu_order.take() {
+                    Some(order) => order,
+                    None => {
+                        let (order, combinational_loop) =
+                            evaluation_order_of(self.talus.iter().map(|column| &column[0].operation));
+                        self.combinational_loop = combinational_loop;
+                        order
+                    }
+                };
+                for &addr in order.iter() {
+                    self.read_talu_inputs(addr);
+                    for talu in self.talus[addr].iter_mut() {
+                        plan.execute(talu);
+                    }
+                    self.write_talu_outputs(addr);
+                }
+                self.talu_order = Some(order);
+            }
+        }
+        self.plan = Some(plan);
+
+        if let Some(req) = self.controller.cpu_registers_writer.get_write_request() {
+            self.registers[*req.addr()].fill(*req.value());
+        }
+        if let Some(req) = self.controller.instruction_reader.program_counter_writer.get_write_request() {
+            self.registers[*req.addr()].fill(*req.value());
+        }
+
+        !self.is_done
+    }
+
+    fn read_talu_inputs(&mut self, addr: TaluAddress) {
+        for (column, talu) in self.talus[addr].iter_mut().enumerate() {
+            for (_, req) in talu.collect_read_requests() {
+                let value = self.registers[*req.addr()][column];
+                req.satisfy_with(value);
+            }
+        }
+    }
+
+    fn write_talu_outputs(&mut self, addr: TaluAddress) {
+        for (column, talu) in self.talus[addr].iter().enumerate() {
+            for (_, req) in talu.collect_write_requests() {
+                self.registers[*req.addr()][column] = *req.value();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::examples::make_loop_program;
+    use crate::application::simulation::instruction::Instruction;
+    use crate::Step;
+
+    const MAX_STEPS: Step = 1_000;
+
+    /// Steps a batch of `inputs` and a `Cpu` per input side by side,
+    /// comparing every lane after every step.
+    fn compare_with_own_runs(inputs: &[FamInput]) -> BatchCpu {
+        let mut batch = BatchCpu::new(inputs).unwrap();
+        let mut cpus: Vec<Cpu> = inputs.iter().map(|input| input.build_cpu().unwrap()).collect();
+        for step in 1..=MAX_STEPS {
+            let batch_running = batch.step();
+            let mut running = false;
+            for (lane, cpu) in cpus.iter_mut().enumerate() {
+                running |= cpu.step();
+                let registers: Vec<Word> = cpu.register_bank.components.iter().map(|reg| reg.value).collect();
+                assert_eq!(batch.registers(lane).to_vec(), registers, "lane {lane} registers at step {step}");
+                assert_eq!(
+                    *batch.main_memory(lane).0.read().unwrap(),
+                    *cpu.main_memory.0.read().unwrap(),
+                    "lane {lane} main memory at step {step}",
+                );
+                assert_eq!(batch.is_done(lane), cpu.is_done, "lane {lane} finishing at step {step}");
+                assert_eq!(batch.combinational_loop(lane), cpu.combinational_loop.as_ref(), "lane {lane} loop at step {step}");
+            }
+            assert_eq!(batch_running, running, "finished apart at step {step}");
+            if !running {
+                break;
+            }
+        }
+        batch
+    }
+
+    fn loop_lanes() -> Vec<FamInput> {
+        (0..4)
+            .map(|lane| {
+                let mut input = make_loop_program();
+                input.data[0].words = (1..=5).map(|word| word * (lane + 1)).collect();
+                input.registers.insert(40, lane);
+                input
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lanes_match_their_own_runs() {
+        let batch = compare_with_own_runs(&loop_lanes());
+        assert_eq!(batch.lockstep_lanes(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn lanes_match_their_own_runs_combinationally() {
+        let inputs: Vec<FamInput> = loop_lanes().into_iter()
+            .map(|input| FamInput { propagation: PropagationModel::Combinational, ..input })
+            .collect();
+        let batch = compare_with_own_runs(&inputs);
+        assert!(batch.combinational_loop(0).is_some());
+    }
+
+    #[test]
+    fn lanes_the_controller_tells_apart_leave_the_lockstep() {
+        let program = vec![
+            Instruction::WaitForActivationSignal { register_index: 40 },
+            Instruction::SetLiteral { literal: 7, reg_addr: 41 },
+        ];
+        let inputs: Vec<FamInput> = [1, 0, 1]
+            .into_iter()
+            .map(|activation| FamInput {
+                program: program.clone(),
+                registers: [(40, activation)].into(),
+                ..Default::default()
+            })
+            .collect();
+        let batch = compare_with_own_runs(&inputs);
+        assert_eq!(batch.lockstep_lanes(), [0, 2]);
+    }
+
+    #[test]
+    fn lanes_must_run_the_same_program() {
+        let mut inputs = loop_lanes();
+        inputs[2].talu_latencies.per_operation.insert("Add".to_string(), 3);
+        assert_eq!(BatchCpu::new(&inputs).err(), Some(BatchError::Differs { lane: 2 }));
+
+        let mut inputs = loop_lanes();
+        inputs[1].registers.insert(REGISTER_COUNT, 0);
+        assert_eq!(
+            BatchCpu::new(&inputs).err(),
+            Some(BatchError::Build { lane: 1, error: BuildError::NoSuchRegister(REGISTER_COUNT) }),
+        );
+    }
+}
@@ -1,28 +1,67 @@
 use crate::application::grid::component::PortName;
-use crate::application::simulation::talu::{TaluAddress, TaluOperation, TaluBank};
-use crate::application::simulation::cpu_registers::{CpuRegisterDataReader, CpuRegisterDataWriter};
+use crate::application::simulation::talu::{TaluAddress, TaluOperation, TaluBank, TALU_COUNT};
+use crate::application::simulation::cpu_registers::{CpuRegisterAddress, CpuRegisterDataReader, CpuRegisterDataWriter, REGISTER_COUNT};
+use crate::application::simulation::encoding::{decode_operation, from_words};
 use crate::application::simulation::instruction::Instruction;
+use crate::application::simulation::main_memory::MainMemoryDataReader;
 use crate::application::simulation::instruction_reader::IncrementCmd::{GoTo, Increment, NoIncrement};
-use crate::application::simulation::instruction_reader::{InstructionMemory, InstructionReader};
-use crate::word::ToActivation;
+use crate::application::simulation::instruction_reader::{InstructionFetchRequest, InstructionMemory, InstructionReader};
+use crate::word::{ToActivation, Word};
 use std::fmt::Debug;
 
-#[derive( PartialEq, Eq, Copy, Clone, Debug, )]
+#[derive( PartialEq, Eq, Copy, Clone, Debug, serde::Serialize, schemars::JsonSchema)]
 pub enum ControllerExecutionState {
 	ReadingInstruction,
 	Processing,
 	WaitingForActivation,
+	/// Reading the words of a `LoadTaluConfigFrom*` instruction, one per step.
+	LoadingTaluConfig,
+	/// Stopped on something the program can't go on from, like loading a
+	/// configuration that doesn't decode, see `Controller::fault`.
+	Halted,
 }
+
+/// Where a `LoadTaluConfigFrom*` instruction reads the encoded operation.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+enum ConfigSource {
+	Registers { config_reg: CpuRegisterAddress },
+	Memory { mem_addr_reg: CpuRegisterAddress },
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+enum ConfigLoadStage {
+	TaluAddress,
+	MemoryAddress,
+	LowWord,
+	HighWord,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+struct ConfigLoad {
+	source		: ConfigSource,
+	stage		: ConfigLoadStage,
+	talu_addr	: Word,
+	mem_addr	: usize,
+	low_word	: Word,
+}
+#[derive(Clone)]
 pub struct Controller{
 	pub state					: ControllerExecutionState,
 
 	pub cpu_registers_reader	: CpuRegisterDataReader,
 	pub cpu_registers_writer	: CpuRegisterDataWriter,
+	pub main_memory_reader		: MainMemoryDataReader,
 	
 	pub talu_config_writer		: TaluConfigWriter	,
 	pub instruction_reader  	: InstructionReader,
+	/// Instructions decoded so far, each counted once however many steps
+	/// it takes.
+	pub instructions_executed	: u64,
+	/// Why the controller halted, once it has.
+	pub fault					: Option<String>,
 	
 	previous_instruction		: Option<Instruction>,
+	config_load					: Option<ConfigLoad>,
 }
 
 impl Controller{
@@ -37,14 +76,26 @@ impl Controller{
 			
 		Controller{
 			previous_instruction: None,
+			config_load			: None,
 			cpu_registers_reader: CpuRegisterDataReader::new(),
 			cpu_registers_writer: CpuRegisterDataWriter::new(),
+			main_memory_reader	: MainMemoryDataReader::new(),
 			talu_config_writer   : configurator,
 			instruction_reader,
+			instructions_executed: 0,
+			fault				: None,
 			state				: ControllerExecutionState::ReadingInstruction,
 		}	
 	}
 
+	/// The fetch of the instruction about to be decoded, in von Neumann mode.
+	pub fn get_instruction_fetch_request(&mut self) -> Option<InstructionFetchRequest<'_>> {
+		if self.state != ControllerExecutionState::Processing {
+			return None;
+		}
+		self.instruction_reader.get_fetch_request()
+	}
+
 	pub fn reset_outputs(&mut self){
 		self.talu_config_writer 	  = TaluConfigWriter::Deactivated;
 		self.cpu_registers_writer = CpuRegisterDataWriter::Deactivated;
@@ -59,11 +110,12 @@ impl Controller{
 				self.state = ControllerExecutionState::Processing;
 			}
 			ControllerExecutionState::Processing => {
-				let Some(current_instruction) = self.instruction_reader.read().map(|i| i.to_owned()) else
-				{
-					return
-					false
+				let current_instruction = match self.instruction_reader.read() {
+					Ok(Some(instruction)) => instruction,
+					Ok(None) => return false,
+					Err(message) => return self.halt(message),
 				};
+				self.instructions_executed += 1;
 
 				match current_instruction {
 					Instruction::SetTaluConfig {  talu_config, talu_addr, } => {
@@ -96,12 +148,23 @@ impl Controller{
 						self.instruction_reader.set_increment_cmd(Increment);
 						self.state = ControllerExecutionState::ReadingInstruction;
 					}
+					Instruction::LoadTaluConfigFromRegisters { talu_addr_reg, config_reg } => {
+						self.begin_config_load(talu_addr_reg, ConfigSource::Registers { config_reg });
+					}
+					Instruction::LoadTaluConfigFromMemory { talu_addr_reg, mem_addr_reg } => {
+						self.begin_config_load(talu_addr_reg, ConfigSource::Memory { mem_addr_reg });
+					}
 					Instruction::NoOp => {
 						self.instruction_reader.set_increment_cmd(Increment);
 						self.state = ControllerExecutionState::ReadingInstruction;
 					}
 				}
 			}
+			ControllerExecutionState::LoadingTaluConfig => {
+				if let Err(message) = self.continue_config_load() {
+					return self.halt(message);
+				}
+			}
 			ControllerExecutionState::WaitingForActivation => {
 				let is_activated = self.cpu_registers_reader.read().unwrap().to_activation();
 				if is_activated.into() {
@@ -111,13 +174,117 @@ impl Controller{
 					self.instruction_reader.set_increment_cmd(NoIncrement);
 				}
 			}
+			ControllerExecutionState::Halted => return false,
 		}
 
 		self.instruction_reader.step();
 		true
 	}
+
+	/// Stops the program for `message`, the way running off its end does.
+	fn halt(&mut self, message: String) -> bool {
+		self.reset_outputs();
+		self.cpu_registers_reader.deactivate();
+		self.main_memory_reader.deactivate();
+		self.config_load = None;
+		self.fault = Some(message);
+		self.state = ControllerExecutionState::Halted;
+		false
+	}
+
+	fn begin_config_load(&mut self, talu_addr_reg: CpuRegisterAddress, source: ConfigSource) {
+		self.cpu_registers_reader.set_connection(Some(talu_addr_reg));
+		self.config_load = Some(ConfigLoad {
+			source,
+			stage		: ConfigLoadStage::TaluAddress,
+			talu_addr	: 0,
+			mem_addr	: 0,
+			low_word	: 0,
+		});
+		self.instruction_reader.set_increment_cmd(NoIncrement);
+		self.state = ControllerExecutionState::LoadingTaluConfig;
+	}
+
+	/// Takes the word read this step and connects the reader for the next,
+	/// writing the configuration once both words of it are in. Fails on
+	/// words the program computed that don't make a configuration.
+	fn continue_config_load(&mut self) -> Result<(), String> {
+		let mut load = self.config_load.take().expect("a configuration is being loaded");
+		self.instruction_reader.set_increment_cmd(NoIncrement);
+
+		match load.stage {
+			ConfigLoadStage::TaluAddress => {
+				load.talu_addr = self.cpu_registers_reader.read().unwrap();
+				match load.source {
+					ConfigSource::Registers { config_reg } => {
+						self.cpu_registers_reader.set_connection(Some(config_reg));
+						load.stage = ConfigLoadStage::LowWord;
+					}
+					ConfigSource::Memory { mem_addr_reg } => {
+						self.cpu_registers_reader.set_connection(Some(mem_addr_reg));
+						load.stage = ConfigLoadStage::MemoryAddress;
+					}
+				}
+			}
+			ConfigLoadStage::MemoryAddress => {
+				let addr = self.cpu_registers_reader.read().unwrap();
+				load.mem_addr = usize::try_from(addr)
+					.map_err(|_| format!("main memory address {addr} out of range"))?;
+				self.cpu_registers_reader.deactivate();
+				self.main_memory_reader.set_connection(Some(load.mem_addr));
+				load.stage = ConfigLoadStage::LowWord;
+			}
+			ConfigLoadStage::LowWord => {
+				load.low_word = self.read_config_word(load.source, load.mem_addr)?;
+				match load.source {
+					ConfigSource::Registers { config_reg } => {
+						let high_reg = Some(config_reg + 1).filter(|reg| *reg < REGISTER_COUNT)
+							.ok_or_else(|| format!("no register after r{config_reg} for the configuration's high word"))?;
+						self.cpu_registers_reader.set_connection(Some(high_reg));
+					}
+					ConfigSource::Memory { .. } =>
+						self.main_memory_reader.set_connection(Some(load.mem_addr + 1)),
+				}
+				load.stage = ConfigLoadStage::HighWord;
+			}
+			ConfigLoadStage::HighWord => {
+				let high_word = self.read_config_word(load.source, load.mem_addr + 1)?;
+				self.cpu_registers_reader.deactivate();
+				self.main_memory_reader.deactivate();
+
+				let target = usize::try_from(load.talu_addr).ok()
+					.filter(|addr| *addr < TALU_COUNT)
+					.ok_or_else(|| format!("TALU address {} out of range", load.talu_addr))?;
+				let encoded = from_words([load.low_word, high_word]);
+				let op = decode_operation(encoded).map_err(|err| err.to_string())
+					.and_then(|op| match op.parameter_error() {
+						Some(err) => Err(format!("{}: {err}", op.kind_name())),
+						None => Ok(op),
+					})
+					.map_err(|err| format!("can't load TALU configuration {encoded:#018x}: {err}"))?;
+				self.talu_config_writer = TaluConfigWriter::WritingToSingle { target, op };
+
+				self.instruction_reader.set_increment_cmd(Increment);
+				self.state = ControllerExecutionState::ReadingInstruction;
+				return Ok(());
+			}
+		}
+		self.config_load = Some(load);
+		Ok(())
+	}
+
+	/// The configuration word read this step, `mem_addr` being where it
+	/// was read from main memory.
+	fn read_config_word(&self, source: ConfigSource, mem_addr: usize) -> Result<Word, String> {
+		match source {
+			ConfigSource::Registers { .. } => Ok(self.cpu_registers_reader.read().unwrap()),
+			ConfigSource::Memory { .. } => self.main_memory_reader.read()
+				.ok_or_else(|| format!("main memory address {mem_addr} out of range")),
+		}
+	}
 }
 
+#[derive(Clone)]
 pub enum TaluConfigWriter{
 	Deactivated,
 	WritingToSingle{
@@ -180,6 +347,9 @@ impl TaluConfigWriteRequest{
 	pub fn address(&self) -> &Option<TaluAddress>{
 		&self.address
 	}
+	pub fn operation(&self) -> &TaluOperation{
+		&self.operation
+	}
 }
 
 
@@ -218,4 +388,107 @@ impl PortName for ControllerPortName{
 			Self::MainMemoryReader => "mmr",
 		}
 	}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::application::input::FamInput;
+	use crate::application::simulation::encoding::{encode_operation, to_words};
+	use crate::application::simulation::simulation::Cpu;
+	use crate::application::simulation::talu::LUT_MAX_SIZE_LOG2;
+
+	const TALU_ADDR_REG: CpuRegisterAddress = 1;
+	const MEM_ADDR_REG: CpuRegisterAddress = 2;
+	const CONFIG_REG: CpuRegisterAddress = 10;
+	const CONFIG_ADDR: usize = 4;
+
+	/// Inverts r31 into r32 while r30 is high.
+	const NOT: TaluOperation = TaluOperation::Not {
+		activation_input: 30, data_input: 31, data_output: 32, activation_output: None,
+	};
+
+	/// Runs `instruction` then a few no-ops, with TALU address `talu_addr`
+	/// in `TALU_ADDR_REG`, the configuration `words` in `CONFIG_REG` and at
+	/// `CONFIG_ADDR`, and `MEM_ADDR_REG` pointing at the latter.
+	fn load(instruction: Instruction, talu_addr: Word, words: [Word; 2]) -> Cpu {
+		let mut main_memory = vec![0; CONFIG_ADDR];
+		main_memory.extend(words);
+		let input = FamInput {
+			program: vec![instruction, Instruction::NoOp, Instruction::NoOp, Instruction::NoOp],
+			main_memory,
+			registers: [
+				(TALU_ADDR_REG, talu_addr), (MEM_ADDR_REG, CONFIG_ADDR as Word),
+				(CONFIG_REG, words[0]), (CONFIG_REG + 1, words[1]),
+				(30, 1), (31, 5),
+			].into(),
+			..Default::default()
+		};
+		let mut cpu = input.build_cpu().unwrap();
+		for _ in 0..100 {
+			if !cpu.step() {
+				return cpu;
+			}
+		}
+		panic!("still running after 100 steps");
+	}
+
+	fn loads() -> [Instruction; 2] {
+		[
+			Instruction::LoadTaluConfigFromRegisters { talu_addr_reg: TALU_ADDR_REG, config_reg: CONFIG_REG },
+			Instruction::LoadTaluConfigFromMemory { talu_addr_reg: TALU_ADDR_REG, mem_addr_reg: MEM_ADDR_REG },
+		]
+	}
+
+	fn words(operation: &TaluOperation) -> [Word; 2] {
+		to_words(encode_operation(operation).unwrap())
+	}
+
+	#[test]
+	fn loaded_configurations_are_applied() {
+		for instruction in loads() {
+			let cpu = load(instruction, 3, words(&NOT));
+			assert_eq!(cpu.fault(), None, "{instruction:?}");
+			assert_eq!(cpu.talu_bank.components[3].operation, NOT, "{instruction:?}");
+			assert_eq!(cpu.register_bank.components[32].value, !5, "{instruction:?}");
+			assert_eq!(cpu.controller.instructions_executed, 4, "{instruction:?}");
+		}
+	}
+
+	#[test]
+	fn halts_on_a_talu_address_out_of_range() {
+		for instruction in loads() {
+			for talu_addr in [-1, TALU_COUNT as Word] {
+				let cpu = load(instruction, talu_addr, words(&NOT));
+				assert_eq!(cpu.fault(), Some(format!("TALU address {talu_addr} out of range").as_str()));
+				assert_eq!(cpu.controller.state, ControllerExecutionState::Halted);
+				assert_eq!(cpu.controller.instructions_executed, 1);
+			}
+		}
+	}
+
+	#[test]
+	fn halts_on_a_configuration_that_doesnt_decode() {
+		for instruction in loads() {
+			let cpu = load(instruction, 3, [-1, -1]);
+			let fault = cpu.fault().unwrap();
+			assert!(fault.starts_with("can't load TALU configuration 0xffffffffffffffff: "), "{fault}");
+			assert_eq!(cpu.talu_bank.components[3].operation, TaluOperation::NoOp);
+			assert_eq!(cpu.controller.instructions_executed, 1);
+		}
+	}
+
+	#[test]
+	fn halts_on_a_configuration_past_what_a_talu_holds() {
+		let lut = TaluOperation::Lut {
+			activation_input: 30, data_input: 31, table_addr: 0, size_log2: LUT_MAX_SIZE_LOG2 + 1,
+			data_output_0: 32, activation_output: None,
+		};
+		let reason = lut.parameter_error().unwrap();
+		for instruction in loads() {
+			let cpu = load(instruction, 3, words(&lut));
+			let fault = cpu.fault().unwrap();
+			assert!(fault.ends_with(&format!(": Lut: {reason}")), "{fault}");
+			assert_eq!(cpu.talu_bank.components[3].operation, TaluOperation::NoOp);
+		}
+	}
+}
@@ -116,6 +116,7 @@ impl CpuRegisterPortName{
 
 
 
+#[derive(Clone)]
 pub enum CpuRegisterDataReader {
     Deactivated,
     Active {
@@ -145,6 +146,13 @@ impl CpuRegisterDataReader {
         }
     }
 
+    /// Overrides the value last read, e.g. to replay latched inputs.
+    pub fn set_value(&mut self, new_value: Option<Word>) {
+        if let Active{ source:_, value} = self {
+            *value = new_value;
+        }
+    }
+
     pub fn read(&self) -> Option<Word> {
         if let Active{ source:_, value} = self
         && let Some(val) = value
@@ -166,6 +174,7 @@ impl CpuRegisterDataReader {
     }
 }
 
+#[derive(Clone)]
 pub enum CpuRegisterDataWriter{
     Deactivated,
     Connected{
@@ -240,6 +249,9 @@ impl<'a> CpuRegisterWriteRequest<'a> {
     pub fn addr(&self) -> &CpuRegisterAddress{
         self.register_addr
     }
+    pub fn value(&self) -> &Word{
+        self.value
+    }
 }
 pub struct CpuRegisterReadRequest<'a>{
     register_addr : &'a CpuRegisterAddress,
@@ -253,6 +265,11 @@ impl CpuRegisterReadRequest<'_>{
     pub fn satisfy(self, register_bank: &CpuRegisterBank)  {
         *self.value_cell = Some( register_bank.components[*self.register_addr].value)
     }
+    /// Satisfies the request with a value read from elsewhere than a
+    /// `CpuRegisterBank`, e.g. one lane of a batch.
+    pub fn satisfy_with(self, value: Word) {
+        *self.value_cell = Some(value)
+    }
 }
 
 pub struct CpuRegisterActReader{
@@ -280,6 +297,9 @@ impl CpuRegisterActReader{
     pub fn read(&self) -> Option<Activation>{
         self.inner.read().map(|val| val.to_activation())
     }
+    pub fn set_value(&mut self, value: Option<Word>) {
+        self.inner.set_value(value);
+    }
 }
 
 pub struct CpuRegisterActWriter {
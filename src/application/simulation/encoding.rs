@@ -0,0 +1,811 @@
+use std::fmt::{Display, Formatter};
+use crate::application::simulation::cpu_registers::{CpuRegisterAddress, REGISTER_COUNT};
+use crate::application::simulation::instruction::{Instruction, CONTROLLER_INSTRUCTION_SIZE};
+use crate::application::simulation::talu::{
+    CmpOp, Edge, FixedOp, FloatOp, LaneWidth, OverflowMode, QFormat, SimdOp, TaluOperation, TALU_COUNT,
+};
+use crate::word::Word;
+
+/// Encoding of one instruction, `CONTROLLER_INSTRUCTION_SIZE` bits wide.
+pub type EncodedInstruction = u64;
+
+const _: () = assert!(EncodedInstruction::BITS as usize == CONTROLLER_INSTRUCTION_SIZE);
+
+const OPCODE_BITS: u32 = 3;
+const TALU_ADDR_BITS: u32 = TALU_COUNT.ilog2();
+const REGISTER_BITS: u32 = REGISTER_COUNT.ilog2();
+const OPERATION_TAG_BITS: u32 = 5;
+const CMP_BITS: u32 = 3;
+const FRAC_BITS_BITS: u32 = 5;
+const TABLE_ADDR_BITS: u32 = 16;
+const SIZE_LOG2_BITS: u32 = 4;
+
+/// Bits left for a `TaluOperation` after the opcode and TALU address of a
+/// `SetTaluConfig`.
+pub const OPERATION_BITS: u32 = EncodedInstruction::BITS - OPCODE_BITS - TALU_ADDR_BITS;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EncodeError {
+    /// A field holds a value its bits can't represent.
+    OutOfRange { field: &'static str, value: i64 },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::OutOfRange { field, value } => write!(f, "{field} {value} doesn't fit its encoding"),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// A tag that no variant uses.
+    UnknownTag { field: &'static str, value: u64 },
+    /// Bits set past the end of the instruction's fields.
+    TrailingBits(EncodedInstruction),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnknownTag { field, value } => write!(f, "unknown {field} {value}"),
+            DecodeError::TrailingBits(bits) => write!(f, "unused bits set in {bits:#018x}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Packs fields from the least significant bit up.
+struct BitWriter {
+    bits    : u64,
+    pos     : u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter { bits: 0, pos: 0 }
+    }
+
+    fn put(&mut self, value: u64, width: u32) {
+        debug_assert!(self.pos + width <= u64::BITS, "encoding overflows its word");
+        debug_assert!(width == u64::BITS || value >> width == 0);
+        self.bits |= value << self.pos;
+        self.pos += width;
+    }
+
+    fn checked(&mut self, field: &'static str, value: i64, width: u32) -> Result<(), EncodeError> {
+        if !(0..1 << width).contains(&value) {
+            return Err(EncodeError::OutOfRange { field, value });
+        }
+        self.put(value as u64, width);
+        Ok(())
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.put(value as u64, 1);
+    }
+
+    fn reg(&mut self, reg: CpuRegisterAddress) -> Result<(), EncodeError> {
+        self.checked("register", reg as i64, REGISTER_BITS)
+    }
+
+    fn opt_reg(&mut self, reg: Option<CpuRegisterAddress>) -> Result<(), EncodeError> {
+        self.bool(reg.is_some());
+        self.reg(reg.unwrap_or(0))
+    }
+
+    fn word(&mut self, word: Word) {
+        self.put(word as u32 as u64, Word::BITS);
+    }
+
+    fn cmp(&mut self, op: CmpOp) {
+        let tag = match op {
+            CmpOp::LessThan => 0,
+            CmpOp::LessThanOrEq => 1,
+            CmpOp::GreaterThan => 2,
+            CmpOp::GreaterThanOrEq => 3,
+            CmpOp::Eq => 4,
+            CmpOp::NotEq => 5,
+        };
+        self.put(tag, CMP_BITS);
+    }
+
+    fn overflow(&mut self, overflow: OverflowMode) {
+        self.bool(overflow == OverflowMode::Saturate);
+    }
+}
+
+/// Unpacks fields in the order `BitWriter` packed them.
+struct BitReader {
+    bits    : u64,
+}
+
+impl BitReader {
+    fn take(&mut self, width: u32) -> u64 {
+        let value = self.bits & (u64::MAX >> (u64::BITS - width));
+        self.bits = self.bits.checked_shr(width).unwrap_or(0);
+        value
+    }
+
+    fn bool(&mut self) -> bool {
+        self.take(1) != 0
+    }
+
+    fn reg(&mut self) -> CpuRegisterAddress {
+        self.take(REGISTER_BITS) as CpuRegisterAddress
+    }
+
+    /// An absent register has to be encoded as zero, so every instruction
+    /// has exactly one encoding.
+    fn opt_reg(&mut self) -> Result<Option<CpuRegisterAddress>, DecodeError> {
+        let present = self.bool();
+        match (present, self.reg()) {
+            (true, reg) => Ok(Some(reg)),
+            (false, 0) => Ok(None),
+            (false, reg) => Err(DecodeError::UnknownTag { field: "absent register", value: reg as u64 }),
+        }
+    }
+
+    fn word(&mut self) -> Word {
+        self.take(Word::BITS) as u32 as Word
+    }
+
+    fn cmp(&mut self) -> Result<CmpOp, DecodeError> {
+        Ok(match self.take(CMP_BITS) {
+            0 => CmpOp::LessThan,
+            1 => CmpOp::LessThanOrEq,
+            2 => CmpOp::GreaterThan,
+            3 => CmpOp::GreaterThanOrEq,
+            4 => CmpOp::Eq,
+            5 => CmpOp::NotEq,
+            value => return Err(DecodeError::UnknownTag { field: "comparison", value }),
+        })
+    }
+
+    fn overflow(&mut self) -> OverflowMode {
+        if self.bool() { OverflowMode::Saturate } else { OverflowMode::Wrap }
+    }
+
+    /// Fails if any bit past the fields read is set.
+    fn finish(&self, encoded: EncodedInstruction) -> Result<(), DecodeError> {
+        if self.bits == 0 { Ok(()) } else { Err(DecodeError::TrailingBits(encoded)) }
+    }
+}
+
+/// Splits an encoding into the two words it takes in registers or main
+/// memory, low word first.
+pub fn to_words(encoded: u64) -> [Word; 2] {
+    [encoded as u32 as Word, (encoded >> 32) as u32 as Word]
+}
+
+/// Joins the words `to_words` gives back into an encoding.
+pub fn from_words([low, high]: [Word; 2]) -> u64 {
+    low as u32 as u64 | (high as u32 as u64) << 32
+}
+
+pub fn encode_instruction(instruction: &Instruction) -> Result<EncodedInstruction, EncodeError> {
+    let mut w = BitWriter::new();
+    match *instruction {
+        Instruction::NoOp => w.put(0, OPCODE_BITS),
+        Instruction::SetTaluConfig { talu_addr, talu_config } => {
+            w.put(1, OPCODE_BITS);
+            w.checked("TALU address", talu_addr as i64, TALU_ADDR_BITS)?;
+            w.put(encode_operation(&talu_config)?, OPERATION_BITS);
+        }
+        Instruction::ResetAllTalus => w.put(2, OPCODE_BITS),
+        Instruction::SetLiteral { literal, reg_addr } => {
+            w.put(3, OPCODE_BITS);
+            w.reg(reg_addr)?;
+            w.word(literal);
+        }
+        Instruction::WaitForActivationSignal { register_index } => {
+            w.put(4, OPCODE_BITS);
+            w.reg(register_index)?;
+        }
+        Instruction::Jump { addr } => {
+            w.put(5, OPCODE_BITS);
+            w.word(addr);
+        }
+        Instruction::LoadTaluConfigFromRegisters { talu_addr_reg, config_reg } => {
+            w.put(6, OPCODE_BITS);
+            w.reg(talu_addr_reg)?;
+            w.reg(config_reg)?;
+        }
+        Instruction::LoadTaluConfigFromMemory { talu_addr_reg, mem_addr_reg } => {
+            w.put(7, OPCODE_BITS);
+            w.reg(talu_addr_reg)?;
+            w.reg(mem_addr_reg)?;
+        }
+    }
+    Ok(w.bits)
+}
+
+pub fn decode_instruction(encoded: EncodedInstruction) -> Result<Instruction, DecodeError> {
+    let mut r = BitReader { bits: encoded };
+    let instruction = match r.take(OPCODE_BITS) {
+        0 => Instruction::NoOp,
+        1 => {
+            let talu_addr = r.take(TALU_ADDR_BITS) as usize;
+            let talu_config = decode_operation(r.take(OPERATION_BITS))?;
+            Instruction::SetTaluConfig { talu_addr, talu_config }
+        }
+        2 => Instruction::ResetAllTalus,
+        3 => {
+            let reg_addr = r.reg();
+            Instruction::SetLiteral { reg_addr, literal: r.word() }
+        }
+        4 => Instruction::WaitForActivationSignal { register_index: r.reg() },
+        5 => Instruction::Jump { addr: r.word() },
+        6 => {
+            let talu_addr_reg = r.reg();
+            Instruction::LoadTaluConfigFromRegisters { talu_addr_reg, config_reg: r.reg() }
+        }
+        7 => {
+            let talu_addr_reg = r.reg();
+            Instruction::LoadTaluConfigFromMemory { talu_addr_reg, mem_addr_reg: r.reg() }
+        }
+        value => return Err(DecodeError::UnknownTag { field: "opcode", value }),
+    };
+    r.finish(encoded)?;
+    Ok(instruction)
+}
+
+/// Encodes a TALU configuration in the low `OPERATION_BITS` bits, tagged
+/// by its position in `TaluOperation`.
+pub fn encode_operation(operation: &TaluOperation) -> Result<u64, EncodeError> {
+    let mut w = BitWriter::new();
+    match *operation {
+        TaluOperation::NoOp => w.put(0, OPERATION_TAG_BITS),
+        TaluOperation::Cmp { op, activation_input, activation_output, data_input_0, data_input_1, data_output } => {
+            w.put(1, OPERATION_TAG_BITS);
+            w.cmp(op);
+            w.reg(activation_input)?;
+            w.opt_reg(activation_output)?;
+            w.reg(data_input_0)?;
+            w.reg(data_input_1)?;
+            w.reg(data_output)?;
+        }
+        TaluOperation::Mov { activation_input, value_input, data_output, activation_output } => {
+            w.put(2, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(value_input)?;
+            w.reg(data_output)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Latch { activation_input, data_input, hold_input, data_output, activation_output } => {
+            w.put(3, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(data_input)?;
+            w.reg(hold_input)?;
+            w.reg(data_output)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Not { activation_input, data_input, data_output, activation_output } => {
+            w.put(4, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(data_input)?;
+            w.reg(data_output)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::And { activation_input, data_input_0, data_input_1, data_output_0, activation_output }
+        | TaluOperation::Or { activation_input, data_input_0, data_input_1, data_output_0, activation_output }
+        | TaluOperation::Xor { activation_input, data_input_0, data_input_1, data_output_0, activation_output }
+        | TaluOperation::ShiftLeft { activation_input, value: data_input_0, shift_count: data_input_1, data_output_0, activation_output }
+        | TaluOperation::ShiftRight { activation_input, value: data_input_0, shift_count: data_input_1, data_output_0, activation_output }
+        | TaluOperation::SelectPart { activation_input, data_input: data_input_0, selection_input: data_input_1, data_output_0, activation_output }
+        | TaluOperation::Counter { activation_input, down_input: data_input_0, reset_input: data_input_1, data_output_0, activation_output } => {
+            let tag = match operation {
+                TaluOperation::And { .. } => 5,
+                TaluOperation::Or { .. } => 6,
+                TaluOperation::Xor { .. } => 7,
+                TaluOperation::ShiftLeft { .. } => 8,
+                TaluOperation::ShiftRight { .. } => 9,
+                TaluOperation::SelectPart { .. } => 10,
+                _ => 21,
+            };
+            w.put(tag, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(data_input_0)?;
+            w.reg(data_input_1)?;
+            w.reg(data_output_0)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Add { activation_input, data_input_0, data_input_1, result_output: data_output_0, flags_output: extra_output, activation_output }
+        | TaluOperation::Sub { activation_input, data_input_0, data_input_1, data_output_0, flags_output: extra_output, activation_output }
+        | TaluOperation::Mul { activation_input, data_input_0, data_input_1, first_word_output: data_output_0, second_word_output: extra_output, activation_output }
+        | TaluOperation::Div { activation_input, dividend: data_input_0, divisor: data_input_1, data_output_0, div_by_zero_flag_output: extra_output, activation_output }
+        | TaluOperation::Rem { activation_input, dividend: data_input_0, divisor: data_input_1, data_output_0, div_by_zero_flag_output: extra_output, activation_output }
+        | TaluOperation::Accumulator { activation_input, data_input: data_input_0, reset_input: data_input_1, data_output_0, flags_output: extra_output, activation_output } => {
+            let tag = match operation {
+                TaluOperation::Add { .. } => 11,
+                TaluOperation::Sub { .. } => 12,
+                TaluOperation::Mul { .. } => 13,
+                TaluOperation::Div { .. } => 14,
+                TaluOperation::Rem { .. } => 15,
+                _ => 22,
+            };
+            w.put(tag, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(data_input_0)?;
+            w.reg(data_input_1)?;
+            w.reg(data_output_0)?;
+            w.opt_reg(extra_output)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Neg { activation_input, input, data_output_0, activation_output } => {
+            w.put(16, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(input)?;
+            w.reg(data_output_0)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::ReadFromMem { activation_input, address_input, data_output, activation_output } => {
+            w.put(17, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(address_input)?;
+            w.reg(data_output)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::WriteToMem { data_input, address_input, activation_input, activation_output } => {
+            w.put(18, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(data_input)?;
+            w.reg(address_input)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Fixed { op, format, overflow, activation_input, data_input_0, data_input_1, data_output_0, flags_output, activation_output } => {
+            w.put(19, OPERATION_TAG_BITS);
+            let op = match op {
+                FixedOp::Mul => 0,
+                FixedOp::Div => 1,
+                FixedOp::RoundingShift => 2,
+            };
+            w.put(op, 2);
+            w.checked("fractional bit count", format.frac_bits as i64, FRAC_BITS_BITS)?;
+            w.overflow(overflow);
+            w.reg(activation_input)?;
+            w.reg(data_input_0)?;
+            w.reg(data_input_1)?;
+            w.reg(data_output_0)?;
+            w.opt_reg(flags_output)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Mux { activation_input, condition, if_true, if_false, data_output_0, activation_output } => {
+            w.put(20, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(condition)?;
+            w.reg(if_true)?;
+            w.reg(if_false)?;
+            w.reg(data_output_0)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Timer { activation_input, period_input, reset_input, count_output, activation_output } => {
+            w.put(23, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(period_input)?;
+            w.reg(reset_input)?;
+            w.opt_reg(count_output)?;
+            w.reg(activation_output)?;
+        }
+        TaluOperation::Lut { activation_input, data_input, table_addr, size_log2, data_output_0, activation_output } => {
+            w.put(24, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(data_input)?;
+            w.checked("table address", table_addr as i64, TABLE_ADDR_BITS)?;
+            w.checked("table size", size_log2 as i64, SIZE_LOG2_BITS)?;
+            w.reg(data_output_0)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Delay { activation_input, data_input, steps, data_output_0, activation_output } => {
+            w.put(25, OPERATION_TAG_BITS);
+            w.reg(activation_input)?;
+            w.reg(data_input)?;
+            w.put(steps as u64, u8::BITS);
+            w.reg(data_output_0)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::EdgeDetect { edge, activation_input, activation_output } => {
+            w.put(26, OPERATION_TAG_BITS);
+            let edge = match edge {
+                Edge::Rising => 0,
+                Edge::Falling => 1,
+                Edge::Both => 2,
+            };
+            w.put(edge, 2);
+            w.reg(activation_input)?;
+            w.reg(activation_output)?;
+        }
+        TaluOperation::Simd { op, lanes, signed, overflow, activation_input, data_input_0, data_input_1, data_output_0, activation_output } => {
+            w.put(27, OPERATION_TAG_BITS);
+            match op {
+                SimdOp::Add => w.put(0, 2),
+                SimdOp::Sub => w.put(1, 2),
+                SimdOp::Cmp(cmp) => {
+                    w.put(2, 2);
+                    w.cmp(cmp);
+                }
+                SimdOp::HorizontalSum => w.put(3, 2),
+            }
+            w.bool(lanes == LaneWidth::Bits16);
+            w.bool(signed);
+            w.overflow(overflow);
+            w.reg(activation_input)?;
+            w.reg(data_input_0)?;
+            w.reg(data_input_1)?;
+            w.reg(data_output_0)?;
+            w.opt_reg(activation_output)?;
+        }
+        TaluOperation::Float { op, activation_input, data_input_0, data_input_1, data_output_0, activation_output } => {
+            w.put(28, OPERATION_TAG_BITS);
+            match op {
+                FloatOp::Add => w.put(0, 3),
+                FloatOp::Sub => w.put(1, 3),
+                FloatOp::Mul => w.put(2, 3),
+                FloatOp::Div => w.put(3, 3),
+                FloatOp::Cmp(cmp) => {
+                    w.put(4, 3);
+                    w.cmp(cmp);
+                }
+                FloatOp::FromInt => w.put(5, 3),
+                FloatOp::ToInt => w.put(6, 3),
+            }
+            w.reg(activation_input)?;
+            w.reg(data_input_0)?;
+            w.reg(data_input_1)?;
+            w.reg(data_output_0)?;
+            w.opt_reg(activation_output)?;
+        }
+    }
+    Ok(w.bits)
+}
+
+pub fn decode_operation(encoded: u64) -> Result<TaluOperation, DecodeError> {
+    let mut r = BitReader { bits: encoded };
+    let operation = match r.take(OPERATION_TAG_BITS) {
+        0 => TaluOperation::NoOp,
+        1 => TaluOperation::Cmp {
+            op: r.cmp()?,
+            activation_input: r.reg(),
+            activation_output: r.opt_reg()?,
+            data_input_0: r.reg(),
+            data_input_1: r.reg(),
+            data_output: r.reg(),
+        },
+        2 => TaluOperation::Mov {
+            activation_input: r.reg(),
+            value_input: r.reg(),
+            data_output: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        3 => TaluOperation::Latch {
+            activation_input: r.reg(),
+            data_input: r.reg(),
+            hold_input: r.reg(),
+            data_output: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        4 => TaluOperation::Not {
+            activation_input: r.reg(),
+            data_input: r.reg(),
+            data_output: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        tag @ (5..=10 | 21) => {
+            let (activation_input, in_0, in_1, data_output_0, activation_output) =
+                (r.reg(), r.reg(), r.reg(), r.reg(), r.opt_reg()?);
+            match tag {
+                5 => TaluOperation::And { activation_input, data_input_0: in_0, data_input_1: in_1, data_output_0, activation_output },
+                6 => TaluOperation::Or { activation_input, data_input_0: in_0, data_input_1: in_1, data_output_0, activation_output },
+                7 => TaluOperation::Xor { activation_input, data_input_0: in_0, data_input_1: in_1, data_output_0, activation_output },
+                8 => TaluOperation::ShiftLeft { activation_input, value: in_0, shift_count: in_1, data_output_0, activation_output },
+                9 => TaluOperation::ShiftRight { activation_input, value: in_0, shift_count: in_1, data_output_0, activation_output },
+                10 => TaluOperation::SelectPart { activation_input, data_input: in_0, selection_input: in_1, data_output_0, activation_output },
+                _ => TaluOperation::Counter { activation_input, down_input: in_0, reset_input: in_1, data_output_0, activation_output },
+            }
+        }
+        tag @ (11..=15 | 22) => {
+            let (activation_input, in_0, in_1, data_output_0, extra, activation_output) =
+                (r.reg(), r.reg(), r.reg(), r.reg(), r.opt_reg()?, r.opt_reg()?);
+            match tag {
+                11 => TaluOperation::Add { activation_input, data_input_0: in_0, data_input_1: in_1, result_output: data_output_0, flags_output: extra, activation_output },
+                12 => TaluOperation::Sub { activation_input, data_input_0: in_0, data_input_1: in_1, data_output_0, flags_output: extra, activation_output },
+                13 => TaluOperation::Mul { activation_input, data_input_0: in_0, data_input_1: in_1, first_word_output: data_output_0, second_word_output: extra, activation_output },
+                14 => TaluOperation::Div { activation_input, dividend: in_0, divisor: in_1, data_output_0, div_by_zero_flag_output: extra, activation_output },
+                15 => TaluOperation::Rem { activation_input, dividend: in_0, divisor: in_1, data_output_0, div_by_zero_flag_output: extra, activation_output },
+                _ => TaluOperation::Accumulator { activation_input, data_input: in_0, reset_input: in_1, data_output_0, flags_output: extra, activation_output },
+            }
+        }
+        16 => TaluOperation::Neg {
+            activation_input: r.reg(),
+            input: r.reg(),
+            data_output_0: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        17 => TaluOperation::ReadFromMem {
+            activation_input: r.reg(),
+            address_input: r.reg(),
+            data_output: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        18 => TaluOperation::WriteToMem {
+            activation_input: r.reg(),
+            data_input: r.reg(),
+            address_input: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        19 => TaluOperation::Fixed {
+            op: match r.take(2) {
+                0 => FixedOp::Mul,
+                1 => FixedOp::Div,
+                2 => FixedOp::RoundingShift,
+                value => return Err(DecodeError::UnknownTag { field: "fixed-point operation", value }),
+            },
+            format: QFormat { frac_bits: r.take(FRAC_BITS_BITS) as u8 },
+            overflow: r.overflow(),
+            activation_input: r.reg(),
+            data_input_0: r.reg(),
+            data_input_1: r.reg(),
+            data_output_0: r.reg(),
+            flags_output: r.opt_reg()?,
+            activation_output: r.opt_reg()?,
+        },
+        20 => TaluOperation::Mux {
+            activation_input: r.reg(),
+            condition: r.reg(),
+            if_true: r.reg(),
+            if_false: r.reg(),
+            data_output_0: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        23 => TaluOperation::Timer {
+            activation_input: r.reg(),
+            period_input: r.reg(),
+            reset_input: r.reg(),
+            count_output: r.opt_reg()?,
+            activation_output: r.reg(),
+        },
+        24 => TaluOperation::Lut {
+            activation_input: r.reg(),
+            data_input: r.reg(),
+            table_addr: r.take(TABLE_ADDR_BITS) as usize,
+            size_log2: r.take(SIZE_LOG2_BITS) as u8,
+            data_output_0: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        25 => TaluOperation::Delay {
+            activation_input: r.reg(),
+            data_input: r.reg(),
+            steps: r.take(u8::BITS) as u8,
+            data_output_0: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        26 => TaluOperation::EdgeDetect {
+            edge: match r.take(2) {
+                0 => Edge::Rising,
+                1 => Edge::Falling,
+                2 => Edge::Both,
+                value => return Err(DecodeError::UnknownTag { field: "edge", value }),
+            },
+            activation_input: r.reg(),
+            activation_output: r.reg(),
+        },
+        27 => TaluOperation::Simd {
+            op: match r.take(2) {
+                0 => SimdOp::Add,
+                1 => SimdOp::Sub,
+                2 => SimdOp::Cmp(r.cmp()?),
+                _ => SimdOp::HorizontalSum,
+            },
+            lanes: if r.bool() { LaneWidth::Bits16 } else { LaneWidth::Bits8 },
+            signed: r.bool(),
+            overflow: r.overflow(),
+            activation_input: r.reg(),
+            data_input_0: r.reg(),
+            data_input_1: r.reg(),
+            data_output_0: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        28 => TaluOperation::Float {
+            op: match r.take(3) {
+                0 => FloatOp::Add,
+                1 => FloatOp::Sub,
+                2 => FloatOp::Mul,
+                3 => FloatOp::Div,
+                4 => FloatOp::Cmp(r.cmp()?),
+                5 => FloatOp::FromInt,
+                6 => FloatOp::ToInt,
+                value => return Err(DecodeError::UnknownTag { field: "float operation", value }),
+            },
+            activation_input: r.reg(),
+            data_input_0: r.reg(),
+            data_input_1: r.reg(),
+            data_output_0: r.reg(),
+            activation_output: r.opt_reg()?,
+        },
+        value => return Err(DecodeError::UnknownTag { field: "TALU operation", value }),
+    };
+    r.finish(encoded)?;
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use crate::application::simulation::talu::DELAY_MAX_STEPS;
+
+    const CMPS: [CmpOp; 6] = [
+        CmpOp::LessThan, CmpOp::LessThanOrEq, CmpOp::GreaterThan, CmpOp::GreaterThanOrEq, CmpOp::Eq, CmpOp::NotEq,
+    ];
+
+    /// Every operation, with registers that differ from each other so
+    /// fields read back in the wrong order show up, and the highest
+    /// register somewhere in each.
+    fn operations() -> Vec<TaluOperation> {
+        let top = REGISTER_COUNT - 1;
+        let mut operations = vec![
+            TaluOperation::NoOp,
+            TaluOperation::Mov { activation_input: top, value_input: 1, data_output: 2, activation_output: Some(3) },
+            TaluOperation::Latch { activation_input: 1, data_input: 2, hold_input: 3, data_output: top, activation_output: None },
+            TaluOperation::Not { activation_input: 1, data_input: top, data_output: 2, activation_output: Some(0) },
+            TaluOperation::And { activation_input: 1, data_input_0: 2, data_input_1: 3, data_output_0: 4, activation_output: Some(top) },
+            TaluOperation::Or { activation_input: top, data_input_0: 2, data_input_1: 3, data_output_0: 4, activation_output: None },
+            TaluOperation::Xor { activation_input: 1, data_input_0: top, data_input_1: 3, data_output_0: 4, activation_output: Some(5) },
+            TaluOperation::ShiftLeft { activation_input: 1, value: 2, shift_count: top, data_output_0: 4, activation_output: None },
+            TaluOperation::ShiftRight { activation_input: 1, value: 2, shift_count: 3, data_output_0: top, activation_output: Some(5) },
+            TaluOperation::SelectPart { activation_input: 1, data_input: 2, selection_input: 3, data_output_0: 4, activation_output: Some(top) },
+            TaluOperation::Add { activation_input: top, data_input_0: 2, data_input_1: 3, result_output: 4, flags_output: Some(5), activation_output: None },
+            TaluOperation::Sub { activation_input: 1, data_input_0: top, data_input_1: 3, data_output_0: 4, flags_output: None, activation_output: Some(6) },
+            TaluOperation::Mul { activation_input: 1, data_input_0: 2, data_input_1: top, first_word_output: 4, second_word_output: Some(5), activation_output: Some(6) },
+            TaluOperation::Div { activation_input: 1, dividend: 2, divisor: 3, data_output_0: top, div_by_zero_flag_output: None, activation_output: None },
+            TaluOperation::Rem { activation_input: 1, dividend: 2, divisor: 3, data_output_0: 4, div_by_zero_flag_output: Some(top), activation_output: Some(6) },
+            TaluOperation::Neg { activation_input: 1, input: 2, data_output_0: 3, activation_output: Some(top) },
+            TaluOperation::ReadFromMem { activation_input: top, address_input: 2, data_output: 3, activation_output: None },
+            TaluOperation::WriteToMem { data_input: 1, address_input: top, activation_input: 3, activation_output: Some(4) },
+            TaluOperation::Mux { activation_input: 1, condition: 2, if_true: 3, if_false: top, data_output_0: 5, activation_output: Some(6) },
+            TaluOperation::Counter { activation_input: 1, down_input: 2, reset_input: 3, data_output_0: 4, activation_output: Some(top) },
+            TaluOperation::Accumulator { activation_input: 1, data_input: top, reset_input: 3, data_output_0: 4, flags_output: Some(5), activation_output: None },
+            TaluOperation::Timer { activation_input: 1, period_input: 2, reset_input: 3, count_output: Some(4), activation_output: top },
+            TaluOperation::Timer { activation_input: 1, period_input: 2, reset_input: 3, count_output: None, activation_output: 5 },
+            TaluOperation::Lut { activation_input: 1, data_input: 2, table_addr: 1234, size_log2: 8, data_output_0: top, activation_output: Some(4) },
+            TaluOperation::Lut { activation_input: 1, data_input: 2, table_addr: (1 << TABLE_ADDR_BITS) - 1, size_log2: 15, data_output_0: 3, activation_output: None },
+            TaluOperation::Delay { activation_input: 1, data_input: 2, steps: DELAY_MAX_STEPS, data_output_0: 3, activation_output: Some(top) },
+            TaluOperation::Delay { activation_input: 1, data_input: 2, steps: u8::MAX, data_output_0: 3, activation_output: None },
+        ];
+        for op in CMPS {
+            operations.push(TaluOperation::Cmp { op, activation_input: 1, activation_output: Some(2), data_input_0: 3, data_input_1: top, data_output: 5 });
+        }
+        for op in [FixedOp::Mul, FixedOp::Div, FixedOp::RoundingShift] {
+            for frac_bits in [0, 16, QFormat::MAX_FRAC_BITS] {
+                for overflow in [OverflowMode::Wrap, OverflowMode::Saturate] {
+                    operations.push(TaluOperation::Fixed {
+                        op, format: QFormat { frac_bits }, overflow,
+                        activation_input: 1, data_input_0: 2, data_input_1: 3, data_output_0: 4, flags_output: Some(top), activation_output: None,
+                    });
+                }
+            }
+        }
+        for edge in [Edge::Rising, Edge::Falling, Edge::Both] {
+            operations.push(TaluOperation::EdgeDetect { edge, activation_input: top, activation_output: 2 });
+        }
+        let simd_ops = [SimdOp::Add, SimdOp::Sub, SimdOp::HorizontalSum].into_iter().chain(CMPS.map(SimdOp::Cmp));
+        for op in simd_ops {
+            for (lanes, signed, overflow) in [
+                (LaneWidth::Bits8, false, OverflowMode::Wrap),
+                (LaneWidth::Bits16, true, OverflowMode::Saturate),
+                (LaneWidth::Bits8, true, OverflowMode::Saturate),
+            ] {
+                operations.push(TaluOperation::Simd {
+                    op, lanes, signed, overflow,
+                    activation_input: 1, data_input_0: 2, data_input_1: 3, data_output_0: top, activation_output: Some(5),
+                });
+            }
+        }
+        let float_ops = [FloatOp::Add, FloatOp::Sub, FloatOp::Mul, FloatOp::Div, FloatOp::FromInt, FloatOp::ToInt]
+            .into_iter()
+            .chain(CMPS.map(FloatOp::Cmp));
+        for op in float_ops {
+            operations.push(TaluOperation::Float { op, activation_input: 1, data_input_0: top, data_input_1: 3, data_output_0: 4, activation_output: None });
+        }
+        operations
+    }
+
+    fn instructions() -> Vec<Instruction> {
+        let top = REGISTER_COUNT - 1;
+        let mut instructions = vec![
+            Instruction::NoOp,
+            Instruction::ResetAllTalus,
+            Instruction::SetLiteral { literal: Word::MIN, reg_addr: top },
+            Instruction::SetLiteral { literal: -1, reg_addr: 0 },
+            Instruction::SetLiteral { literal: Word::MAX, reg_addr: 7 },
+            Instruction::WaitForActivationSignal { register_index: top },
+            Instruction::Jump { addr: 0 },
+            Instruction::Jump { addr: -3 },
+            Instruction::LoadTaluConfigFromRegisters { talu_addr_reg: 1, config_reg: top },
+            Instruction::LoadTaluConfigFromMemory { talu_addr_reg: top, mem_addr_reg: 2 },
+        ];
+        for (ix, talu_config) in operations().into_iter().enumerate() {
+            instructions.push(Instruction::SetTaluConfig { talu_addr: ix % TALU_COUNT, talu_config });
+        }
+        instructions.push(Instruction::SetTaluConfig { talu_addr: TALU_COUNT - 1, talu_config: TaluOperation::NoOp });
+        instructions
+    }
+
+    #[test]
+    fn operations_round_trip() {
+        let mut kinds = BTreeSet::new();
+        for operation in operations() {
+            let encoded = encode_operation(&operation).unwrap();
+            assert!(encoded >> OPERATION_BITS == 0, "{operation:?} takes more than {OPERATION_BITS} bits");
+            assert_eq!(decode_operation(encoded), Ok(operation));
+            kinds.insert(operation.kind_name());
+        }
+        assert_eq!(kinds, BTreeSet::from(TaluOperation::KIND_NAMES), "every kind of operation is covered");
+    }
+
+    #[test]
+    fn instructions_round_trip() {
+        for instruction in instructions() {
+            let encoded = encode_instruction(&instruction).unwrap();
+            assert_eq!(decode_instruction(encoded), Ok(instruction));
+            assert_eq!(from_words(to_words(encoded)), encoded);
+        }
+    }
+
+    #[test]
+    fn values_past_their_bits_fail_to_encode() {
+        let out_of_range = |field, value| Err(EncodeError::OutOfRange { field, value });
+        let mov = |reg| TaluOperation::Mov { activation_input: 1, value_input: reg, data_output: 2, activation_output: None };
+        let fixed = |frac_bits| TaluOperation::Fixed {
+            op: FixedOp::Mul, format: QFormat { frac_bits }, overflow: OverflowMode::Wrap,
+            activation_input: 1, data_input_0: 2, data_input_1: 3, data_output_0: 4, flags_output: None, activation_output: None,
+        };
+        let lut = |table_addr, size_log2| TaluOperation::Lut {
+            activation_input: 1, data_input: 2, table_addr, size_log2, data_output_0: 3, activation_output: None,
+        };
+
+        assert_eq!(encode_operation(&mov(REGISTER_COUNT)), out_of_range("register", REGISTER_COUNT as i64));
+        assert_eq!(encode_operation(&fixed(QFormat::MAX_FRAC_BITS + 1)), out_of_range("fractional bit count", 32));
+        assert_eq!(encode_operation(&lut(1 << TABLE_ADDR_BITS, 0)), out_of_range("table address", 1 << 16));
+        assert_eq!(encode_operation(&lut(0, 16)), out_of_range("table size", 16));
+        assert_eq!(
+            encode_instruction(&Instruction::SetTaluConfig { talu_addr: TALU_COUNT, talu_config: TaluOperation::NoOp }),
+            out_of_range("TALU address", TALU_COUNT as i64),
+        );
+        assert_eq!(
+            encode_instruction(&Instruction::SetLiteral { literal: 0, reg_addr: REGISTER_COUNT }),
+            out_of_range("register", REGISTER_COUNT as i64),
+        );
+        assert_eq!(
+            encode_instruction(&Instruction::WaitForActivationSignal { register_index: usize::MAX }),
+            out_of_range("register", -1),
+        );
+    }
+
+    #[test]
+    fn unused_tags_fail_to_decode() {
+        let unknown = |field, value| DecodeError::UnknownTag { field, value };
+        let tagged = |tag: u64, sub_tag: u64| tag | sub_tag << OPERATION_TAG_BITS;
+
+        for tag in TaluOperation::KIND_NAMES.len() as u64..1 << OPERATION_TAG_BITS {
+            assert_eq!(decode_operation(tag), Err(unknown("TALU operation", tag)));
+        }
+        assert_eq!(decode_operation(tagged(1, 6)), Err(unknown("comparison", 6)));
+        assert_eq!(decode_operation(tagged(1, 7)), Err(unknown("comparison", 7)));
+        assert_eq!(decode_operation(tagged(19, 3)), Err(unknown("fixed-point operation", 3)));
+        assert_eq!(decode_operation(tagged(26, 3)), Err(unknown("edge", 3)));
+        assert_eq!(decode_operation(tagged(27, 2 | 6 << 2)), Err(unknown("comparison", 6)));
+        assert_eq!(decode_operation(tagged(28, 7)), Err(unknown("float operation", 7)));
+        assert_eq!(decode_operation(tagged(28, 4 | 7 << 3)), Err(unknown("comparison", 7)));
+        // A `Mov` whose absent activation output isn't zero.
+        assert_eq!(decode_operation(tagged(2, 5 << (3 * REGISTER_BITS + 1))), Err(unknown("absent register", 5)));
+
+        let set_talu_config = |operation: u64| 1 | operation << (OPCODE_BITS + TALU_ADDR_BITS);
+        assert_eq!(decode_instruction(set_talu_config(31)), Err(unknown("TALU operation", 31)));
+        assert_eq!(decode_instruction(1 << 40), Err(DecodeError::TrailingBits(1 << 40)));
+    }
+}
@@ -5,8 +5,11 @@ use crate::word::Word;
 pub const CONTROLLER_INSTRUCTION_SIZE   		: usize = 64;
 
 
-#[derive( PartialEq, Copy, Clone, Debug,Eq, Default, serde::Serialize, serde::Deserialize)]
-pub enum Instruction {
+/// A controller instruction. `L` is what `SetLiteral` sets, always a word
+/// once a program is loaded; inputs may name data sections instead, see
+/// `Literal`.
+#[derive( PartialEq, Copy, Clone, Debug,Eq, Default, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum Instruction<L = Word> {
     
     SetTaluConfig{
         talu_addr	: TaluAddress,
@@ -15,7 +18,7 @@ pub enum Instruction {
     ResetAllTalus,
     
     SetLiteral{
-        literal	: Word,
+        literal	: L,
         reg_addr: CpuRegisterAddress,
     },
 
@@ -35,10 +38,44 @@ pub enum Instruction {
         // relative        : bool,
         addr            : Word
     },
+
+    /// Configures the TALU whose address is in `talu_addr_reg` with an
+    /// operation encoded by `encode_operation`, its low word in
+    /// `config_reg` and its high word in `config_reg + 1`.
+    LoadTaluConfigFromRegisters{
+        talu_addr_reg   : CpuRegisterAddress,
+        config_reg      : CpuRegisterAddress,
+    },
+
+    /// Like `LoadTaluConfigFromRegisters`, with the encoded operation in
+    /// main memory at the address in `mem_addr_reg`, low word first.
+    LoadTaluConfigFromMemory{
+        talu_addr_reg   : CpuRegisterAddress,
+        mem_addr_reg    : CpuRegisterAddress,
+    },
     #[default]
     NoOp,
 }
 
+impl<L> Instruction<L> {
+    /// The same instruction, with `SetLiteral`'s literal replaced by what
+    /// `f` makes of it.
+    pub fn try_map_literal<M, E>(self, f: impl FnOnce(L) -> Result<M, E>) -> Result<Instruction<M>, E> {
+        Ok(match self {
+            Instruction::SetTaluConfig { talu_addr, talu_config } => Instruction::SetTaluConfig { talu_addr, talu_config },
+            Instruction::ResetAllTalus => Instruction::ResetAllTalus,
+            Instruction::SetLiteral { literal, reg_addr } => Instruction::SetLiteral { literal: f(literal)?, reg_addr },
+            Instruction::WaitForActivationSignal { register_index } => Instruction::WaitForActivationSignal { register_index },
+            Instruction::Jump { addr } => Instruction::Jump { addr },
+            Instruction::LoadTaluConfigFromRegisters { talu_addr_reg, config_reg } =>
+                Instruction::LoadTaluConfigFromRegisters { talu_addr_reg, config_reg },
+            Instruction::LoadTaluConfigFromMemory { talu_addr_reg, mem_addr_reg } =>
+                Instruction::LoadTaluConfigFromMemory { talu_addr_reg, mem_addr_reg },
+            Instruction::NoOp => Instruction::NoOp,
+        })
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum HorizontalDir{
     Left,
@@ -1,12 +1,13 @@
-use std::ops::Deref;
 use std::sync::Arc;
 use crate::application::draw::instruction_memory::InstructionMemoryCurrentPosition;
 use crate::{Step,  PROGRAM_COUNTER_REGISTER_ADDR};
 use crate::application::simulation::cpu_registers::{CpuRegisterDataReader, CpuRegisterDataWriter, };
+use crate::application::simulation::encoding::{decode_instruction, from_words};
 use crate::application::simulation::instruction::Instruction;
 use crate::application::simulation::main_memory::MainMemory;
 use crate::application::simulation::cpu_registers::CpuRegisterBank;
 use crate::application::simulation::main_memory::MainMemoryIo;
+use crate::word::Word;
 
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Copy)]
 pub enum IncrementCmd{
@@ -15,6 +16,7 @@ pub enum IncrementCmd{
 	GoTo(i32),
 }
 
+#[derive(Clone)]
 pub struct InstructionMemory(
 	pub Arc<Vec<Instruction>>,
 );
@@ -25,11 +27,46 @@ impl InstructionMemory{
 	}
 }
 
+/// Words each instruction takes in main memory, see `to_words`.
+pub const WORDS_PER_INSTRUCTION: usize = 2;
+
+/// The part of main memory a program runs from in von Neumann mode.
+/// Instruction `n` is the two words at `base + 2 * n`, encoded by
+/// `encode_instruction` and split by `to_words`. Instructions are fetched
+/// when the controller decodes them, so a word written to the region in an
+/// earlier step is what runs, including words the program writes itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct CodeRegion {
+	/// Main memory address of instruction 0.
+	pub base	: usize,
+	/// Number of instructions the region holds.
+	pub len		: usize,
+}
+
+impl CodeRegion {
+	/// The main memory address of the instruction at `pc`, if it's inside
+	/// the region.
+	pub fn address_of(&self, pc: Word) -> Option<usize> {
+		let pc = usize::try_from(pc).ok().filter(|pc| *pc < self.len)?;
+		Some(self.base + pc * WORDS_PER_INSTRUCTION)
+	}
+
+	/// One past the last main memory address of the region.
+	pub fn end(&self) -> usize {
+		self.base.saturating_add(self.len.saturating_mul(WORDS_PER_INSTRUCTION))
+	}
+}
+
+#[derive(Clone)]
 pub struct InstructionReader{
 	pub program_counter_reader	: CpuRegisterDataReader,
 	pub program_counter_writer  : CpuRegisterDataWriter,
 	increment_cmd				: IncrementCmd,
 	instruction_memory			: Arc<Vec<Instruction>>,
+	/// When set, instructions come from this region of main memory
+	/// instead of `instruction_memory`.
+	pub code_region				: Option<CodeRegion>,
+	fetched						: Option<[Word; WORDS_PER_INSTRUCTION]>,
 }
 
 impl InstructionReader{
@@ -42,6 +79,8 @@ impl InstructionReader{
 			PROGRAM_COUNTER_REGISTER_ADDR, value: None},
 			program_counter_writer	: CpuRegisterDataWriter::Deactivated,
 			increment_cmd			: IncrementCmd::Increment,
+			code_region				: None,
+			fetched					: None,
 		}
 	}
 }
@@ -51,15 +90,36 @@ impl InstructionReader{
 		self.increment_cmd = cmd;
 	}
 
-	pub fn read<'a>(&'a self) -> Option<impl Deref<Target=Instruction> + 'a>{
-		let addr = self.program_counter_reader.read().unwrap() as usize;
-		self.instruction_memory.get(addr)
+	/// The instruction at the program counter, or `None` past the end of
+	/// the program. In von Neumann mode, fails on words that aren't an
+	/// instruction or that couldn't be fetched.
+	pub fn read(&self) -> Result<Option<Instruction>, String> {
+		let pc = self.program_counter_reader.read().unwrap();
+		let Some(region) = self.code_region else {
+			return Ok(self.instruction_memory.get(pc as usize).copied());
+		};
+		let Some(addr) = region.address_of(pc) else {
+			return Ok(None);
+		};
+		let words = self.fetched
+			.ok_or_else(|| format!("instruction {pc} at main memory address {addr} is past the end of memory"))?;
+		let instruction = decode_instruction(from_words(words))
+			.map_err(|err| format!("can't decode instruction {pc} at main memory address {addr}: {err}"))?;
+		Ok(Some(instruction))
+	}
+
+	/// In von Neumann mode, a request for the words of the instruction at
+	/// the program counter, once the program counter has been read.
+	pub fn get_fetch_request(&mut self) -> Option<InstructionFetchRequest<'_>> {
+		let addr = self.code_region?.address_of(self.program_counter_reader.read()?)?;
+		Some(InstructionFetchRequest { addr, words_cell: &mut self.fetched })
 	}
 
 	pub fn get_instruction_pos(&self) -> Option<InstructionMemoryCurrentPosition>{
 		Some(self.program_counter_reader.read()? as usize)
 	}
 	pub fn step(&mut self) {
+		self.fetched = None;
 		match self.increment_cmd {
 		    IncrementCmd::Increment => {
 				let current_pc = self.program_counter_reader.read().unwrap() ;
@@ -75,4 +135,116 @@ impl InstructionReader{
 			}
 		}
 	}
-}
\ No newline at end of file
+}
+
+pub struct InstructionFetchRequest<'a> {
+	addr		: usize,
+	words_cell	: &'a mut Option<[Word; WORDS_PER_INSTRUCTION]>,
+}
+
+impl InstructionFetchRequest<'_> {
+	/// Main memory address of the instruction's first word.
+	pub fn addr(&self) -> usize {
+		self.addr
+	}
+	/// Fetches the instruction's words, leaving an instruction that runs
+	/// past the end of memory unfetched for the controller to halt on.
+	pub fn satisfy(self, main_memory: &MainMemory) {
+		let memory = main_memory.0.read().unwrap();
+		*self.words_cell = memory.get(self.addr..self.addr + WORDS_PER_INSTRUCTION)
+			.map(|words| words.try_into().unwrap());
+	}
+	pub fn satisfy_with(self, words: [Word; WORDS_PER_INSTRUCTION]) {
+		*self.words_cell = Some(words);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::application::input::FamInput;
+	use crate::application::simulation::encoding::{encode_instruction, to_words};
+	use crate::application::simulation::simulation::Cpu;
+	use crate::application::simulation::talu::{LatencyTable, TaluOperation};
+
+	const MAX_STEPS: usize = 100;
+
+	/// Runs `cpu` until it stops, giving the steps it took.
+	fn run(cpu: &mut Cpu) -> usize {
+		let steps = (0..MAX_STEPS).take_while(|_| cpu.step()).count();
+		assert!(steps < MAX_STEPS, "still running after {MAX_STEPS} steps");
+		steps
+	}
+
+	fn von_neumann(program: Vec<Instruction>, main_memory: Vec<Word>, region: CodeRegion) -> Cpu {
+		let input = FamInput { program, main_memory, code_region: Some(region), ..Default::default() };
+		input.build_cpu().unwrap()
+	}
+
+	#[test]
+	fn runs_what_the_program_writes_over_itself() {
+		let target = 5;
+		let replacement = to_words(encode_instruction(&Instruction::SetLiteral { literal: 2, reg_addr: 5 }).unwrap());
+		let write = |data_input, address_input, activation_output| TaluOperation::WriteToMem {
+			data_input, address_input, activation_input: 3, activation_output,
+		};
+		let program = vec![
+			Instruction::SetTaluConfig { talu_addr: 0, talu_config: write(1, 2, Some(4)) },
+			Instruction::SetTaluConfig { talu_addr: 1, talu_config: write(11, 12, None) },
+			Instruction::SetLiteral { literal: 1, reg_addr: 3 },
+			Instruction::WaitForActivationSignal { register_index: 4 },
+			Instruction::SetLiteral { literal: 0, reg_addr: 3 },
+			Instruction::SetLiteral { literal: 1, reg_addr: 5 },
+		];
+		let region = CodeRegion { base: 0, len: program.len() };
+		let addr = region.address_of(target).unwrap();
+		let input = FamInput {
+			program,
+			main_memory: vec![0; region.end()],
+			code_region: Some(region),
+			registers: [
+				(1, replacement[0]), (2, addr as Word),
+				(11, replacement[1]), (12, addr as Word + 1),
+			].into(),
+			..Default::default()
+		};
+
+		let mut cpu = input.build_cpu().unwrap();
+		run(&mut cpu);
+		assert_eq!(cpu.fault(), None);
+		assert_eq!(cpu.register_bank.components[5].value, 2);
+
+		// Without the write, the instruction runs as loaded.
+		let mut cpu = FamInput { code_region: None, ..input }.build_cpu().unwrap();
+		run(&mut cpu);
+		assert_eq!(cpu.register_bank.components[5].value, 1);
+	}
+
+	#[test]
+	fn halts_on_words_that_arent_an_instruction() {
+		let mut cpu = von_neumann(Vec::new(), vec![0, 0, -1, -1], CodeRegion { base: 0, len: 2 });
+		run(&mut cpu);
+		let fault = cpu.fault().unwrap();
+		assert!(fault.starts_with("can't decode instruction 1 at main memory address 2: "), "{fault}");
+	}
+
+	#[test]
+	fn halts_on_instructions_past_the_end_of_memory() {
+		let mut cpu = Cpu::new(Vec::new(), vec![0; 3], LatencyTable::default());
+		cpu.controller.instruction_reader.code_region = Some(CodeRegion { base: 0, len: 2 });
+		run(&mut cpu);
+		assert_eq!(cpu.fault(), Some("instruction 1 at main memory address 2 is past the end of memory"));
+	}
+
+	#[test]
+	fn stops_at_a_pc_outside_the_region() {
+		for addr in [-1, 2, 10] {
+			let program = vec![Instruction::Jump { addr }, Instruction::NoOp];
+			let mut cpu = von_neumann(program, Vec::new(), CodeRegion { base: 4, len: 2 });
+			run(&mut cpu);
+			assert_eq!(cpu.fault(), None);
+			assert_eq!(cpu.register_bank.components[PROGRAM_COUNTER_REGISTER_ADDR].value, addr);
+			assert_eq!(cpu.controller.instructions_executed, 1);
+		}
+	}
+}
@@ -27,7 +27,79 @@ impl MainMemoryIo {
     pub fn read(& self, addr: usize) -> Word{
         self.0.read().unwrap().get(addr).unwrap().clone()
     }
+    /// Reads `len` words starting at `addr`, with words past the end of
+    /// memory read as `0`.
+    pub fn read_range(&self, addr: usize, len: usize) -> Vec<Word> {
+        let memory = self.0.read().unwrap();
+        (0..len)
+            .map(|ix| addr.checked_add(ix).and_then(|addr| memory.get(addr)).copied().unwrap_or_default())
+            .collect()
+    }
     pub fn write(&self, addr: usize, value: Word) {
         *self.0.write().unwrap().get_mut(addr).unwrap() = value;
     }
 }
+
+/// Reads one word of main memory per step, the way `CpuRegisterDataReader`
+/// reads a register.
+#[derive(Clone, Default)]
+pub enum MainMemoryDataReader {
+    #[default]
+    Deactivated,
+    Active {
+        addr    : usize,
+        value   : Option<Word>,
+    }
+}
+
+impl MainMemoryDataReader {
+    pub fn new() -> Self {
+        MainMemoryDataReader::Deactivated
+    }
+    pub fn deactivate(&mut self) {
+        *self = MainMemoryDataReader::Deactivated;
+    }
+    pub fn set_connection(&mut self, addr: Option<usize>) {
+        *self = match addr {
+            Some(addr) => MainMemoryDataReader::Active { addr, value: None },
+            None => MainMemoryDataReader::Deactivated,
+        };
+    }
+    pub fn read(&self) -> Option<Word> {
+        match self {
+            MainMemoryDataReader::Active { value, .. } => *value,
+            MainMemoryDataReader::Deactivated => None,
+        }
+    }
+    pub fn get_read_request(&mut self) -> Option<MainMemoryReadRequest<'_>> {
+        match self {
+            MainMemoryDataReader::Active { addr, value } => Some(MainMemoryReadRequest {
+                addr,
+                value_cell: value,
+            }),
+            MainMemoryDataReader::Deactivated => None,
+        }
+    }
+}
+
+pub struct MainMemoryReadRequest<'a> {
+    addr        : &'a usize,
+    value_cell  : &'a mut Option<Word>,
+}
+
+impl MainMemoryReadRequest<'_> {
+    pub fn addr(&self) -> &usize {
+        self.addr
+    }
+    /// Reads the word, leaving an address past the end of memory unread
+    /// for the reader's owner to halt on.
+    pub fn satisfy(self, main_memory: &MainMemory) {
+        let memory = main_memory.0.read().unwrap();
+        *self.value_cell = memory.get(*self.addr).copied();
+    }
+    /// Satisfies the request with a word read from elsewhere than a
+    /// `MainMemory`, e.g. one lane of a batch.
+    pub fn satisfy_with(self, value: Word) {
+        *self.value_cell = Some(value);
+    }
+}
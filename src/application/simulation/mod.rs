@@ -7,3 +7,8 @@ pub mod main_memory;
 pub mod simulation;
 pub mod component_bank;
 pub mod memory_primitives;
+pub mod propagation;
+pub mod scheduler;
+pub mod plan;
+pub mod batch;
+pub mod encoding;
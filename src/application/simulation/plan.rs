@@ -0,0 +1,246 @@
+use crate::application::simulation::talu::{fixed, float, simd, FixedOp, TaluBank, TaluCore, TaluOperation, TaluState};
+use crate::word::{ToActivation, ToWord, Word};
+
+/// How `Cpu::step` executes TALU operations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub enum SimulationBackend {
+    /// Interprets each TALU's operation on every step.
+    #[default]
+    Reference,
+    /// Runs an `ExecutionPlan` compiled from the TALU configurations.
+    Compiled,
+}
+
+/// Computes the data outputs of a stateless operation from its three data
+/// inputs, with `None` leaving `data_output_1` untouched.
+type Kernel = Box<dyn Fn(&[Word; 3]) -> (Word, Option<Word>) + Send + Sync>;
+
+enum PlanStep {
+    /// Falls back to `TaluCore::execute`.
+    Reference,
+    /// A stateless operation with no latency.
+    Pure(Kernel),
+}
+
+/// The TALU configurations lowered to one step per TALU, with operation
+/// parameters and latencies resolved up front. It has to be recompiled
+/// whenever a TALU is reconfigured.
+pub struct ExecutionPlan {
+    steps: Vec<PlanStep>,
+}
+
+impl ExecutionPlan {
+    pub fn compile(talu_bank: &TaluBank) -> Self {
+        Self::compile_talus(talu_bank.components.iter())
+    }
+
+    /// Compiles a plan from TALUs `0..TALU_COUNT`, in address order.
+    pub fn compile_talus<'a>(talus: impl IntoIterator<Item = &'a TaluCore>) -> Self {
+        ExecutionPlan {
+            steps: talus.into_iter().map(Self::lower).collect(),
+        }
+    }
+
+    fn lower(talu: &TaluCore) -> PlanStep {
+        if talu.latencies.latency_of(&talu.operation) > 0 {
+            return PlanStep::Reference;
+        }
+        match talu.operation {
+            TaluOperation::Mov { .. } => PlanStep::Pure(Box::new(|i| (i[0], None))),
+            TaluOperation::Cmp { op, .. } => PlanStep::Pure(Box::new(move |i| (op.holds(i[0], i[1]).to_word(), None))),
+            TaluOperation::Not { .. } => PlanStep::Pure(Box::new(|i| (!i[0], None))),
+            TaluOperation::And { .. } => PlanStep::Pure(Box::new(|i| (i[0] & i[1], None))),
+            TaluOperation::Or { .. } => PlanStep::Pure(Box::new(|i| (i[0] | i[1], None))),
+            TaluOperation::Xor { .. } => PlanStep::Pure(Box::new(|i| (i[0] ^ i[1], None))),
+            TaluOperation::ShiftLeft { .. } => PlanStep::Pure(Box::new(|i| (i[0] << i[1], None))),
+            TaluOperation::ShiftRight { .. } => PlanStep::Pure(Box::new(|i| (i[0] >> i[1], None))),
+            TaluOperation::Add { .. } => PlanStep::Pure(Box::new(|i| {
+                let (sum, overflow) = i[0].overflowing_add(i[1]);
+                (sum, Some(overflow as Word))
+            })),
+            TaluOperation::Sub { .. } => PlanStep::Pure(Box::new(|i| {
+                let (difference, overflow) = i[0].overflowing_sub(i[1]);
+                (difference, Some(overflow as Word))
+            })),
+            TaluOperation::Neg { .. } => PlanStep::Pure(Box::new(|i| (-i[0], None))),
+            TaluOperation::Fixed { op, format, overflow, .. } => PlanStep::Pure(Box::new(move |i| {
+                let (res, flag) = match op {
+                    FixedOp::Mul => fixed::mul(i[0], i[1], format, overflow),
+                    FixedOp::Div => fixed::div(i[0], i[1], format, overflow),
+                    FixedOp::RoundingShift => fixed::rounding_shift(i[0], i[1], overflow),
+                };
+                (res, Some(flag as Word))
+            })),
+            TaluOperation::Mux { .. } => PlanStep::Pure(Box::new(|i| {
+                let condition: bool = i[2].to_activation().into();
+                (if condition { i[0] } else { i[1] }, None)
+            })),
+            TaluOperation::Simd { op, lanes, signed, overflow, .. } => PlanStep::Pure(Box::new(move |i| {
+                (simd::apply(op, lanes, signed, overflow, i[0], i[1]), None)
+            })),
+            TaluOperation::Float { op, .. } => PlanStep::Pure(Box::new(move |i| {
+                (float::apply(op, i[0], i[1]), None)
+            })),
+            _ => PlanStep::Reference,
+        }
+    }
+
+    /// Executes `talu` for one step, with its inputs already read.
+    pub fn execute(&self, talu: &mut TaluCore) {
+        let PlanStep::Pure(kernel) = &self.steps[talu.addr] else {
+            talu.execute();
+            return;
+        };
+
+        if talu.activation_input.read().unwrap().into() {
+            let inputs = [
+                talu.data_input_0.read().unwrap_or_default(),
+                talu.data_input_1.read().unwrap_or_default(),
+                talu.data_input_2.read().unwrap_or_default(),
+            ];
+            let (out_0, out_1) = kernel(&inputs);
+            talu.data_output_0.write(out_0);
+            if let Some(out_1) = out_1 {
+                talu.data_output_1.write(out_1);
+            }
+            talu.activation_output.write(true);
+            talu.state = TaluState::JustProcessed;
+        } else if talu.state == TaluState::JustProcessed {
+            talu.state = TaluState::Closing;
+            talu.activation_output.write(false);
+        } else {
+            talu.state = TaluState::Done;
+            talu.activation_output.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::examples::make_loop_program;
+    use crate::application::input::FamInput;
+    use crate::application::simulation::instruction::Instruction;
+    use crate::application::simulation::propagation::PropagationModel;
+    use crate::application::simulation::simulation::Cpu;
+    use crate::application::simulation::talu::{CmpOp, FloatOp, LaneWidth, OverflowMode, QFormat, SimdOp};
+    use crate::Step;
+
+    const MAX_STEPS: Step = 300;
+
+    fn build(input: &FamInput, backend: SimulationBackend) -> Cpu {
+        let mut cpu = input.build_cpu().unwrap();
+        cpu.backend = backend;
+        cpu
+    }
+
+    fn registers(cpu: &Cpu) -> Vec<Word> {
+        cpu.register_bank.components.iter().map(|reg| reg.value).collect()
+    }
+
+    fn memory(cpu: &Cpu) -> Vec<Word> {
+        cpu.main_memory.0.read().unwrap().clone()
+    }
+
+    /// Steps both backends side by side for up to `MAX_STEPS`, comparing
+    /// registers and main memory after every step, and both finishing on
+    /// the same one.
+    fn compare_with_reference(input: &FamInput) {
+        let mut reference = build(input, SimulationBackend::Reference);
+        let mut compiled = build(input, SimulationBackend::Compiled);
+        for step in 1..=MAX_STEPS {
+            let running = reference.step();
+            assert_eq!(compiled.step(), running, "finished apart at step {step}");
+            assert_eq!(registers(&compiled), registers(&reference), "registers at step {step}");
+            assert_eq!(memory(&compiled), memory(&reference), "main memory at step {step}");
+            if !running {
+                return;
+            }
+        }
+    }
+
+    /// Configures every operation `lower` compiles, on inputs that change
+    /// every step: a count in r10, a running sum of a large odd number in
+    /// r11, the count's low five bits in r12 and its low bit in r13.
+    fn make_every_operation_program() -> FamInput {
+        let (one, wide_constant, low_bits) = (1, 2, 3);
+        let (count, wide, shift, condition) = (10, 11, 12, 13);
+        let mut operations = vec![
+            TaluOperation::Counter { activation_input: one, down_input: 0, reset_input: 0, data_output_0: count, activation_output: None },
+            TaluOperation::Accumulator { activation_input: one, data_input: wide_constant, reset_input: 0, data_output_0: wide, flags_output: None, activation_output: None },
+            TaluOperation::And { activation_input: one, data_input_0: count, data_input_1: low_bits, data_output_0: shift, activation_output: None },
+            TaluOperation::And { activation_input: one, data_input_0: count, data_input_1: one, data_output_0: condition, activation_output: None },
+        ];
+        let mut output = 20..;
+        let mut next = || output.next().unwrap();
+        operations.extend([
+            TaluOperation::Mov { activation_input: one, value_input: wide, data_output: next(), activation_output: None },
+            TaluOperation::Cmp { op: CmpOp::LessThan, activation_input: one, activation_output: None, data_input_0: wide, data_input_1: count, data_output: next() },
+            TaluOperation::Not { activation_input: one, data_input: wide, data_output: next(), activation_output: None },
+            TaluOperation::Or { activation_input: one, data_input_0: wide, data_input_1: count, data_output_0: next(), activation_output: None },
+            TaluOperation::Xor { activation_input: one, data_input_0: wide, data_input_1: wide_constant, data_output_0: next(), activation_output: None },
+            TaluOperation::ShiftLeft { activation_input: one, value: wide, shift_count: shift, data_output_0: next(), activation_output: None },
+            TaluOperation::ShiftRight { activation_input: one, value: wide, shift_count: shift, data_output_0: next(), activation_output: None },
+            TaluOperation::Add { activation_input: one, data_input_0: wide, data_input_1: wide_constant, result_output: next(), flags_output: Some(next()), activation_output: None },
+            TaluOperation::Sub { activation_input: one, data_input_0: wide, data_input_1: wide_constant, data_output_0: next(), flags_output: Some(next()), activation_output: None },
+            TaluOperation::Neg { activation_input: one, input: count, data_output_0: next(), activation_output: None },
+            TaluOperation::Fixed { op: FixedOp::Mul, format: QFormat { frac_bits: 16 }, overflow: OverflowMode::Saturate, activation_input: one, data_input_0: wide, data_input_1: wide_constant, data_output_0: next(), flags_output: Some(next()), activation_output: None },
+            TaluOperation::Fixed { op: FixedOp::Div, format: QFormat { frac_bits: 8 }, overflow: OverflowMode::Wrap, activation_input: one, data_input_0: wide, data_input_1: count, data_output_0: next(), flags_output: Some(next()), activation_output: None },
+            TaluOperation::Fixed { op: FixedOp::RoundingShift, format: QFormat { frac_bits: 0 }, overflow: OverflowMode::Saturate, activation_input: one, data_input_0: wide, data_input_1: shift, data_output_0: next(), flags_output: None, activation_output: None },
+            TaluOperation::Mux { activation_input: one, condition, if_true: wide, if_false: count, data_output_0: next(), activation_output: None },
+            TaluOperation::Simd { op: SimdOp::Add, lanes: LaneWidth::Bits8, signed: true, overflow: OverflowMode::Saturate, activation_input: one, data_input_0: wide, data_input_1: wide_constant, data_output_0: next(), activation_output: None },
+            TaluOperation::Simd { op: SimdOp::Sub, lanes: LaneWidth::Bits16, signed: false, overflow: OverflowMode::Wrap, activation_input: one, data_input_0: wide, data_input_1: wide_constant, data_output_0: next(), activation_output: None },
+            TaluOperation::Simd { op: SimdOp::Cmp(CmpOp::GreaterThan), lanes: LaneWidth::Bits8, signed: false, overflow: OverflowMode::Wrap, activation_input: one, data_input_0: wide, data_input_1: wide_constant, data_output_0: next(), activation_output: None },
+            TaluOperation::Simd { op: SimdOp::HorizontalSum, lanes: LaneWidth::Bits16, signed: true, overflow: OverflowMode::Wrap, activation_input: one, data_input_0: wide, data_input_1: 0, data_output_0: next(), activation_output: None },
+            TaluOperation::Float { op: FloatOp::Add, activation_input: one, data_input_0: wide, data_input_1: wide_constant, data_output_0: next(), activation_output: None },
+            TaluOperation::Float { op: FloatOp::Div, activation_input: one, data_input_0: wide, data_input_1: count, data_output_0: next(), activation_output: None },
+            TaluOperation::Float { op: FloatOp::Cmp(CmpOp::GreaterThanOrEq), activation_input: one, data_input_0: wide, data_input_1: wide_constant, data_output_0: next(), activation_output: None },
+            TaluOperation::Float { op: FloatOp::ToInt, activation_input: one, data_input_0: wide, data_input_1: 0, data_output_0: next(), activation_output: None },
+            TaluOperation::Float { op: FloatOp::FromInt, activation_input: one, data_input_0: count, data_input_1: 0, data_output_0: next(), activation_output: None },
+        ]);
+
+        let mut program = vec![
+            Instruction::SetLiteral { literal: 1, reg_addr: one },
+            Instruction::SetLiteral { literal: 0x1357_9bdf, reg_addr: wide_constant },
+            Instruction::SetLiteral { literal: 31, reg_addr: low_bits },
+        ];
+        program.extend(operations.into_iter().enumerate().map(|(talu_addr, talu_config)|
+            Instruction::SetTaluConfig { talu_addr, talu_config }
+        ));
+        // Nothing writes r60.
+        program.push(Instruction::WaitForActivationSignal { register_index: 60 });
+        FamInput { program, ..Default::default() }
+    }
+
+    fn examples() -> Vec<FamInput> {
+        let sample = serde_json::from_str(include_str!("../../../sample.json")).unwrap();
+        vec![sample, make_loop_program(), make_every_operation_program()]
+    }
+
+    #[test]
+    fn compiled_matches_reference() {
+        for input in examples() {
+            compare_with_reference(&input);
+        }
+    }
+
+    #[test]
+    fn compiled_matches_reference_combinationally() {
+        for mut input in examples() {
+            input.propagation = PropagationModel::Combinational;
+            compare_with_reference(&input);
+        }
+    }
+
+    #[test]
+    fn lowers_every_operation_of_the_program() {
+        let mut cpu = make_every_operation_program().build_cpu().unwrap();
+        while cpu.talu_bank.components[26].operation == TaluOperation::NoOp {
+            assert!(cpu.step());
+        }
+        let plan = ExecutionPlan::compile(&cpu.talu_bank);
+        // The Counter and Accumulator feeding the rest run on the reference engine.
+        let lowered = plan.steps.iter().filter(|step| matches!(step, PlanStep::Pure(_))).count();
+        assert_eq!(lowered, 25);
+    }
+}
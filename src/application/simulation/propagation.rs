@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use itertools::Itertools;
+use crate::application::simulation::cpu_registers::REGISTER_COUNT;
+use crate::application::simulation::talu::{TALU_COUNT, TaluAddress, TaluBank, TaluOperation};
+
+/// How values written by one TALU reach the TALUs that read them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub enum PropagationModel {
+    /// Every TALU reads before any of them writes, so a value crosses one
+    /// TALU per step.
+    #[default]
+    Registered,
+    /// TALUs run in dependency order and each sees the values written
+    /// earlier in the same step. A TALU reading its own output still sees
+    /// the value from the previous step.
+    Combinational,
+}
+
+/// TALUs that read each other's outputs in a cycle, or depend on one. They
+/// are evaluated in address order after every other TALU.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CombinationalLoop {
+    pub talus: Vec<TaluAddress>,
+}
+
+impl Display for CombinationalLoop {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "combinational loop through TALUs {}", self.talus.iter().join(", "))
+    }
+}
+
+/// Orders the TALUs so that every one comes after the TALUs writing the
+/// registers it reads, preferring lower addresses.
+pub fn evaluation_order(talu_bank: &TaluBank) -> (Vec<TaluAddress>, Option<CombinationalLoop>) {
+    evaluation_order_of(talu_bank.components.iter().map(|talu| &talu.operation))
+}
+
+/// `evaluation_order` for the operations of TALUs `0..TALU_COUNT`.
+pub fn evaluation_order_of<'a>(
+    operations: impl IntoIterator<Item = &'a TaluOperation>,
+) -> (Vec<TaluAddress>, Option<CombinationalLoop>) {
+    let ports = operations.into_iter()
+        .map(|operation| operation.get_ports_config())
+        .collect_vec();
+
+    let mut writers = vec![Vec::new(); REGISTER_COUNT];
+    for (addr, ports) in ports.iter().enumerate() {
+        for reg in [ports.data_output_0, ports.data_output_1, ports.activation_output]
+            .into_iter()
+            .flatten()
+        {
+            writers[reg].push(addr);
+        }
+    }
+
+    let mut dependents = vec![BTreeSet::new(); TALU_COUNT];
+    let mut pending_deps = vec![0; TALU_COUNT];
+    for (addr, ports) in ports.iter().enumerate() {
+        let deps: BTreeSet<TaluAddress> =
+            [ports.data_input_0, ports.data_input_1, ports.data_input_2, ports.activation_input]
+            .into_iter()
+            .flatten()
+            .flat_map(|reg| writers[reg].iter().copied())
+            .filter(|writer| *writer != addr)
+            .collect();
+        pending_deps[addr] = deps.len();
+        for dep in deps {
+            dependents[dep].insert(addr);
+        }
+    }
+
+    let mut ready: BTreeSet<TaluAddress> =
+        (0..TALU_COUNT).filter(|addr| pending_deps[*addr] == 0).collect();
+    let mut order = Vec::with_capacity(TALU_COUNT);
+    while let Some(addr) = ready.pop_first() {
+        order.push(addr);
+        for &dependent in &dependents[addr] {
+            pending_deps[dependent] -= 1;
+            if pending_deps[dependent] == 0 {
+                ready.insert(dependent);
+            }
+        }
+    }
+
+    if order.len() == TALU_COUNT {
+        return (order, None);
+    }
+    let looped = (0..TALU_COUNT).filter(|addr| pending_deps[*addr] > 0).collect_vec();
+    order.extend(looped.iter().copied());
+    (order, Some(CombinationalLoop { talus: looped }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::simulation::cpu_registers::CpuRegisterAddress;
+    use crate::application::simulation::instruction::Instruction;
+    use crate::application::simulation::simulation::Cpu;
+    use crate::word::Word;
+    use crate::Step;
+
+    const ONE: CpuRegisterAddress = 1;
+    const NEVER_SET: CpuRegisterAddress = 20;
+
+    fn add_one(input: CpuRegisterAddress, output: CpuRegisterAddress) -> TaluOperation {
+        TaluOperation::Add {
+            activation_input: ONE,
+            data_input_0: input,
+            data_input_1: ONE,
+            result_output: output,
+            flags_output: None,
+            activation_output: None,
+        }
+    }
+
+    /// Three TALUs adding one in a chain r10 → r11 → r12 → r13, at
+    /// addresses against the order of the chain, then 7 written to r10.
+    fn chain() -> Vec<Instruction> {
+        vec![
+            Instruction::SetLiteral { literal: 1, reg_addr: ONE },
+            Instruction::SetTaluConfig { talu_addr: 2, talu_config: add_one(10, 11) },
+            Instruction::SetTaluConfig { talu_addr: 1, talu_config: add_one(11, 12) },
+            Instruction::SetTaluConfig { talu_addr: 0, talu_config: add_one(12, 13) },
+            Instruction::SetLiteral { literal: 7, reg_addr: 10 },
+            Instruction::WaitForActivationSignal { register_index: NEVER_SET },
+        ]
+    }
+
+    /// The first step after which each of `registers` holds its value in
+    /// `expected`.
+    fn settled_at(propagation: PropagationModel, registers: &[CpuRegisterAddress], expected: &[Word]) -> Vec<Step> {
+        let mut cpu = Cpu::new(chain(), Vec::new(), Default::default());
+        cpu.propagation = propagation;
+        let mut settled = vec![None; registers.len()];
+        for step in 1..=50 {
+            assert!(cpu.step());
+            for (ix, reg) in registers.iter().enumerate() {
+                if settled[ix].is_none() && cpu.register_bank.components[*reg].value == expected[ix] {
+                    settled[ix] = Some(step);
+                }
+            }
+        }
+        settled.into_iter().map(|step| step.expect("every register settles")).collect()
+    }
+
+    #[test]
+    fn registered_takes_a_step_per_talu() {
+        let registers = [11, 12, 13];
+        let expected = [8, 9, 10];
+        let combinational = settled_at(PropagationModel::Combinational, &registers, &expected);
+        let registered = settled_at(PropagationModel::Registered, &registers, &expected);
+
+        assert_eq!(combinational, [combinational[0]; 3], "combinational results settle together");
+        assert_eq!(registered[0], combinational[0]);
+        assert_eq!(registered, [registered[0], registered[0] + 1, registered[0] + 2]);
+    }
+
+    #[test]
+    fn orders_by_dependency() {
+        let mut operations = [TaluOperation::NoOp; TALU_COUNT];
+        operations[2] = add_one(10, 11);
+        operations[1] = add_one(11, 12);
+        operations[0] = add_one(12, 13);
+        let (order, combinational_loop) = evaluation_order_of(&operations);
+
+        assert_eq!(combinational_loop, None);
+        let position = |addr| order.iter().position(|talu| *talu == addr).unwrap();
+        assert!(position(2) < position(1) && position(1) < position(0));
+    }
+
+    #[test]
+    fn finds_loops_but_not_talus_reading_their_own_output() {
+        let mut operations = [TaluOperation::NoOp; TALU_COUNT];
+        operations[0] = add_one(13, 13);
+        assert_eq!(evaluation_order_of(&operations).1, None);
+
+        operations[3] = add_one(11, 12);
+        operations[5] = add_one(12, 11);
+        let (order, combinational_loop) = evaluation_order_of(&operations);
+        assert_eq!(combinational_loop, Some(CombinationalLoop { talus: vec![3, 5] }));
+        assert_eq!(order[TALU_COUNT - 2..], [3, 5]);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::application::simulation::cpu_registers::{CpuRegisterAddress, REGISTER_COUNT};
+use crate::application::simulation::talu::{TALU_COUNT, TaluAddress, TaluBank};
+use crate::word::Word;
+
+/// One bit per TALU.
+pub type TaluMask = u32;
+
+const _: () = assert!(TALU_COUNT <= TaluMask::BITS as usize);
+
+pub const ALL_TALUS: TaluMask = TaluMask::MAX >> (TaluMask::BITS as usize - TALU_COUNT);
+
+/// Which TALUs `Cpu::step` evaluates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub enum TaluScheduling {
+    /// Every TALU reads and executes on every step.
+    #[default]
+    FullSweep,
+    /// Only TALUs whose input registers changed since they last ran, or
+    /// that are not quiescent, read and execute. All TALUs still write, as
+    /// outputs are held between evaluations. Results are identical to
+    /// `FullSweep`, but skipped TALUs report no read connections.
+    EventDriven,
+}
+
+/// Tracks register changes and the TALUs they wake up.
+#[derive(Clone, Debug)]
+pub struct EventScheduler {
+    /// TALUs reading each register, rebuilt whenever a TALU is reconfigured.
+    register_readers: [TaluMask; REGISTER_COUNT],
+    /// TALUs with an input register changed since they last ran.
+    dirty: TaluMask,
+}
+
+impl EventScheduler {
+    pub fn new() -> Self {
+        EventScheduler {
+            register_readers: [0; REGISTER_COUNT],
+            dirty: ALL_TALUS,
+        }
+    }
+
+    pub fn reconfigured(&mut self, talu_bank: &TaluBank) {
+        self.register_readers = [0; REGISTER_COUNT];
+        for (addr, talu) in talu_bank.components.iter().enumerate() {
+            let ports = talu.operation.get_ports_config();
+            for reg in [ports.data_input_0, ports.data_input_1, ports.data_input_2, ports.activation_input]
+                .into_iter()
+                .flatten()
+            {
+                self.register_readers[reg] |= 1 << addr;
+            }
+        }
+        self.dirty = ALL_TALUS;
+    }
+
+    pub fn register_written(&mut self, addr: CpuRegisterAddress, old_value: Word, new_value: Word) {
+        if old_value != new_value {
+            self.dirty |= self.register_readers[addr];
+        }
+    }
+
+    /// Whether the TALU at `addr` has to run, consuming its pending changes.
+    pub fn take_due(&mut self, talu_bank: &TaluBank, addr: TaluAddress) -> bool {
+        let bit = 1 << addr;
+        let dirty = self.dirty & bit != 0;
+        self.dirty &= !bit;
+        dirty || !talu_bank.components[addr].is_quiescent()
+    }
+}
+
+impl Default for EventScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::examples::make_loop_program;
+    use crate::application::input::FamInput;
+    use crate::application::simulation::propagation::PropagationModel;
+    use crate::application::simulation::simulation::Cpu;
+    use crate::Step;
+
+    const MAX_STEPS: Step = 1_000;
+
+    fn build(input: &FamInput, scheduling: TaluScheduling) -> Cpu {
+        let mut cpu = input.build_cpu().unwrap();
+        cpu.scheduling = scheduling;
+        cpu
+    }
+
+    fn registers(cpu: &Cpu) -> Vec<Word> {
+        cpu.register_bank.components.iter().map(|reg| reg.value).collect()
+    }
+
+    fn memory(cpu: &Cpu) -> Vec<Word> {
+        cpu.main_memory.0.read().unwrap().clone()
+    }
+
+    /// Steps both schedulings side by side for up to `MAX_STEPS`, comparing
+    /// registers and main memory after every step, and both finishing on
+    /// the same one. Gives whether they finished.
+    fn compare_with_full_sweep(input: &FamInput) -> bool {
+        let mut full = build(input, TaluScheduling::FullSweep);
+        let mut event = build(input, TaluScheduling::EventDriven);
+        for step in 1..=MAX_STEPS {
+            let running = full.step();
+            assert_eq!(event.step(), running, "finished apart at step {step}");
+            assert_eq!(registers(&event), registers(&full), "registers at step {step}");
+            assert_eq!(memory(&event), memory(&full), "main memory at step {step}");
+            if !running {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn examples() -> Vec<FamInput> {
+        let sample = serde_json::from_str(include_str!("../../../sample.json")).unwrap();
+        // Memory latencies keep TALUs busy, and so not quiescent, for a few steps.
+        let mut slow = make_loop_program();
+        slow.talu_latencies.per_operation.insert("ReadFromMem".to_string(), 2);
+        slow.talu_latencies.per_operation.insert("WriteToMem".to_string(), 3);
+        vec![sample, make_loop_program(), slow]
+    }
+
+    #[test]
+    fn event_driven_matches_full_sweep() {
+        for input in examples() {
+            assert!(compare_with_full_sweep(&input), "still running after {MAX_STEPS} steps");
+        }
+    }
+
+    #[test]
+    fn event_driven_matches_full_sweep_combinationally() {
+        // The loop program has a combinational loop through TALUs 0, 1 and 2, and stalls.
+        for mut input in examples() {
+            input.propagation = PropagationModel::Combinational;
+            compare_with_full_sweep(&input);
+        }
+    }
+
+}
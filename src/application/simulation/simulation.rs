@@ -1,4 +1,5 @@
 use std::collections::HashSet;
+use std::sync::Arc;
 use std::{array, iter};
 use std::ops::Not;
 use getset::Getters;
@@ -6,12 +7,15 @@ use itertools::Itertools;
 use wgpu::naga::{FastHashMap, FastHashSet};
 use crate::application::connection::{CpuConnection, CpuConnectionEndpoint};
 use crate::application::grid::connection::ConnectionEndpoint;
-use crate::application::simulation::talu::{TALU_COUNT, TaluAddress, TaluBank, TaluCore, TaluOperation, TaluPortName};
+use crate::application::simulation::talu::{LatencyTable, TALU_COUNT, TaluAddress, TaluBank, TaluCore, TaluOperation, TaluPortName};
 use crate::application::simulation::controller::{self, Controller, ControllerPortName, TaluConfigWriter};
 use crate::application::simulation::cpu_registers::{CpuRegisterAddress, CpuRegisterBank, CpuRegisterPortName, REGISTER_COUNT};
 use crate::application::simulation::instruction::Instruction;
 use crate::application::simulation::instruction_reader::{InstructionMemory, InstructionReader};
 use crate::application::simulation::main_memory::MainMemory;
+use crate::application::simulation::propagation::{evaluation_order, CombinationalLoop, PropagationModel};
+use crate::application::simulation::scheduler::{EventScheduler, TaluScheduling};
+use crate::application::simulation::plan::{ExecutionPlan, SimulationBackend};
 use crate::{PROGRAM_COUNTER_REGISTER_ADDR, Step};
 use crate::word::Word;
 
@@ -20,13 +24,27 @@ pub type NetlistId = u16;
 #[derive(Clone, Default, Debug)]
 pub struct Netlists { 
     next_netlist_id: NetlistId,
-    netlists: FastHashMap<CpuConnectionEndpoint, NetlistId>  
+    netlists: FastHashMap<CpuConnectionEndpoint, NetlistId>,
+    /// The connections the netlists were last built from.
+    connections: FastHashSet<CpuConnection>,
 }
 
 impl Netlists{
     
    pub fn new() -> Self{
-        Netlists{ next_netlist_id: 0, netlists: Default::default() }
+        Default::default()
+    }
+    /// Rebuilds the netlists from `connections`, unless they're the ones
+    /// they were last built from.
+    pub fn update(&mut self, connections: &FastHashSet<CpuConnection>){
+        if self.connections == *connections {
+            return;
+        }
+        self.clear();
+        self.connections.clone_from(connections);
+        for conn in connections.iter(){
+            self.add(conn)
+        }
     }
     pub fn get_for_endpoint(&self, endpoint: &CpuConnectionEndpoint) -> Option<NetlistId>{
         self.netlists.get(endpoint).cloned()
@@ -36,6 +54,7 @@ impl Netlists{
     }
     pub fn clear(&mut self){
         self.netlists.clear();
+        self.connections.clear();
         self.next_netlist_id = 0;
     }
      pub fn add(&mut self, conn: &CpuConnection){
@@ -62,10 +81,91 @@ pub struct Cpu {
 
     pub connections         : FastHashSet<CpuConnection>,
     pub netlists            : Netlists,
+    /// When false, `step` skips recording connections and updating
+    /// netlists. Only the visualizer needs them.
+    pub track_connections   : bool,
     pub is_done             : bool,
+
+    pub propagation         : PropagationModel,
+    /// TALU evaluation order for combinational propagation, recomputed
+    /// whenever a TALU is reconfigured.
+    pub talu_order          : Option<Vec<TaluAddress>>,
+    pub combinational_loop  : Option<CombinationalLoop>,
+
+    pub scheduling          : TaluScheduling,
+    pub scheduler           : EventScheduler,
+
+    pub backend             : SimulationBackend,
+    /// Compiled TALU configurations for the `Compiled` backend, dropped
+    /// whenever a TALU is reconfigured.
+    pub plan                : Option<ExecutionPlan>,
 }
 
+// Machines are run on worker threads, see `application::runner`.
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<Cpu>();
+};
+
 impl Cpu {
+    pub fn new(program: Vec<Instruction>, data: Vec<Word>, latencies: LatencyTable) -> Self {
+        let mut main_memory = MainMemory::new(data);
+        let instruction_memory = InstructionMemory::new(program);
+        let talu_bank = TaluBank::new(&mut main_memory, &Arc::new(latencies));
+        let controller = Controller::new(&instruction_memory);
+
+        Self::from_components(talu_bank, CpuRegisterBank::new(), controller, instruction_memory, main_memory)
+    }
+
+    /// Assembles a CPU around components that may already be mid-run.
+    pub fn from_components(
+        talu_bank           : TaluBank,
+        register_bank       : CpuRegisterBank,
+        controller          : Controller,
+        instruction_memory  : InstructionMemory,
+        main_memory         : MainMemory,
+    ) -> Self {
+        let mut scheduler = EventScheduler::new();
+        scheduler.reconfigured(&talu_bank);
+
+        Cpu {
+            talu_bank,
+            register_bank,
+            controller,
+            instruction_memory,
+            main_memory,
+            connections: Default::default(),
+            netlists: Default::default(),
+            track_connections: true,
+            is_done: false,
+            propagation: Default::default(),
+            talu_order: None,
+            combinational_loop: None,
+            scheduling: Default::default(),
+            scheduler,
+            backend: Default::default(),
+            plan: None,
+        }
+    }
+
+    /// Why the program halted before its end, if it did.
+    pub fn fault(&self) -> Option<&str> {
+        self.controller.fault.as_deref()
+    }
+
+    fn connect(
+        connections: &mut FastHashSet<CpuConnection>,
+        track: bool,
+        a: CpuConnectionEndpoint,
+        b: CpuConnectionEndpoint,
+    ) {
+        if track {
+            connections.insert(CpuConnection::new(a, b));
+        }
+    }
+
+    /// Advances the simulation by one step. Allocation-free in steady state:
+    /// only reconfiguring a TALU may touch the heap.
     #[must_use]
     pub fn step(&mut self) -> bool {
         if self.is_done { return false; }
@@ -74,17 +174,26 @@ impl Cpu {
 
         if let Some(mut controller_read_req) =
             self.controller.cpu_registers_reader.get_read_request() {
-            self.connections.insert(CpuConnection::new(
+            Self::connect(&mut self.connections, self.track_connections,
                 CpuConnectionEndpoint::Controller(ControllerPortName::RegisterReader), 
                 CpuConnectionEndpoint::Register(
                     *controller_read_req.addr(), 
                     CpuRegisterPortName::Output
                 )
-            ));
+            );
 
             controller_read_req.satisfy( &self.register_bank);
         }
 
+        if let Some(memory_read_req) = self.controller.main_memory_reader.get_read_request() {
+            Self::connect(&mut self.connections, self.track_connections,
+                CpuConnectionEndpoint::Controller(ControllerPortName::MainMemoryReader),
+                CpuConnectionEndpoint::MainMemory,
+            );
+
+            memory_read_req.satisfy(&self.main_memory);
+        }
+
 
         if let Some(mut controller_pc_read_req) =
             self.controller
@@ -92,7 +201,7 @@ impl Cpu {
             .program_counter_reader
             .get_read_request()
         {
-            self.connections.insert(CpuConnection::new(
+            Self::connect(&mut self.connections, self.track_connections,
                 CpuConnectionEndpoint::Controller(
                     ControllerPortName::ProgramCounterReader
                 ), 
@@ -100,81 +209,115 @@ impl Cpu {
                     PROGRAM_COUNTER_REGISTER_ADDR, 
                     CpuRegisterPortName::Output
                 )
-           ));
+           );
 
             controller_pc_read_req.satisfy(&self.register_bank);
         }
 
+        if let Some(fetch_req) = self.controller.get_instruction_fetch_request() {
+            Self::connect(&mut self.connections, self.track_connections,
+                CpuConnectionEndpoint::Controller(ControllerPortName::MainMemoryReader),
+                CpuConnectionEndpoint::MainMemory,
+            );
+
+            fetch_req.satisfy(&self.main_memory);
+        }
+
         if let Some(config_write_request) = 
             self.controller
             .talu_config_writer
             .get_config_write_request()
         {
             if let Some(addr) = config_write_request.address(){
-                self.connections.insert(CpuConnection::new(
+                Self::connect(&mut self.connections, self.track_connections,
                     CpuConnectionEndpoint::Controller(
                         ControllerPortName::TaluConfigWriter
                     ),
                     CpuConnectionEndpoint::Talu(*addr, TaluPortName::SetupIn)
-                ));
+                );
             } else {
                 for talu_addr in 0..TALU_COUNT{
-                    self.connections.insert(CpuConnection::new(
+                    Self::connect(&mut self.connections, self.track_connections,
                         CpuConnectionEndpoint::Controller(
                             ControllerPortName::TaluConfigWriter
                         ),
                         CpuConnectionEndpoint::Talu(talu_addr, TaluPortName::SetupIn)
-                    ));
+                    );
                 }
             }
             config_write_request.satisfy(&mut self.talu_bank);
+            self.talu_order = None;
+            self.plan = None;
+            self.scheduler.reconfigured(&self.talu_bank);
         } 
 
-
-        // give talus the requested data
-        for ( talu_addr, talu ) in self.talu_bank.components.iter_mut().enumerate(){
-            let mut reqs = talu.collect_read_requests();
-            for (port, req) in reqs{
-                self.connections.insert( CpuConnection::new(
-                    CpuConnectionEndpoint::Talu(talu_addr, port),
-                    CpuConnectionEndpoint::Register(*req.addr(), CpuRegisterPortName::Output)
-                ));
-                req.satisfy(&self.register_bank);
-            }
-            // talu_reads.push(reqs);
-        }
-
         if self.controller.execute().not(){
             self.is_done = true;
         };
 
-        for talu in self.talu_bank.components.iter_mut(){
-            talu.execute();
-        }
+        let plan = match self.backend {
+            SimulationBackend::Reference => None,
+            SimulationBackend::Compiled => Some(
+                self.plan.take().unwrap_or_else(|| ExecutionPlan::compile(&self.talu_bank))
+            ),
+        };
 
-        for ( talu_addr, talu ) in self.talu_bank.components.iter_mut().enumerate(){
-            let reqs = talu.collect_write_requests();
-            for (talu_port, req) in reqs{
-                self.connections.insert( CpuConnection::new(
-                    CpuConnectionEndpoint::Talu(talu_addr, talu_port),
-                    CpuConnectionEndpoint::Register(*req.addr(), CpuRegisterPortName::Input)
-                ));
-                req.satisfy(&mut self.register_bank);
+        match self.propagation {
+            PropagationModel::Registered => {
+                let mut due = [true; TALU_COUNT];
+                for talu_addr in 0..TALU_COUNT {
+                    due[talu_addr] = self.is_talu_due(talu_addr);
+                }
+                // give talus the requested data
+                for talu_addr in 0..TALU_COUNT {
+                    if due[talu_addr] {
+                        self.read_talu_inputs(talu_addr);
+                    }
+                }
+                for (talu, due) in self.talu_bank.components.iter_mut().zip(due){
+                    if due {
+                        Self::execute_talu(plan.as_ref(), talu);
+                    }
+                }
+                for talu_addr in 0..TALU_COUNT {
+                    self.write_talu_outputs(talu_addr);
+                }
+            }
+            PropagationModel::Combinational => {
+                let order = match self.talu_order.take() {
+                    Some(order) => order,
+                    None => {
+                        let (order, combinational_loop) = evaluation_order(&self.talu_bank);
+                        self.combinational_loop = combinational_loop;
+                        order
+                    }
+                };
+                for &talu_addr in order.iter() {
+                    if self.is_talu_due(talu_addr) {
+                        self.read_talu_inputs(talu_addr);
+                        Self::execute_talu(plan.as_ref(), &mut self.talu_bank.components[talu_addr]);
+                    }
+                    self.write_talu_outputs(talu_addr);
+                }
+                self.talu_order = Some(order);
             }
-            // talu_reads.push(reqs);
         }
+        self.plan = plan;
 
         if let Some(req) = self.controller.cpu_registers_writer.get_write_request(){
-            self.connections.insert( CpuConnection::new(
+            Self::connect(&mut self.connections, self.track_connections,
                 CpuConnectionEndpoint::Controller(ControllerPortName::RegisterWriter),
                 CpuConnectionEndpoint::Register(*req.addr(), CpuRegisterPortName::Input)
-            ));
+            );
 
+            let addr = *req.addr();
+            let old_value = self.register_bank.components[addr].value;
             req.satisfy(&mut self.register_bank);
+            self.scheduler.register_written(addr, old_value, self.register_bank.components[addr].value);
         }
 
         if let Some(write_pc_req) = self.controller.instruction_reader.program_counter_writer.get_write_request(){
-            self.connections.insert( CpuConnection::new(
+            Self::connect(&mut self.connections, self.track_connections,
                 CpuConnectionEndpoint::Controller(
                     ControllerPortName::ProgramCounterWriter
                 ),
@@ -182,18 +325,62 @@ impl Cpu {
                     PROGRAM_COUNTER_REGISTER_ADDR, 
                     CpuRegisterPortName::Input
                 )
-            ));
+            );
 
+            let old_value = self.register_bank.components[PROGRAM_COUNTER_REGISTER_ADDR].value;
             write_pc_req.satisfy(&mut self.register_bank);
+            self.scheduler.register_written(
+                PROGRAM_COUNTER_REGISTER_ADDR,
+                old_value,
+                self.register_bank.components[PROGRAM_COUNTER_REGISTER_ADDR].value,
+            );
         }
 
-        self.netlists.clear();
-        for conn in self.connections.iter(){
-            self.netlists.add(conn)
+        if self.track_connections {
+            self.netlists.update(&self.connections);
         }
 
         return !self.is_done ;
     }
+
+    fn execute_talu(plan: Option<&ExecutionPlan>, talu: &mut TaluCore) {
+        match plan {
+            Some(plan) => plan.execute(talu),
+            None => talu.execute(),
+        }
+    }
+
+    fn is_talu_due(&mut self, talu_addr: TaluAddress) -> bool {
+        match self.scheduling {
+            TaluScheduling::FullSweep => true,
+            TaluScheduling::EventDriven => self.scheduler.take_due(&self.talu_bank, talu_addr),
+        }
+    }
+
+    fn read_talu_inputs(&mut self, talu_addr: TaluAddress) {
+        let reqs = self.talu_bank.components[talu_addr].collect_read_requests();
+        for (port, req) in reqs{
+            Self::connect(&mut self.connections, self.track_connections,
+                CpuConnectionEndpoint::Talu(talu_addr, port),
+                CpuConnectionEndpoint::Register(*req.addr(), CpuRegisterPortName::Output)
+            );
+            req.satisfy(&self.register_bank);
+        }
+    }
+
+    fn write_talu_outputs(&mut self, talu_addr: TaluAddress) {
+        let reqs = self.talu_bank.components[talu_addr].collect_write_requests();
+        for (talu_port, req) in reqs{
+            Self::connect(&mut self.connections, self.track_connections,
+                CpuConnectionEndpoint::Talu(talu_addr, talu_port),
+                CpuConnectionEndpoint::Register(*req.addr(), CpuRegisterPortName::Input)
+            );
+            let addr = *req.addr();
+            let old_value = self.register_bank.components[addr].value;
+            req.satisfy(&mut self.register_bank);
+            self.scheduler.register_written(addr, old_value, self.register_bank.components[addr].value);
+        }
+    }
 }
 
 
@@ -1,15 +1,17 @@
-use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
 use super::TaluOperation;
 use crate::application::draw::port::SignalType::Activation;
 use crate::application::draw::port::{PortDefns, PortSignalDirection, SignalType};
 use crate::application::grid::component::{PortDataContainer, PortName};
-use crate::application::simulation::talu::CmpOp;
+use crate::application::simulation::talu::{fixed, float, simd, LatencyTable, Edge, FixedOp, DELAY_MAX_STEPS, LUT_MAX_SIZE_LOG2};
 use crate::application::simulation::talu::TaluPortName::{
-    ActivationIn, ActivationOut, DataIn0, DataIn1, DataOut0, DataOut1, SetupIn
+    ActivationIn, ActivationOut, DataIn0, DataIn1, DataIn2, DataOut0, DataOut1, SetupIn
 };
 use crate::application::simulation::main_memory::{MainMemory, MainMemoryIo};
 use crate::application::simulation::memory_primitives::register::Register;
 use crate::word::{ToActivation, ToWord, Word};
+use crate::Step;
 use std::mem::transmute;
 use std::ops::Index;
 use PortSignalDirection::{Input, Output};
@@ -21,6 +23,7 @@ use crate::application::simulation::cpu_registers::{CpuRegisterActReader, CpuReg
 pub enum TaluPortName {
     DataIn0,
     DataIn1,
+    DataIn2,
     ActivationIn,
 
     DataOut0,
@@ -34,6 +37,7 @@ impl PortName for TaluPortName {
         vec![
             DataIn0,
             DataIn1,
+            DataIn2,
             ActivationIn,
             DataOut0,
             DataOut1,
@@ -46,6 +50,7 @@ impl PortName for TaluPortName {
         match self {
             DataIn0 => "di0",
             DataIn1 => "di1",
+            DataIn2 => "di2",
             ActivationIn => "ai",
             DataOut0 => "do0",
             DataOut1 => "do1",
@@ -59,6 +64,7 @@ pub struct TaluPortsDefns {
     // pub state_in            : PortInfo,
     pub data_input_0    : PortDefns,
     pub data_input_1    : PortDefns,
+    pub data_input_2    : PortDefns,
     pub activation_input: PortDefns,
 
     pub data_output_0   : PortDefns,
@@ -72,6 +78,7 @@ impl PortDataContainer<TaluPortName, PortDefns> for TaluPortsDefns {
             ActivationIn => &self.activation_input,
             DataIn0 => &self.data_input_0,
             DataIn1 => &self.data_input_1,
+            DataIn2 => &self.data_input_2,
             DataOut0 => &self.data_output_0,
             DataOut1 => &self.data_output_1,
             ActivationOut => &self.activation_output,
@@ -80,11 +87,25 @@ impl PortDataContainer<TaluPortName, PortDefns> for TaluPortsDefns {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+/// Where a TALU is in its activation pulse. An activation seen on a step
+/// makes the TALU write its outputs and drive `activation_output` high on that
+/// same step. The following step drives it low again, and after that the
+/// output is left alone, so a single-step activation produces a single-step
+/// pulse, and an activation held high keeps the output high.
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, schemars::JsonSchema)]
 pub enum TaluState{
+    /// Drove `activation_output` low this step, after having fired on the
+    /// previous one.
     Closing,
+    /// Fired this step: outputs were written and `activation_output` is high.
     JustProcessed,
-    Done
+    /// Idle, and not writing `activation_output` at all.
+    Done,
+    /// Activated, but still `remaining` steps away from writing its results,
+    /// as set by the operation's latency.
+    Busy {
+        remaining: Step,
+    },
 }
 
 
@@ -97,9 +118,18 @@ pub struct TaluCore {
 
     pub inner_memory_0  : Word,
     pub inner_memory_1  : Word,
+    pub table           : Vec<Word>,
+    pub delay_line      : VecDeque<Option<Word>>,
+    pub latencies       : Arc<LatencyTable>,
+    /// Data and activation inputs seen when the TALU became busy.
+    latched_inputs      : [Option<Word>; 4],
+    /// Set when the TALU went busy again on the step it fired, so the next
+    /// step drives `activation_output` low.
+    pulse_open          : bool,
 
     pub data_input_0    : CpuRegisterDataReader,
     pub data_input_1    : CpuRegisterDataReader,
+    pub data_input_2    : CpuRegisterDataReader,
     pub activation_input: CpuRegisterActReader,
 
     pub data_output_0   : CpuRegisterDataWriter,
@@ -114,19 +144,26 @@ pub struct TaluCore {
 // }
 
 impl TaluCore {
-    pub fn collect_read_requests<'a>(&'a mut self) -> HashMap<TaluPortName, CpuRegisterReadRequest<'a>> {
+    /// Pending register reads of this TALU's input ports. Yields them lazily
+    /// so that the simulation step does not allocate.
+    pub fn collect_read_requests<'a>(&'a mut self)
+        -> impl Iterator<Item = (TaluPortName, CpuRegisterReadRequest<'a>)> + 'a
+    {
         [
             (DataIn0, self.data_input_0.get_read_request()),
             (DataIn1, self.data_input_1.get_read_request()),
+            (DataIn2, self.data_input_2.get_read_request()),
             (ActivationIn, self.activation_input.get_read_request()),
         ]
         .into_iter()
         .filter_map(|(name, opt_req)|
             opt_req.map(|req| (name, req))
         )
-        .collect()
     }
-    pub fn collect_write_requests(&mut self) -> HashMap<TaluPortName, CpuRegisterWriteRequest> {
+    /// Pending register writes of this TALU's output ports.
+    pub fn collect_write_requests(&self)
+        -> impl Iterator<Item = (TaluPortName, CpuRegisterWriteRequest<'_>)> + '_
+    {
         [
             (DataOut0, self.data_output_0.get_write_request()),
             (DataOut1, self.data_output_1.get_write_request()),
@@ -136,9 +173,8 @@ impl TaluCore {
         .filter_map(|(name, opt_req)|
             opt_req.map(|req| (name, req))
         )
-        .collect()
     }
-    pub fn new(talu_addr: usize, main_memory: &MainMemory) -> Self {
+    pub fn new(talu_addr: usize, main_memory: &MainMemory, latencies: &Arc<LatencyTable>) -> Self {
         TaluCore {
             state               : TaluState::Closing,
             addr                : talu_addr,
@@ -148,9 +184,15 @@ impl TaluCore {
 
             inner_memory_0      : Default::default(),
             inner_memory_1      : Default::default(),
+            table               : Vec::new(),
+            delay_line          : VecDeque::new(),
+            latencies           : latencies.clone(),
+            latched_inputs      : [None; 4],
+            pulse_open          : false,
 
             data_input_0        : CpuRegisterDataReader::new(),
             data_input_1        : CpuRegisterDataReader::new(),
+            data_input_2        : CpuRegisterDataReader::new(),
             activation_input    : CpuRegisterActReader::new(),
 
             data_output_0       : CpuRegisterDataWriter::new(),
@@ -171,6 +213,11 @@ impl TaluCore {
                 signal_dir: Input,
                 signal_type: Data,
             },
+            data_input_2: PortDefns {
+                active: true,
+                signal_dir: Input,
+                signal_type: Data,
+            },
             activation_input: PortDefns {
                 active: true,
                 signal_dir: Input,
@@ -204,10 +251,12 @@ impl TaluCore {
         self.old_operation = self.operation.clone();
         self.operation = new_operation.clone();
         self.state = TaluState::Done;
+        self.pulse_open = false;
 
         let ports_config = new_operation.get_ports_config();
         self.data_input_0.set_connection(ports_config.data_input_0);
         self.data_input_1.set_connection(ports_config.data_input_1);
+        self.data_input_2.set_connection(ports_config.data_input_2);
         self.activation_input
             .set_connection(ports_config.activation_input);
         self.data_output_0
@@ -218,9 +267,97 @@ impl TaluCore {
             .set_connection(ports_config.activation_output);
         self.inner_memory_0 = 0;
         self.inner_memory_1 = 0;
+
+        self.table = if let TaluOperation::Lut { table_addr, size_log2, .. } = new_operation {
+            self.main_memory.read_range(table_addr, 1 << size_log2.min(LUT_MAX_SIZE_LOG2))
+        } else {
+            Vec::new()
+        };
+
+        self.delay_line.clear();
+        if let TaluOperation::Delay { steps, .. } = new_operation {
+            self.delay_line.resize(steps.min(DELAY_MAX_STEPS) as usize, None);
+        }
+    }
+
+    /// Whether executing again with unchanged inputs would leave this TALU
+    /// exactly as it is: no pulse in flight, no pending delayed value and
+    /// activation low.
+    pub fn is_quiescent(&self) -> bool {
+        match self.operation {
+            TaluOperation::NoOp => true,
+            TaluOperation::Latch { .. } => false,
+            _ => {
+                self.state == TaluState::Done
+                    && !self.activation_input.read().is_some_and(Into::<bool>::into)
+                    && self.delay_line.iter().all(Option::is_none)
+            }
+        }
     }
 
     pub fn execute(&mut self) {
+        let latency = self.latencies.latency_of(&self.operation);
+        if latency > 0 {
+            match self.state.clone() {
+                TaluState::Busy { remaining } if remaining > 1 => {
+                    self.state = TaluState::Busy { remaining: remaining - 1 };
+                    if self.pulse_open {
+                        self.pulse_open = false;
+                        self.activation_output.write(false);
+                    } else {
+                        self.activation_output.clear();
+                    }
+                    return;
+                }
+                TaluState::Busy { .. } => {
+                    // An activation on the step the results are written
+                    // starts the next operation, as on any later step.
+                    let next_inputs = Into::<bool>::into(self.activation_input.read().unwrap())
+                        .then(|| self.read_inputs());
+
+                    // finished: run the operation on the inputs it was activated with
+                    let [in_0, in_1, in_2, activation] = self.latched_inputs;
+                    self.data_input_0.set_value(in_0);
+                    self.data_input_1.set_value(in_1);
+                    self.data_input_2.set_value(in_2);
+                    self.activation_input.set_value(activation);
+                    self.state = TaluState::Done;
+                    self.pulse_open = false;
+                    self.execute_operation();
+
+                    if let Some(inputs) = next_inputs {
+                        self.latched_inputs = inputs;
+                        self.state = TaluState::Busy { remaining: latency };
+                        self.pulse_open = true;
+                    }
+                    return;
+                }
+                state if self.activation_input.read().unwrap().into() => {
+                    self.latched_inputs = self.read_inputs();
+                    self.state = TaluState::Busy { remaining: latency };
+                    if state == TaluState::JustProcessed {
+                        self.activation_output.write(false);
+                    } else {
+                        self.activation_output.clear();
+                    }
+                    return;
+                }
+                _ => {}
+            }
+        }
+        self.execute_operation();
+    }
+
+    fn read_inputs(&self) -> [Option<Word>; 4] {
+        [
+            self.data_input_0.read(),
+            self.data_input_1.read(),
+            self.data_input_2.read(),
+            self.activation_input.read().map(|act| Into::<bool>::into(act).to_word()),
+        ]
+    }
+
+    fn execute_operation(&mut self) {
         let op = self.operation;
         match &op {
             TaluOperation::NoOp => {}
@@ -235,6 +372,7 @@ impl TaluCore {
                         self.activation_output.write(false);
                         self.state = TaluState::Closing;
                     }  else {
+                        self.state = TaluState::Done;
                         self.activation_output.clear();
                     }
                 }
@@ -243,14 +381,7 @@ impl TaluCore {
                 if self.activation_input.read().unwrap().into() {
                     let in_0 = self.data_input_0.read().unwrap();
                     let in_1 = self.data_input_1.read().unwrap();
-                    let res = match  op{
-                        CmpOp::LessThan => in_0 < in_1,
-                        CmpOp::LessThanOrEq => in_0 <= in_1,
-                        CmpOp::GreaterThan => in_0 > in_1,
-                        CmpOp::GreaterThanOrEq => in_0 >= in_1,
-                        CmpOp::Eq => in_0 == in_1,
-                        CmpOp::NotEq => in_0 != in_1,
-                    };
+                    let res = op.holds(in_0, in_1);
                     self.data_output_0.write(res.to_word());
                     self.activation_output.write(true);
                     self.state = TaluState::JustProcessed;
@@ -259,6 +390,7 @@ impl TaluCore {
                         self.activation_output.write(false);
                         self.state = TaluState::Closing;
                     } else {
+                        self.state = TaluState::Done;
                         self.activation_output.clear();
                     }
                 }
@@ -276,6 +408,7 @@ impl TaluCore {
                         self.state = TaluState::Closing;
                         self.activation_output.write(false);
                     } else {
+                        self.state = TaluState::Done;
                         self.activation_output.clear();
                     }
                 }
@@ -588,6 +721,235 @@ impl TaluCore {
                     }
                 }
             }
+            TaluOperation::Fixed {
+                op,
+                format,
+                overflow,
+                ..
+            } => {
+                if self.activation_input.read().unwrap().into() {
+                    let inp_0 = self.data_input_0.read().unwrap();
+                    let inp_1 = self.data_input_1.read().unwrap();
+
+                    let (res, flag) = match op {
+                        FixedOp::Mul => fixed::mul(inp_0, inp_1, *format, *overflow),
+                        FixedOp::Div => fixed::div(inp_0, inp_1, *format, *overflow),
+                        FixedOp::RoundingShift => fixed::rounding_shift(inp_0, inp_1, *overflow),
+                    };
+                    self.data_output_0.write(res);
+                    self.data_output_1.write(flag as i32);
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::Mux { .. } => {
+                if self.activation_input.read().unwrap().into() {
+                    let condition: bool = self.data_input_2.read().unwrap().to_activation().into();
+                    let res = if condition {
+                        self.data_input_0.read().unwrap()
+                    } else {
+                        self.data_input_1.read().unwrap()
+                    };
+                    self.data_output_0.write(res);
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::Counter { .. } => {
+                let reset: bool = self.data_input_1.read().unwrap().to_activation().into();
+                if reset {
+                    self.inner_memory_0 = 0;
+                    self.data_output_0.write(0);
+                }
+                if !reset && self.activation_input.read().unwrap().into() {
+                    let down: bool = self.data_input_0.read().unwrap().to_activation().into();
+                    self.inner_memory_0 = if down {
+                        self.inner_memory_0.wrapping_sub(1)
+                    } else {
+                        self.inner_memory_0.wrapping_add(1)
+                    };
+                    self.data_output_0.write(self.inner_memory_0);
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::Accumulator { .. } => {
+                let reset: bool = self.data_input_1.read().unwrap().to_activation().into();
+                if reset {
+                    self.inner_memory_0 = 0;
+                    self.data_output_0.write(0);
+                }
+                if !reset && self.activation_input.read().unwrap().into() {
+                    let inp = self.data_input_0.read().unwrap();
+
+                    let (sum, overflow) = self.inner_memory_0.overflowing_add(inp);
+                    self.inner_memory_0 = sum;
+                    self.data_output_0.write(sum);
+                    self.data_output_1.write(overflow as i32);
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::Timer { .. } => {
+                let reset: bool = self.data_input_1.read().unwrap().to_activation().into();
+                let mut elapsed = false;
+                if reset {
+                    self.inner_memory_0 = 0;
+                    self.data_output_0.write(0);
+                } else if self.activation_input.read().unwrap().into() {
+                    let period = self.data_input_0.read().unwrap().max(1);
+                    self.inner_memory_0 += 1;
+                    if self.inner_memory_0 >= period {
+                        self.inner_memory_0 = 0;
+                        elapsed = true;
+                    }
+                    self.data_output_0.write(self.inner_memory_0);
+                }
+
+                if elapsed {
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::Lut { .. } => {
+                if self.activation_input.read().unwrap().into() {
+                    let inp = self.data_input_0.read().unwrap();
+                    let ix = inp as usize & (self.table.len() - 1);
+                    self.data_output_0.write(self.table[ix]);
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::Delay { .. } => {
+                let incoming = if self.activation_input.read().unwrap().into() {
+                    Some(self.data_input_0.read().unwrap())
+                } else {
+                    None
+                };
+                self.delay_line.push_back(incoming);
+
+                if let Some(value) = self.delay_line.pop_front().flatten() {
+                    self.data_output_0.write(value);
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::EdgeDetect { edge, .. } => {
+                let current: bool = self.activation_input.read().unwrap().into();
+                let previous: bool = self.inner_memory_0.to_activation().into();
+                self.inner_memory_0 = current.to_word();
+
+                let is_edge = match edge {
+                    Edge::Rising    => current && !previous,
+                    Edge::Falling   => !current && previous,
+                    Edge::Both      => current != previous,
+                };
+                if is_edge {
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::Simd { op, lanes, signed, overflow, .. } => {
+                if self.activation_input.read().unwrap().into() {
+                    let inp_0 = self.data_input_0.read().unwrap();
+                    let inp_1 = self.data_input_1.read().unwrap_or_default();
+
+                    let res = simd::apply(*op, *lanes, *signed, *overflow, inp_0, inp_1);
+                    self.data_output_0.write(res);
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
+            TaluOperation::Float { op, .. } => {
+                if self.activation_input.read().unwrap().into() {
+                    let inp_0 = self.data_input_0.read().unwrap();
+                    let inp_1 = self.data_input_1.read().unwrap_or_default();
+
+                    self.data_output_0.write(float::apply(*op, inp_0, inp_1));
+                    self.activation_output.write(true);
+                    self.state = TaluState::JustProcessed;
+                } else {
+                    if self.state == TaluState::JustProcessed{
+                        self.state = TaluState::Closing;
+                        self.activation_output.write(false);
+                    } else {
+                        self.state = TaluState::Done;
+                        self.activation_output.clear();
+                    }
+                }
+            }
             TaluOperation::Latch {
                 ..
             } => {
@@ -610,3 +972,343 @@ impl TaluCore {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::simulation::cpu_registers::{CpuRegisterAddress, CpuRegisterBank};
+    use crate::application::simulation::talu::CmpOp;
+
+    const ACTIVATION_IN: CpuRegisterAddress = 1;
+    const DATA_IN: CpuRegisterAddress = 2;
+    const DATA_OUT: CpuRegisterAddress = 3;
+    const ACTIVATION_OUT: CpuRegisterAddress = 4;
+    const RESET_IN: CpuRegisterAddress = 5;
+    const FLAGS_OUT: CpuRegisterAddress = 6;
+    const OTHER_DATA_IN: CpuRegisterAddress = 7;
+    const CONDITION_IN: CpuRegisterAddress = 8;
+
+    /// Runs `operation` on a lone TALU, a step per activation input, the
+    /// way `Cpu::step` does with registered propagation. Gives the
+    /// activation output, as 0 or 1, and the data output after each step.
+    fn run(operation: TaluOperation, activations: &[Word], data: &[Word]) -> Vec<(Word, Word)> {
+        run_with(LatencyTable::default(), operation, activations, data)
+    }
+
+    fn run_with(latencies: LatencyTable, operation: TaluOperation, activations: &[Word], data: &[Word]) -> Vec<(Word, Word)> {
+        run_talu(latencies, operation, activations, data).1
+    }
+
+    /// `run_with`, also giving the TALU as the last step left it.
+    fn run_talu(latencies: LatencyTable, operation: TaluOperation, activations: &[Word], data: &[Word]) -> (TaluCore, Vec<(Word, Word)>) {
+        let main_memory = MainMemory::new(Vec::new());
+        let mut talu = TaluCore::new(0, &main_memory, &Arc::new(latencies));
+        talu.set_new_operation(operation);
+        let mut registers = CpuRegisterBank::new();
+        let outputs = activations.iter().enumerate().map(|(step, activation)| {
+            registers.components[DATA_IN].write(data.get(step).copied().unwrap_or_default());
+            step_with(&mut talu, &mut registers, *activation)
+        }).collect();
+        (talu, outputs)
+    }
+
+    /// Executes a step of `talu` with `activation` on its activation input,
+    /// giving its outputs as `run` does.
+    fn step_with(talu: &mut TaluCore, registers: &mut CpuRegisterBank, activation: Word) -> (Word, Word) {
+        registers.components[ACTIVATION_IN].write(activation);
+        for (_, req) in talu.collect_read_requests() {
+            req.satisfy(registers);
+        }
+        talu.execute();
+        for (_, req) in talu.collect_write_requests() {
+            req.satisfy(registers);
+        }
+        let activation: bool = registers.components[ACTIVATION_OUT].value.to_activation().into();
+        (activation as Word, registers.components[DATA_OUT].value)
+    }
+
+    fn edges(edge: Edge, activations: &[Word]) -> Vec<Word> {
+        let operation = TaluOperation::EdgeDetect {
+            edge,
+            activation_input: ACTIVATION_IN,
+            activation_output: ACTIVATION_OUT,
+        };
+        run(operation, activations, &[]).into_iter().map(|(activation, _)| activation).collect()
+    }
+
+    fn delay(steps: u8, activations: &[Word], data: &[Word]) -> Vec<(Word, Word)> {
+        let operation = TaluOperation::Delay {
+            activation_input: ACTIVATION_IN,
+            data_input: DATA_IN,
+            steps,
+            data_output_0: DATA_OUT,
+            activation_output: Some(ACTIVATION_OUT),
+        };
+        run(operation, activations, data)
+    }
+
+    const INPUT: [Word; 9] = [0, 1, 1, 1, 0, 0, 1, 0, 0];
+
+    #[test]
+    fn edge_detect_rising() {
+        assert_eq!(edges(Edge::Rising, &INPUT), [0, 1, 0, 0, 0, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn edge_detect_falling() {
+        assert_eq!(edges(Edge::Falling, &INPUT), [0, 0, 0, 0, 1, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn edge_detect_both() {
+        // Edges on consecutive steps make a pulse two steps long.
+        assert_eq!(edges(Edge::Both, &INPUT), [0, 1, 0, 0, 1, 0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn delay_repeats_activations_later() {
+        let activations = [1, 0, 0, 1, 1, 0, 0, 0];
+        let data = [10, 11, 12, 20, 30, 31, 32, 33];
+        assert_eq!(delay(2, &activations, &data), [
+            (0, 0), (0, 0), (1, 10), (0, 10), (0, 10), (1, 20), (1, 30), (0, 30),
+        ]);
+    }
+
+    #[test]
+    fn delay_of_zero_steps_passes_through() {
+        let activations = [0, 1, 0, 1];
+        let data = [5, 6, 7, 8];
+        assert_eq!(delay(0, &activations, &data), [(0, 0), (1, 6), (0, 6), (1, 8)]);
+    }
+
+    #[test]
+    fn longest_delay() {
+        let steps = DELAY_MAX_STEPS as usize;
+        let mut activations = vec![0; steps + 2];
+        activations[0] = 1;
+        let outputs = delay(DELAY_MAX_STEPS, &activations, &[7]);
+        assert_eq!(outputs[steps - 1], (0, 0));
+        assert_eq!(outputs[steps], (1, 7));
+        assert_eq!(outputs[steps + 1], (0, 7));
+    }
+
+    fn mov_with_latency(latency: Step, activations: &[Word], data: &[Word]) -> Vec<(Word, Word)> {
+        let latencies = LatencyTable { default: 0, per_operation: [("Mov".to_string(), latency)].into() };
+        let operation = TaluOperation::Mov {
+            activation_input: ACTIVATION_IN,
+            value_input: DATA_IN,
+            data_output: DATA_OUT,
+            activation_output: Some(ACTIVATION_OUT),
+        };
+        run_with(latencies, operation, activations, data)
+    }
+
+    #[test]
+    fn activation_on_the_finishing_step_starts_the_next_operation() {
+        // The activation on step 1 comes while busy and is ignored; the one
+        // on step 3 comes as the first result is written.
+        let activations = [1, 1, 0, 1, 0, 0, 0, 0];
+        let data = [10, 15, 0, 20, 0, 0, 0, 0];
+        assert_eq!(mov_with_latency(3, &activations, &data), [
+            (0, 0), (0, 0), (0, 0), (1, 10), (0, 10), (0, 10), (1, 20), (0, 20),
+        ]);
+    }
+
+    #[test]
+    fn held_activation_gives_a_result_per_latency() {
+        let activations = [1; 5];
+        let data = [1, 2, 3, 4, 5];
+        assert_eq!(mov_with_latency(1, &activations, &data), [(0, 0), (1, 1), (1, 2), (1, 3), (1, 4)]);
+        assert_eq!(mov_with_latency(2, &activations, &data), [(0, 0), (0, 0), (1, 1), (0, 1), (1, 3)]);
+    }
+
+    #[test]
+    fn settles_after_a_pulse() {
+        let operations = [
+            TaluOperation::Mov { activation_input: ACTIVATION_IN, value_input: DATA_IN, data_output: DATA_OUT, activation_output: None },
+            TaluOperation::Cmp { op: CmpOp::Eq, activation_input: ACTIVATION_IN, activation_output: None, data_input_0: DATA_IN, data_input_1: DATA_IN, data_output: DATA_OUT },
+            TaluOperation::Not { activation_input: ACTIVATION_IN, data_input: DATA_IN, data_output: DATA_OUT, activation_output: None },
+            TaluOperation::And { activation_input: ACTIVATION_IN, data_input_0: DATA_IN, data_input_1: DATA_IN, data_output_0: DATA_OUT, activation_output: None },
+        ];
+        for operation in operations {
+            let (talu, _) = run_talu(LatencyTable::default(), operation, &[1, 0, 0], &[5]);
+            assert!(talu.is_quiescent(), "{} is left {:?}", operation.kind_name(), talu.state);
+        }
+    }
+
+    fn counter() -> TaluOperation {
+        TaluOperation::Counter {
+            activation_input: ACTIVATION_IN,
+            down_input: DATA_IN,
+            reset_input: RESET_IN,
+            data_output_0: DATA_OUT,
+            activation_output: Some(ACTIVATION_OUT),
+        }
+    }
+
+    fn accumulator() -> TaluOperation {
+        TaluOperation::Accumulator {
+            activation_input: ACTIVATION_IN,
+            data_input: DATA_IN,
+            reset_input: RESET_IN,
+            data_output_0: DATA_OUT,
+            flags_output: Some(FLAGS_OUT),
+            activation_output: Some(ACTIVATION_OUT),
+        }
+    }
+
+    fn timer() -> TaluOperation {
+        TaluOperation::Timer {
+            activation_input: ACTIVATION_IN,
+            period_input: DATA_IN,
+            reset_input: RESET_IN,
+            count_output: Some(DATA_OUT),
+            activation_output: ACTIVATION_OUT,
+        }
+    }
+
+    /// A TALU configured with `operation`, and the registers it runs on.
+    fn configured(operation: TaluOperation) -> (TaluCore, CpuRegisterBank) {
+        configured_on(&MainMemory::new(Vec::new()), operation)
+    }
+
+    fn configured_on(main_memory: &MainMemory, operation: TaluOperation) -> (TaluCore, CpuRegisterBank) {
+        let mut talu = TaluCore::new(0, main_memory, &Arc::new(LatencyTable::default()));
+        talu.set_new_operation(operation);
+        (talu, CpuRegisterBank::new())
+    }
+
+    #[test]
+    fn counter_counts_up_and_down() {
+        let activations = [1, 1, 1, 0, 1, 1, 1];
+        let down = [0, 0, 0, 0, 1, 1, 1];
+        assert_eq!(run(counter(), &activations, &down), [
+            (1, 1), (1, 2), (1, 3), (0, 3), (1, 2), (1, 1), (1, 0),
+        ]);
+        assert_eq!(run(counter(), &[1, 1], &[1, 1]), [(1, -1), (1, -2)]);
+    }
+
+    #[test]
+    fn counter_reset_wins_over_activation() {
+        let (mut talu, mut registers) = configured(counter());
+        assert_eq!(step_with(&mut talu, &mut registers, 1), (1, 1));
+        assert_eq!(step_with(&mut talu, &mut registers, 1), (1, 2));
+        registers.components[RESET_IN].write(1);
+        assert_eq!(step_with(&mut talu, &mut registers, 1), (0, 0));
+        registers.components[RESET_IN].write(0);
+        assert_eq!(step_with(&mut talu, &mut registers, 1), (1, 1));
+    }
+
+    #[test]
+    fn accumulator_sums_and_flags_overflow() {
+        let (mut talu, mut registers) = configured(accumulator());
+        let mut outputs = Vec::new();
+        for (activation, data) in [(1, 5), (1, 7), (0, 100), (1, -2), (1, Word::MAX)] {
+            registers.components[DATA_IN].write(data);
+            let (activation, sum) = step_with(&mut talu, &mut registers, activation);
+            outputs.push((activation, sum, registers.components[FLAGS_OUT].value));
+        }
+        assert_eq!(outputs, [
+            (1, 5, 0), (1, 12, 0), (0, 12, 0), (1, 10, 0), (1, Word::MIN + 9, 1),
+        ]);
+    }
+
+    #[test]
+    fn timer_fires_every_period_activations() {
+        let activations = [1, 1, 0, 1, 1, 1, 1, 1, 1];
+        let period = [3; 9];
+        assert_eq!(run(timer(), &activations, &period), [
+            (0, 1), (0, 2), (0, 2), (1, 0), (0, 1), (0, 2), (1, 0), (0, 1), (0, 2),
+        ]);
+        // A period below 1 fires on every activation.
+        assert_eq!(run(timer(), &[1, 1, 1], &[0, 0, 0]), [(1, 0), (1, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn reconfiguring_starts_over() {
+        for operation in [counter(), accumulator(), timer()] {
+            let (mut talu, mut registers) = configured(operation);
+            registers.components[DATA_IN].write(3);
+            step_with(&mut talu, &mut registers, 1);
+            let before = step_with(&mut talu, &mut registers, 1);
+            talu.set_new_operation(operation);
+            step_with(&mut talu, &mut registers, 0);
+            let after = [step_with(&mut talu, &mut registers, 1), step_with(&mut talu, &mut registers, 1)];
+            assert_eq!(after[1], before, "{}", operation.kind_name());
+        }
+    }
+
+    #[test]
+    fn mux_selects_by_its_condition() {
+        let mux = TaluOperation::Mux {
+            activation_input: ACTIVATION_IN,
+            condition: CONDITION_IN,
+            if_true: DATA_IN,
+            if_false: OTHER_DATA_IN,
+            data_output_0: DATA_OUT,
+            activation_output: Some(ACTIVATION_OUT),
+        };
+        let (mut talu, mut registers) = configured(mux);
+        registers.components[DATA_IN].write(10);
+        registers.components[OTHER_DATA_IN].write(20);
+        let mut outputs = Vec::new();
+        for (activation, condition) in [(1, 1), (1, 0), (1, -5), (0, 0), (1, 0)] {
+            registers.components[CONDITION_IN].write(condition);
+            outputs.push(step_with(&mut talu, &mut registers, activation));
+        }
+        assert_eq!(outputs, [(1, 10), (1, 20), (1, 10), (0, 10), (1, 20)]);
+    }
+
+    fn lut(table_addr: usize, size_log2: u8) -> TaluOperation {
+        TaluOperation::Lut {
+            activation_input: ACTIVATION_IN,
+            data_input: DATA_IN,
+            table_addr,
+            size_log2,
+            data_output_0: DATA_OUT,
+            activation_output: Some(ACTIVATION_OUT),
+        }
+    }
+
+    /// Looks each of `inputs` up in `talu`, a step each.
+    fn look_up(talu: &mut TaluCore, registers: &mut CpuRegisterBank, inputs: &[Word]) -> Vec<Word> {
+        inputs.iter().map(|input| {
+            registers.components[DATA_IN].write(*input);
+            step_with(talu, registers, 1).1
+        }).collect()
+    }
+
+    #[test]
+    fn lut_masks_inputs_to_its_size() {
+        let main_memory = MainMemory::new(vec![99, 10, 11, 12, 13, 99]);
+        let (mut talu, mut registers) = configured_on(&main_memory, lut(1, 2));
+        assert_eq!(look_up(&mut talu, &mut registers, &[0, 3, 4, 6, -1, Word::MIN + 1]), [10, 13, 10, 12, 13, 11]);
+    }
+
+    #[test]
+    fn lut_keeps_the_table_it_was_configured_with() {
+        let main_memory = MainMemory::new(vec![10, 11]);
+        let (mut talu, mut registers) = configured_on(&main_memory, lut(0, 1));
+        main_memory.get_io().write(1, 21);
+        assert_eq!(look_up(&mut talu, &mut registers, &[0, 1]), [10, 11]);
+        talu.set_new_operation(lut(0, 1));
+        assert_eq!(look_up(&mut talu, &mut registers, &[0, 1]), [10, 21]);
+    }
+
+    #[test]
+    fn lut_reads_past_the_end_of_memory_as_zero() {
+        let main_memory = MainMemory::new(vec![1, 2, 3]);
+        let (mut talu, mut registers) = configured_on(&main_memory, lut(2, 2));
+        assert_eq!(look_up(&mut talu, &mut registers, &[0, 1, 2, 3]), [3, 0, 0, 0]);
+        let (mut talu, mut registers) = configured_on(&main_memory, lut(usize::MAX, 1));
+        assert_eq!(look_up(&mut talu, &mut registers, &[0, 1]), [0, 0]);
+    }
+
+    #[test]
+    fn lut_tables_are_at_most_the_largest_size() {
+        let main_memory = MainMemory::new((0..1024).collect());
+        let (mut talu, mut registers) = configured_on(&main_memory, lut(0, 10));
+        assert_eq!(talu.table.len(), 1 << LUT_MAX_SIZE_LOG2);
+        assert_eq!(look_up(&mut talu, &mut registers, &[255, 256, 257]), [255, 0, 1]);
+    }
+}
@@ -0,0 +1,185 @@
+//! Fixed-point arithmetic. Every result is rounded to nearest with ties
+//! going up, towards positive infinity, as adding half before an
+//! arithmetic shift does.
+
+use crate::application::simulation::talu::{OverflowMode, QFormat};
+use crate::word::Word;
+
+/// Narrows a wide intermediate result back into a word, returning whether it
+/// had to be saturated or wrapped to fit.
+pub fn narrow(wide: i64, overflow: OverflowMode) -> (Word, bool) {
+    if let Ok(res) = Word::try_from(wide) {
+        return (res, false);
+    }
+    let res = match overflow {
+        OverflowMode::Wrap      => wide as Word,
+        OverflowMode::Saturate  => if wide < 0 { Word::MIN } else { Word::MAX },
+    };
+    (res, true)
+}
+
+/// Arithmetic right shift that rounds to nearest, with ties going up.
+pub fn rounding_shr(wide: i64, count: u32) -> i64 {
+    match count {
+        0       => wide,
+        1..=62  => (wide + (1 << (count - 1))) >> count,
+        _       => 0,
+    }
+}
+
+pub fn mul(a: Word, b: Word, format: QFormat, overflow: OverflowMode) -> (Word, bool) {
+    let wide = a as i64 * b as i64;
+    narrow(rounding_shr(wide, format.frac_bits()), overflow)
+}
+
+/// Divides with the quotient rounded to nearest, ties going up. Dividing by
+/// zero raises the flag and yields `0` when wrapping, or the extreme matching
+/// the dividend's sign when saturating.
+pub fn div(a: Word, b: Word, format: QFormat, overflow: OverflowMode) -> (Word, bool) {
+    if b == 0 {
+        let res = match overflow {
+            OverflowMode::Wrap      => 0,
+            OverflowMode::Saturate  => if a < 0 { Word::MIN } else { Word::MAX },
+        };
+        return (res, true);
+    }
+    let mut dividend = (a as i64) << format.frac_bits();
+    let mut divisor = b as i64;
+    if divisor < 0 {
+        (dividend, divisor) = (-dividend, -divisor);
+    }
+
+    // Rounded up from exactly half way, as the quotient is floored.
+    let mut quotient = dividend.div_euclid(divisor);
+    if 2 * dividend.rem_euclid(divisor) >= divisor {
+        quotient += 1;
+    }
+    narrow(quotient, overflow)
+}
+
+/// Shifts right by `count` with rounding, or left by `-count`. Bits lost on a
+/// left shift are handled according to `overflow`.
+pub fn rounding_shift(value: Word, count: Word, overflow: OverflowMode) -> (Word, bool) {
+    if count >= 0 {
+        narrow(rounding_shr(value as i64, count as u32), overflow)
+    } else if value == 0 {
+        (0, false)
+    } else {
+        let count = count.unsigned_abs();
+        if count > 32 {
+            let res = match overflow {
+                OverflowMode::Wrap      => 0,
+                OverflowMode::Saturate  => if value < 0 { Word::MIN } else { Word::MAX },
+            };
+            (res, true)
+        } else {
+            narrow((value as i64) << count, overflow)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OVERFLOWS: [OverflowMode; 2] = [OverflowMode::Wrap, OverflowMode::Saturate];
+
+    /// Words around every edge, and some in between.
+    fn words() -> Vec<Word> {
+        let mut words = vec![0, 1, -1, 2, -2, 3, -3, 7, -7, 0x8000, -0x8000, 0xffff, -0x10000];
+        words.extend([Word::MIN, Word::MIN + 1, Word::MAX, Word::MAX - 1, Word::MAX / 2, Word::MIN / 2]);
+        let mut seed = 0x2545_f491_u32;
+        for _ in 0..24 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            words.push(seed as Word);
+            words.push((seed >> (seed % 24)) as Word);
+        }
+        words
+    }
+
+    /// `n / d` rounded to nearest with ties going up, for a positive `d`.
+    fn reference_round(n: i128, d: i128) -> i128 {
+        let (quotient, remainder) = (n.div_euclid(d), n.rem_euclid(d));
+        if 2 * remainder >= d { quotient + 1 } else { quotient }
+    }
+
+    fn reference_narrow(value: i128, overflow: OverflowMode) -> (Word, bool) {
+        match Word::try_from(value) {
+            Ok(value) => (value, false),
+            Err(_) => match overflow {
+                OverflowMode::Wrap => (value as Word, true),
+                OverflowMode::Saturate => (if value < 0 { Word::MIN } else { Word::MAX }, true),
+            },
+        }
+    }
+
+    #[test]
+    fn ties_go_up() {
+        assert_eq!(rounding_shr(5, 1), 3);
+        assert_eq!(rounding_shr(-5, 1), -2);
+        assert_eq!(rounding_shr(-6, 2), -1);
+        let q1 = QFormat { frac_bits: 1 };
+        assert_eq!(div(5, 2, QFormat { frac_bits: 0 }, OverflowMode::Wrap), (3, false));
+        assert_eq!(div(-5, 2, QFormat { frac_bits: 0 }, OverflowMode::Wrap), (-2, false));
+        assert_eq!(div(5, -2, QFormat { frac_bits: 0 }, OverflowMode::Wrap), (-2, false));
+        assert_eq!(mul(-5, 1, q1, OverflowMode::Wrap), (-2, false));
+        assert_eq!(rounding_shift(-5, 1, OverflowMode::Wrap), (-2, false));
+    }
+
+    #[test]
+    fn mul_matches_reference() {
+        for frac_bits in [0, 1, 8, 15, 16, 30, 31] {
+            let format = QFormat { frac_bits };
+            for a in words() {
+                for b in words() {
+                    for overflow in OVERFLOWS {
+                        let expected = reference_narrow(reference_round(a as i128 * b as i128, 1 << frac_bits), overflow);
+                        assert_eq!(mul(a, b, format, overflow), expected, "{a} * {b} in Q.{frac_bits}, {overflow:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn div_matches_reference() {
+        for frac_bits in [0, 1, 8, 15, 16, 30, 31] {
+            let format = QFormat { frac_bits };
+            for a in words() {
+                for b in words().into_iter().filter(|b| *b != 0) {
+                    let (n, d) = ((a as i128) << frac_bits, b as i128);
+                    let rounded = if d < 0 { reference_round(-n, -d) } else { reference_round(n, d) };
+                    for overflow in OVERFLOWS {
+                        let expected = reference_narrow(rounded, overflow);
+                        assert_eq!(div(a, b, format, overflow), expected, "{a} / {b} in Q.{frac_bits}, {overflow:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn div_by_zero() {
+        let format = QFormat { frac_bits: 16 };
+        assert_eq!(div(5, 0, format, OverflowMode::Wrap), (0, true));
+        assert_eq!(div(5, 0, format, OverflowMode::Saturate), (Word::MAX, true));
+        assert_eq!(div(-5, 0, format, OverflowMode::Saturate), (Word::MIN, true));
+    }
+
+    #[test]
+    fn rounding_shift_matches_reference() {
+        for value in words() {
+            for count in -90..=100 {
+                for overflow in OVERFLOWS {
+                    let expected = match count {
+                        0.. => reference_narrow(reference_round(value as i128, 1 << count), overflow),
+                        _ => reference_narrow((value as i128) << -count, overflow),
+                    };
+                    assert_eq!(rounding_shift(value, count, overflow), expected, "{value} >> {count}, {overflow:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::application::simulation::talu::FloatOp;
+use crate::word::{ToWord, Word};
+
+pub fn from_word(word: Word) -> f32 {
+    f32::from_bits(word as u32)
+}
+
+pub fn to_word(value: f32) -> Word {
+    value.to_bits() as Word
+}
+
+/// Applies `op` to words holding IEEE-754 bit patterns. Comparisons yield the
+/// usual all-ones/zero masks, and `ToInt` saturates, with NaN becoming `0`.
+pub fn apply(op: FloatOp, a: Word, b: Word) -> Word {
+    let (x, y) = (from_word(a), from_word(b));
+    match op {
+        FloatOp::Add        => to_word(x + y),
+        FloatOp::Sub        => to_word(x - y),
+        FloatOp::Mul        => to_word(x * y),
+        FloatOp::Div        => to_word(x / y),
+        FloatOp::Cmp(cmp)   => cmp.holds(x, y).to_word(),
+        FloatOp::FromInt    => to_word(a as f32),
+        FloatOp::ToInt      => x as Word,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::simulation::talu::CmpOp;
+
+    fn float(op: FloatOp, x: f32, y: f32) -> f32 {
+        from_word(apply(op, to_word(x), to_word(y)))
+    }
+
+    fn to_int(x: f32) -> Word {
+        apply(FloatOp::ToInt, to_word(x), 0)
+    }
+
+    #[test]
+    fn arithmetic_works_on_bit_patterns() {
+        assert_eq!(float(FloatOp::Add, 1.5, 2.25), 3.75);
+        assert_eq!(float(FloatOp::Sub, 1.5, 2.25), -0.75);
+        assert_eq!(float(FloatOp::Mul, 1.5, -4.0), -6.0);
+        assert_eq!(float(FloatOp::Div, 1.0, 0.0), f32::INFINITY);
+        assert!(float(FloatOp::Div, 0.0, 0.0).is_nan());
+    }
+
+    #[test]
+    fn to_int_truncates_and_saturates() {
+        assert_eq!(to_int(2.9), 2);
+        assert_eq!(to_int(-2.9), -2);
+        assert_eq!(to_int(3e9), Word::MAX);
+        assert_eq!(to_int(-3e9), Word::MIN);
+        assert_eq!(to_int(f32::INFINITY), Word::MAX);
+        assert_eq!(to_int(f32::NEG_INFINITY), Word::MIN);
+        assert_eq!(to_int(f32::NAN), 0);
+    }
+
+    #[test]
+    fn from_int_rounds_to_the_nearest_float() {
+        assert_eq!(from_word(apply(FloatOp::FromInt, -7, 0)), -7.0);
+        assert_eq!(from_word(apply(FloatOp::FromInt, Word::MAX, 0)), 2147483648.0);
+        assert_eq!(from_word(apply(FloatOp::FromInt, 16_777_217, 0)), 16_777_216.0);
+    }
+
+    #[test]
+    fn comparisons_give_masks() {
+        let cmp = |op, x: f32, y: f32| apply(FloatOp::Cmp(op), to_word(x), to_word(y));
+        assert_eq!(cmp(CmpOp::LessThan, -1.0, 0.5), !0);
+        assert_eq!(cmp(CmpOp::LessThan, 0.5, -1.0), 0);
+        assert_eq!(cmp(CmpOp::GreaterThanOrEq, 0.5, 0.5), !0);
+        // Zeroes are equal whatever their sign, unlike their bit patterns.
+        assert_eq!(cmp(CmpOp::Eq, 0.0, -0.0), !0);
+        // NaN is unordered: only `NotEq` holds.
+        assert_eq!(cmp(CmpOp::Eq, f32::NAN, f32::NAN), 0);
+        assert_eq!(cmp(CmpOp::LessThanOrEq, f32::NAN, 1.0), 0);
+        assert_eq!(cmp(CmpOp::GreaterThan, f32::NAN, 1.0), 0);
+        assert_eq!(cmp(CmpOp::NotEq, f32::NAN, f32::NAN), !0);
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::BTreeMap;
+use crate::application::simulation::talu::TaluOperation;
+use crate::Step;
+
+/// How many steps each operation keeps its TALU busy before its results are
+/// written, keyed by operation name (e.g. `"Div"`, see
+/// `TaluOperation::KIND_NAMES`). A latency of `0` finishes in the step the
+/// TALU is activated. Activations while busy are ignored, but one on the step
+/// the results are written starts the next operation.
+#[derive(Clone, PartialEq, Eq, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(default)]
+pub struct LatencyTable {
+    pub default         : Step,
+    pub per_operation   : BTreeMap<String, Step>,
+}
+
+impl LatencyTable {
+    /// Names in `per_operation` that aren't an operation's, and so never
+    /// apply.
+    pub fn unknown_operations(&self) -> impl Iterator<Item = &str> {
+        self.per_operation.keys()
+            .map(String::as_str)
+            .filter(|name| !TaluOperation::KIND_NAMES.contains(name))
+    }
+
+    /// `NoOp`, `Delay` and `EdgeDetect` define their own timing and are
+    /// never delayed.
+    pub fn latency_of(&self, operation: &TaluOperation) -> Step {
+        match operation {
+            TaluOperation::NoOp
+            | TaluOperation::Delay { .. }
+            | TaluOperation::EdgeDetect { .. } => 0,
+            _ => self.per_operation
+                .get(operation.kind_name())
+                .copied()
+                .unwrap_or(self.default),
+        }
+    }
+}
@@ -1,9 +1,15 @@
 pub mod core;
 pub mod op;
+pub mod fixed;
+pub mod float;
+pub mod simd;
+pub mod latency;
 
 use std::array;
+use std::sync::Arc;
 pub use core::*;
 pub use op::*;
+pub use latency::*;
 use crate::{Step };
 use crate::application::simulation::component_bank::ComponentBank;
 use crate::application::simulation::cpu_registers::CpuRegisterBank;
@@ -15,12 +21,14 @@ pub const TALU_COUNT: usize = 32;
 impl TaluBank {
     pub fn new(
         main_memory: &mut MainMemory,
+        latencies: &Arc<LatencyTable>,
     ) -> Self{
         Self{
             components: Box::new(array::from_fn(|i|
                 TaluCore::new(
                     i,
                     main_memory,
+                    latencies,
                 )
             ))
         }
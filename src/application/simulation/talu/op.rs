@@ -5,7 +5,7 @@ pub enum MovInput {
     Source(CpuRegisterAddress),
     // SourceAddr(CpuRegisterAddress),
 }
-#[derive(Clone, PartialEq, Eq, Debug, Copy,serde::Deserialize, serde::Serialize )]
+#[derive(Clone, PartialEq, Eq, Debug, Copy,serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 pub enum CmpOp{
     LessThan,
     LessThanOrEq,
@@ -15,7 +15,109 @@ pub enum CmpOp{
     NotEq
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize)]
+impl CmpOp {
+    pub fn holds<T: PartialOrd>(&self, lhs: T, rhs: T) -> bool {
+        match self {
+            CmpOp::LessThan => lhs < rhs,
+            CmpOp::LessThanOrEq => lhs <= rhs,
+            CmpOp::GreaterThan => lhs > rhs,
+            CmpOp::GreaterThanOrEq => lhs >= rhs,
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::NotEq => lhs != rhs,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub enum FixedOp{
+    Mul,
+    Div,
+    /// Shifts `data_input_0` right by `data_input_1` with rounding, or left
+    /// when the count is negative.
+    RoundingShift,
+}
+
+/// Fixed-point format of a word, given by its number of fractional bits:
+/// `frac_bits: 16` is Q16.16 and `frac_bits: 31` is Q1.31.
+#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct QFormat{
+    pub frac_bits: u8,
+}
+
+impl QFormat{
+    /// Most fractional bits a word holds, as Q1.31.
+    pub const MAX_FRAC_BITS: u8 = 31;
+
+    /// `frac_bits`, clamped to `MAX_FRAC_BITS`. More is a parameter error,
+    /// see `TaluOperation::parameter_error`.
+    pub fn frac_bits(&self) -> u32{
+        self.frac_bits.min(Self::MAX_FRAC_BITS) as u32
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub enum FloatOp{
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Cmp(CmpOp),
+    /// Converts the integer in `data_input_0`; `data_input_1` is unused.
+    FromInt,
+    /// Truncates the float in `data_input_0`; `data_input_1` is unused.
+    ToInt,
+}
+
+impl FloatOp{
+    pub fn is_unary(&self) -> bool{
+        matches!(self, FloatOp::FromInt | FloatOp::ToInt)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub enum SimdOp{
+    Add,
+    Sub,
+    /// Sets every bit of each lane where the comparison holds.
+    Cmp(CmpOp),
+    /// Sums the lanes of `data_input_0`; `data_input_1` is unused.
+    HorizontalSum,
+}
+
+impl SimdOp{
+    pub fn is_unary(&self) -> bool{
+        matches!(self, SimdOp::HorizontalSum)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub enum LaneWidth{
+    /// Four 8-bit lanes.
+    Bits8,
+    /// Two 16-bit lanes.
+    Bits16,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub enum Edge{
+    Rising,
+    Falling,
+    Both,
+}
+
+/// What to do with results that don't fit in a word.
+#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub enum OverflowMode{
+    Wrap,
+    Saturate,
+}
+
+/// Largest lookup table a TALU holds, as a power of two.
+pub const LUT_MAX_SIZE_LOG2: u8 = 8;
+/// Longest delay line a TALU holds, in steps.
+pub const DELAY_MAX_STEPS: u8 = 32;
+
+#[derive(Clone, PartialEq, Eq, Debug, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 
 pub enum TaluOperation {
     NoOp,
@@ -147,22 +249,178 @@ pub enum TaluOperation {
         activation_input        : CpuRegisterAddress,
         activation_output       : Option<CpuRegisterAddress>,
     },
+    Fixed {
+        op                  : FixedOp,
+        format              : QFormat,
+        overflow            : OverflowMode,
+        activation_input    : CpuRegisterAddress,
+        data_input_0        : CpuRegisterAddress,
+        data_input_1        : CpuRegisterAddress,
+        data_output_0       : CpuRegisterAddress,
+        flags_output        : Option<CpuRegisterAddress>,
+        activation_output   : Option<CpuRegisterAddress>,
+    },
+    Mux {
+        activation_input    : CpuRegisterAddress,
+        condition           : CpuRegisterAddress,
+        if_true             : CpuRegisterAddress,
+        if_false            : CpuRegisterAddress,
+        data_output_0       : CpuRegisterAddress,
+        activation_output   : Option<CpuRegisterAddress>,
+    },
+    /// Counts every step its activation is high, downwards while
+    /// `down_input` is non-zero. A non-zero `reset_input` zeroes the count.
+    Counter {
+        activation_input    : CpuRegisterAddress,
+        down_input          : CpuRegisterAddress,
+        reset_input         : CpuRegisterAddress,
+        data_output_0       : CpuRegisterAddress,
+        activation_output   : Option<CpuRegisterAddress>,
+    },
+    /// Adds `data_input` to its running sum every step its activation is high.
+    Accumulator {
+        activation_input    : CpuRegisterAddress,
+        data_input          : CpuRegisterAddress,
+        reset_input         : CpuRegisterAddress,
+        data_output_0       : CpuRegisterAddress,
+        flags_output        : Option<CpuRegisterAddress>,
+        activation_output   : Option<CpuRegisterAddress>,
+    },
+    /// Pulses `activation_output` once every `period_input` activations.
+    Timer {
+        activation_input    : CpuRegisterAddress,
+        period_input        : CpuRegisterAddress,
+        reset_input         : CpuRegisterAddress,
+        count_output        : Option<CpuRegisterAddress>,
+        activation_output   : CpuRegisterAddress,
+    },
+    /// Maps `data_input`, masked to the table size, through a table of
+    /// `1 << size_log2` words copied from main memory at `table_addr` when
+    /// the TALU is configured, with `size_log2` at most `LUT_MAX_SIZE_LOG2`.
+    Lut {
+        activation_input    : CpuRegisterAddress,
+        data_input          : CpuRegisterAddress,
+        table_addr          : usize,
+        size_log2           : u8,
+        data_output_0       : CpuRegisterAddress,
+        activation_output   : Option<CpuRegisterAddress>,
+    },
+    /// Repeats each activation of its input, and the data that came with
+    /// it, `steps` steps later, at most `DELAY_MAX_STEPS`.
+    Delay {
+        activation_input    : CpuRegisterAddress,
+        data_input          : CpuRegisterAddress,
+        steps               : u8,
+        data_output_0       : CpuRegisterAddress,
+        activation_output   : Option<CpuRegisterAddress>,
+    },
+    /// Pulses `activation_output` for one step when its activation input
+    /// changes in the direction given by `edge`.
+    EdgeDetect {
+        edge                : Edge,
+        activation_input    : CpuRegisterAddress,
+        activation_output   : CpuRegisterAddress,
+    },
+    /// Treats words as packed lanes, read as signed or unsigned integers.
+    Simd {
+        op                  : SimdOp,
+        lanes               : LaneWidth,
+        signed              : bool,
+        overflow            : OverflowMode,
+        activation_input    : CpuRegisterAddress,
+        data_input_0        : CpuRegisterAddress,
+        data_input_1        : CpuRegisterAddress,
+        data_output_0       : CpuRegisterAddress,
+        activation_output   : Option<CpuRegisterAddress>,
+    },
+    Float {
+        op                  : FloatOp,
+        activation_input    : CpuRegisterAddress,
+        data_input_0        : CpuRegisterAddress,
+        data_input_1        : CpuRegisterAddress,
+        data_output_0       : CpuRegisterAddress,
+        activation_output   : Option<CpuRegisterAddress>,
+    },
 }
 pub struct TaluPortsConfig {
     pub data_input_0    : Option<CpuRegisterAddress>,
     pub data_input_1    : Option<CpuRegisterAddress>,
+    pub data_input_2    : Option<CpuRegisterAddress>,
     pub activation_input: Option<CpuRegisterAddress>,
     pub data_output_0    : Option<CpuRegisterAddress>,
     pub data_output_1    : Option<CpuRegisterAddress>,
     pub activation_output: Option<CpuRegisterAddress>,
 }
 impl TaluOperation {
+    /// Every `kind_name`, in declaration order.
+    pub const KIND_NAMES: [&'static str; 29] = [
+        "NoOp", "Cmp", "Mov", "Latch", "Not", "And", "Or", "Xor", "ShiftLeft", "ShiftRight",
+        "SelectPart", "Add", "Sub", "Mul", "Div", "Rem", "Neg", "ReadFromMem", "WriteToMem", "Fixed",
+        "Mux", "Counter", "Accumulator", "Timer", "Lut", "Delay", "EdgeDetect", "Simd", "Float",
+    ];
+
+    /// The variant name, as used in serialized programs.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            TaluOperation::NoOp => "NoOp",
+            TaluOperation::Cmp { .. } => "Cmp",
+            TaluOperation::Mov { .. } => "Mov",
+            TaluOperation::Latch { .. } => "Latch",
+            TaluOperation::Not { .. } => "Not",
+            TaluOperation::And { .. } => "And",
+            TaluOperation::Or { .. } => "Or",
+            TaluOperation::Xor { .. } => "Xor",
+            TaluOperation::ShiftLeft { .. } => "ShiftLeft",
+            TaluOperation::ShiftRight { .. } => "ShiftRight",
+            TaluOperation::SelectPart { .. } => "SelectPart",
+            TaluOperation::Add { .. } => "Add",
+            TaluOperation::Sub { .. } => "Sub",
+            TaluOperation::Mul { .. } => "Mul",
+            TaluOperation::Div { .. } => "Div",
+            TaluOperation::Rem { .. } => "Rem",
+            TaluOperation::Neg { .. } => "Neg",
+            TaluOperation::ReadFromMem { .. } => "ReadFromMem",
+            TaluOperation::WriteToMem { .. } => "WriteToMem",
+            TaluOperation::Fixed { .. } => "Fixed",
+            TaluOperation::Mux { .. } => "Mux",
+            TaluOperation::Counter { .. } => "Counter",
+            TaluOperation::Accumulator { .. } => "Accumulator",
+            TaluOperation::Timer { .. } => "Timer",
+            TaluOperation::Lut { .. } => "Lut",
+            TaluOperation::Delay { .. } => "Delay",
+            TaluOperation::EdgeDetect { .. } => "EdgeDetect",
+            TaluOperation::Simd { .. } => "Simd",
+            TaluOperation::Float { .. } => "Float",
+        }
+    }
+
+    /// Why a parameter of the operation is out of what a TALU supports,
+    /// which the TALU would clamp rather than run as written. `check`
+    /// reports it, and loading such a configuration halts the controller.
+    pub fn parameter_error(&self) -> Option<String> {
+        match *self {
+            TaluOperation::Fixed { format, .. } if format.frac_bits > QFormat::MAX_FRAC_BITS => Some(format!(
+                "{} fractional bits, more than the {} a word holds",
+                format.frac_bits,
+                QFormat::MAX_FRAC_BITS,
+            )),
+            TaluOperation::Lut { size_log2, .. } if size_log2 > LUT_MAX_SIZE_LOG2 => Some(format!(
+                "a table of 2^{size_log2} words, more than the 2^{LUT_MAX_SIZE_LOG2} a TALU holds",
+            )),
+            TaluOperation::Delay { steps, .. } if steps > DELAY_MAX_STEPS => Some(format!(
+                "a delay of {steps} steps, more than the {DELAY_MAX_STEPS} a TALU holds",
+            )),
+            _ => None,
+        }
+    }
+
     pub fn get_ports_config(&self) -> TaluPortsConfig {
         match self.clone() {
             Self::Mov { activation_input, value_input, data_output, activation_output } => {
                 TaluPortsConfig{
                     data_input_0: Some(value_input),
                     data_input_1: None,
+                    data_input_2: None,
                     activation_input: Some(activation_input),
                     data_output_0: Some(data_output),
                     activation_output: activation_output,
@@ -172,6 +430,7 @@ impl TaluOperation {
             TaluOperation::NoOp => TaluPortsConfig {
                 data_input_0: None,
                 data_input_1: None,
+                data_input_2: None,
                 activation_input: None,
                 data_output_0: None,
                 data_output_1: None,
@@ -188,6 +447,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -216,6 +476,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input),
                 data_input_1: Some(hold_input),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output),
                 data_output_1: None,
@@ -229,6 +490,7 @@ impl TaluOperation {
             } => TaluPortsConfig{
                 data_input_0: Some(data_input_0),
                 data_input_1: None,
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -243,6 +505,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -257,6 +520,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1        : Some(data_input_1),
+                data_input_2        : None,
                 activation_input    : Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -271,6 +535,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -285,6 +550,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(value),
                 data_input_1: Some(shift_count),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -299,6 +565,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(value),
                 data_input_1: Some(shift_count),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -313,6 +580,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -328,6 +596,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: flags_output,
@@ -343,6 +612,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: flags_output,
@@ -358,6 +628,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: second_word_output,
@@ -373,6 +644,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: div_by_zero_output,
@@ -388,6 +660,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: Some(data_input_1),
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: div_by_zero_output,
@@ -401,6 +674,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input_0),
                 data_input_1: None,
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -414,6 +688,7 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(addr_input),
                 data_input_1: None,
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: Some(data_output_0),
                 data_output_1: None,
@@ -427,9 +702,165 @@ impl TaluOperation {
             } => TaluPortsConfig {
                 data_input_0: Some(data_input),
                 data_input_1: Some(address_input),
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: None,
+                data_output_1: None,
+                activation_output,
+            },
+            TaluOperation::Fixed {
+                activation_input,
+                data_input_0,
+                data_input_1,
+                data_output_0,
+                flags_output,
+                activation_output,
+                ..
+            } => TaluPortsConfig {
+                data_input_0: Some(data_input_0),
+                data_input_1: Some(data_input_1),
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: Some(data_output_0),
+                data_output_1: flags_output,
+                activation_output,
+            },
+            TaluOperation::Mux {
+                activation_input,
+                condition,
+                if_true,
+                if_false,
+                data_output_0,
+                activation_output,
+            } => TaluPortsConfig {
+                data_input_0: Some(if_true),
+                data_input_1: Some(if_false),
+                data_input_2: Some(condition),
+                activation_input: Some(activation_input),
+                data_output_0: Some(data_output_0),
+                data_output_1: None,
+                activation_output,
+            },
+            TaluOperation::Counter {
+                activation_input,
+                down_input,
+                reset_input,
+                data_output_0,
+                activation_output,
+            } => TaluPortsConfig {
+                data_input_0: Some(down_input),
+                data_input_1: Some(reset_input),
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: Some(data_output_0),
+                data_output_1: None,
+                activation_output,
+            },
+            TaluOperation::Accumulator {
+                activation_input,
+                data_input,
+                reset_input,
+                data_output_0,
+                flags_output,
+                activation_output,
+            } => TaluPortsConfig {
+                data_input_0: Some(data_input),
+                data_input_1: Some(reset_input),
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: Some(data_output_0),
+                data_output_1: flags_output,
+                activation_output,
+            },
+            TaluOperation::Timer {
+                activation_input,
+                period_input,
+                reset_input,
+                count_output,
+                activation_output,
+            } => TaluPortsConfig {
+                data_input_0: Some(period_input),
+                data_input_1: Some(reset_input),
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: count_output,
+                data_output_1: None,
+                activation_output: Some(activation_output),
+            },
+            TaluOperation::Lut {
+                activation_input,
+                data_input,
+                data_output_0,
+                activation_output,
+                ..
+            } => TaluPortsConfig {
+                data_input_0: Some(data_input),
+                data_input_1: None,
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: Some(data_output_0),
+                data_output_1: None,
+                activation_output,
+            },
+            TaluOperation::Delay {
+                activation_input,
+                data_input,
+                data_output_0,
+                activation_output,
+                ..
+            } => TaluPortsConfig {
+                data_input_0: Some(data_input),
+                data_input_1: None,
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: Some(data_output_0),
+                data_output_1: None,
+                activation_output,
+            },
+            TaluOperation::EdgeDetect {
+                activation_input,
+                activation_output,
+                ..
+            } => TaluPortsConfig {
+                data_input_0: None,
+                data_input_1: None,
+                data_input_2: None,
                 activation_input: Some(activation_input),
                 data_output_0: None,
                 data_output_1: None,
+                activation_output: Some(activation_output),
+            },
+            TaluOperation::Simd {
+                op,
+                activation_input,
+                data_input_0,
+                data_input_1,
+                data_output_0,
+                activation_output,
+                ..
+            } => TaluPortsConfig {
+                data_input_0: Some(data_input_0),
+                data_input_1: (!op.is_unary()).then_some(data_input_1),
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: Some(data_output_0),
+                data_output_1: None,
+                activation_output,
+            },
+            TaluOperation::Float {
+                op,
+                activation_input,
+                data_input_0,
+                data_input_1,
+                data_output_0,
+                activation_output,
+            } => TaluPortsConfig {
+                data_input_0: Some(data_input_0),
+                data_input_1: (!op.is_unary()).then_some(data_input_1),
+                data_input_2: None,
+                activation_input: Some(activation_input),
+                data_output_0: Some(data_output_0),
+                data_output_1: None,
                 activation_output,
             },
         }
@@ -0,0 +1,130 @@
+use crate::application::simulation::talu::{LaneWidth, OverflowMode, SimdOp};
+use crate::word::Word;
+
+impl LaneWidth {
+    pub fn bits(&self) -> u32 {
+        match self {
+            LaneWidth::Bits8    => 8,
+            LaneWidth::Bits16   => 16,
+        }
+    }
+    pub fn lane_count(&self) -> u32 {
+        Word::BITS / self.bits()
+    }
+    fn mask(&self) -> u32 {
+        u32::MAX >> (u32::BITS - self.bits())
+    }
+    fn range(&self, signed: bool) -> (i64, i64) {
+        if signed {
+            (-(1 << (self.bits() - 1)), (1 << (self.bits() - 1)) - 1)
+        } else {
+            (0, self.mask() as i64)
+        }
+    }
+    /// Reads lane `ix` of `word`, counting from the least significant lane.
+    pub fn lane(&self, word: Word, ix: u32, signed: bool) -> i64 {
+        let raw = (word as u32 >> (ix * self.bits())) & self.mask();
+        if signed && raw >> (self.bits() - 1) != 0 {
+            raw as i64 - (1 << self.bits())
+        } else {
+            raw as i64
+        }
+    }
+}
+
+/// Applies `op` lane by lane. `HorizontalSum` adds up the lanes of `a` into a
+/// single word and ignores `b`.
+pub fn apply(
+    op      : SimdOp,
+    lanes   : LaneWidth,
+    signed  : bool,
+    overflow: OverflowMode,
+    a       : Word,
+    b       : Word,
+) -> Word {
+    if let SimdOp::HorizontalSum = op {
+        return (0..lanes.lane_count())
+            .map(|ix| lanes.lane(a, ix, signed))
+            .sum::<i64>() as Word;
+    }
+
+    let (min, max) = lanes.range(signed);
+    (0..lanes.lane_count())
+        .map(|ix| {
+            let (x, y) = (lanes.lane(a, ix, signed), lanes.lane(b, ix, signed));
+            let res = match op {
+                SimdOp::Add => x + y,
+                SimdOp::Sub => x - y,
+                SimdOp::Cmp(cmp) => if cmp.holds(x, y) { lanes.mask() as i64 } else { 0 },
+                SimdOp::HorizontalSum => unreachable!(),
+            };
+            let res = match (op, overflow) {
+                (SimdOp::Cmp(_), _) | (_, OverflowMode::Wrap) => res,
+                (_, OverflowMode::Saturate) => res.clamp(min, max),
+            };
+            (res as u32 & lanes.mask()) << (ix * lanes.bits())
+        })
+        .fold(0, |word, lane| word | lane) as Word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::simulation::talu::CmpOp;
+    use LaneWidth::{Bits16, Bits8};
+    use OverflowMode::{Saturate, Wrap};
+
+    /// `apply`, on words given by their bits.
+    fn simd(op: SimdOp, lanes: LaneWidth, signed: bool, overflow: OverflowMode, a: u32, b: u32) -> u32 {
+        apply(op, lanes, signed, overflow, a as Word, b as Word) as u32
+    }
+
+    #[test]
+    fn lanes_are_signed_or_not() {
+        let word = 0x80FF_7F01_u32 as Word;
+        let lanes = |signed| (0..4).map(|ix| Bits8.lane(word, ix, signed)).collect::<Vec<_>>();
+        assert_eq!(lanes(false), [1, 127, 255, 128]);
+        assert_eq!(lanes(true), [1, 127, -1, -128]);
+        assert_eq!(Bits16.lane(word, 0, true), 0x7F01);
+        assert_eq!(Bits16.lane(word, 1, true), -0x7F01);
+        assert_eq!(Bits16.lane(word, 1, false), 0x80FF);
+    }
+
+    #[test]
+    fn add_wraps_or_saturates_at_lane_bounds() {
+        let (a, b) = (0x7FFF_8001, 0x0101_FFFF);
+        assert_eq!(simd(SimdOp::Add, Bits8, true, Wrap, a, b), 0x8000_7F00);
+        assert_eq!(simd(SimdOp::Add, Bits8, true, Saturate, a, b), 0x7F00_8000);
+        assert_eq!(simd(SimdOp::Add, Bits8, false, Wrap, a, b), 0x8000_7F00);
+        assert_eq!(simd(SimdOp::Add, Bits8, false, Saturate, a, b), 0x80FF_FFFF);
+    }
+
+    #[test]
+    fn sub_wraps_or_saturates_at_lane_bounds() {
+        assert_eq!(simd(SimdOp::Sub, Bits8, false, Wrap, 0x0000_0005, 0x0000_0106), 0x0000_FFFF);
+        assert_eq!(simd(SimdOp::Sub, Bits8, false, Saturate, 0x0000_0005, 0x0000_0106), 0);
+        assert_eq!(simd(SimdOp::Sub, Bits16, true, Wrap, 0x8000_0001, 0x0001_0002), 0x7FFF_FFFF);
+        assert_eq!(simd(SimdOp::Sub, Bits16, true, Saturate, 0x8000_0001, 0x0001_0002), 0x8000_FFFF);
+    }
+
+    #[test]
+    fn comparisons_fill_lanes() {
+        let (a, b) = (0x8001_05FF, 0x0001_0600);
+        let less = SimdOp::Cmp(CmpOp::LessThan);
+        assert_eq!(simd(less, Bits8, true, Wrap, a, b), 0xFF00_FFFF);
+        assert_eq!(simd(less, Bits8, false, Wrap, a, b), 0x0000_FF00);
+        // Masks aren't values, so saturation leaves them whole.
+        assert_eq!(simd(less, Bits8, true, Saturate, a, b), 0xFF00_FFFF);
+        assert_eq!(simd(SimdOp::Cmp(CmpOp::Eq), Bits16, false, Saturate, a, b), 0);
+        assert_eq!(simd(SimdOp::Cmp(CmpOp::NotEq), Bits16, false, Saturate, a, b), 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn horizontal_sum_adds_the_lanes_of_the_first_input() {
+        let sum = |lanes, signed, a: u32| simd(SimdOp::HorizontalSum, lanes, signed, Saturate, a, !0) as Word;
+        assert_eq!(sum(Bits8, true, 0x80FF_7F01), -1);
+        assert_eq!(sum(Bits8, false, 0x80FF_7F01), 511);
+        assert_eq!(sum(Bits16, true, 0x8000_FFFF), -32769);
+        assert_eq!(sum(Bits16, false, 0x8000_FFFF), 98303);
+    }
+}
@@ -1,12 +1,13 @@
+use fam::application::check::{check, Severity};
 use fam::application::direction::Axis::{Horizontal, Vertical};
 use fam::application::draw::controller::ControllerDrawingDefns;
 use fam::application::draw::cpu::CpuDrawingData;
-use fam::application::draw::cpu_register::CpuRegisterBankDrawingDefns;
+use fam::application::draw::cpu_register::{CpuRegisterBankDrawingDefns, RegisterValueFormat};
 use fam::application::draw::cursor::RectCursor;
 use fam::application::draw::grid_to_screen::{GridScreenTransformer, draw_path_grid};
 use fam::application::draw::instruction_memory;
 use fam::application::draw::instruction_memory::{
-    InstructionMemoryCurrentPosition, InstructionMemoryDrawingDefns,
+    InstructionMemoryCurrentPosition, InstructionMemoryDrawingDefns, InstructionMemoryDrawingState,
 };
 use fam::application::draw::path::draw_path;
 use fam::application::draw::port::{
@@ -14,7 +15,8 @@ use fam::application::draw::port::{
 };
 use fam::application::draw::pos::{Size, dist, pos, size};
 use fam::application::draw::shapes::draw_rectangle_pos;
-use fam::application::draw::talu::{TaluBankDrawingDefns, TaluDrawingDefns};
+use fam::application::draw::text::{TextStyle, draw_text_pos};
+use fam::application::draw::talu::{TaluBankDrawingDefns, TaluDrawingDefns, draw_talu_tooltip};
 use fam::application::grid::blocked_point::BlockedPoints;
 use fam::application::grid::component::{ComponentCalculatedDefns, DrawableComponent};
 use fam::application::grid::controller::ControllerGridDefns;
@@ -22,55 +24,175 @@ use fam::application::grid::cpu::CpuGridData;
 use fam::application::grid::grid_limits::GridLimits;
 use fam::application::grid::path::{Path, Paths};
 use fam::application::grid::pos::grid_pos;
-use fam::application::simulation::controller::Controller;
-use fam::application::simulation::cpu_registers::{CpuRegisterBank, REGISTER_COUNT};
+use fam::application::format::{take_format_arg, DataFormat, ParseError};
+use fam::application::input::{DataSection, FamInput};
+use fam::application::output::{collect_output, take_output_arg, OutputOptions, RunOutput};
+use fam::application::runner::{RunError, RunResult};
+use fam::application::simulation::cpu_registers::REGISTER_COUNT;
 use fam::application::simulation::instruction::Instruction;
-use fam::application::simulation::instruction_reader::InstructionMemory;
-use fam::application::simulation::main_memory::MainMemory;
 use fam::application::simulation::simulation::{Cpu, Netlists};
-use fam::application::simulation::talu::{CmpOp, TALU_COUNT, TaluBank, TaluOperation};
+use fam::application::simulation::talu::{CmpOp, TALU_COUNT, TaluOperation};
 use fam::word::Word;
 use macroquad::input::get_keys_pressed;
 use macroquad::miniquad::window::set_window_size;
 use macroquad::prelude::*;
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{Read, Write, stdin, stdout};
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 use wgpu::naga::FastHashMap;
 
-fn main() {
-    let input = read_input();
+/// How often an input file is looked at for changes.
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Shows a program running. Reads the input from the file given, or from
+/// stdin, and writes main memory to stdout once the window closes. Both are
+/// JSON or RON, by `--format` or else by the file's extension. A file is
+/// watched, and the program reloaded whenever it changes. `--output` adds
+/// more to the output, as `run_all` does.
+///
+/// Usage: `application [--format json|ron] [--output <list>] [input]`
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let format = match take_format_arg(&mut args) {
+        Ok(format) => format,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let options = match take_output_arg(&mut args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let input_file = args.first().map(|path| {
+        let path = PathBuf::from(path);
+        InputFile::new(DataFormat::choose(format, Some(&path)), path)
+    });
+    let format = input_file.as_ref().map_or(format.unwrap_or_default(), |file| file.format);
+    let input = match &input_file {
+        Some(file) => file.read(),
+        None => read_input(format).map_err(|err| format!("<stdin>:{err}")),
+    };
+    let input = match input {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(err) = check_input(&input) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+
     let output = Arc::new(OnceLock::new());
     let set_output = {
         let output = output.clone();
-        move |main_memory| {
-            output.set(main_memory).unwrap()
+        move |run_output| {
+            output.set(run_output).unwrap()
         }
     };
 
-    macroquad::Window::new("FAM Simulator", amain(input, set_output));
+    let cpu = match input.build_cpu() {
+        Ok(cpu) => cpu,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    macroquad::Window::new("FAM Simulator", amain(cpu, input, input_file, options, set_output));
     
-    write_output(output.get().unwrap())
+    match output.get().unwrap() {
+        Ok(output) => {
+            write_output(output, format);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
 }
-pub fn write_output(res: &FamOutput){
-    stdout().write_all(serde_json::to_string_pretty(&res).unwrap().as_bytes()).unwrap();
+pub fn write_output(res: &RunOutput, format: DataFormat){
+    stdout().write_all(format.write(res).as_bytes()).unwrap();
 }
 
-pub type FamOutput = Vec<Word>;
+fn read_input(format: DataFormat) -> Result<FamInput, ParseError> {
+    let mut text = String::new();
+    stdin().read_to_string(&mut text).expect("can't read stdin");
+    format.parse(&text)
+}
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
-pub struct FamInput{
-    pub program: Vec<Instruction>,
-    pub main_memory: Vec<Word>,
+/// Prints what `check` finds, failing if any of it is an error.
+fn check_input(input: &FamInput) -> Result<(), String> {
+    let diagnostics = check(input);
+    for diagnostic in diagnostics.iter() {
+        eprintln!("{diagnostic}");
+    }
+    match diagnostics.iter().find(|d| d.severity == Severity::Error) {
+        Some(error) => Err(error.to_string()),
+        None => Ok(()),
+    }
 }
 
-fn read_input() -> FamInput {
-    serde_json::from_reader(stdin()).expect("invalid input")
+/// An input file, read again whenever its modification time changes.
+pub struct InputFile {
+    pub path    : PathBuf,
+    pub format  : DataFormat,
+    modified    : Option<SystemTime>,
+    last_poll   : Instant,
 }
-async fn amain<'a>(input: FamInput, send_output: impl FnOnce(Vec<Word>) + 'a) {
-    let ( program, data ) = ( input.program, input.main_memory );
 
+impl InputFile {
+    pub fn new(format: DataFormat, path: PathBuf) -> Self {
+        InputFile {
+            modified: Self::modified(&path),
+            path,
+            format,
+            last_poll: Instant::now(),
+        }
+    }
+
+    fn modified(path: &std::path::Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    pub fn read(&self) -> Result<FamInput, String> {
+        let text = fs::read_to_string(&self.path)
+            .map_err(|err| format!("{}: {err}", self.path.display()))?;
+        self.format.parse(&text).map_err(|err| format!("{}:{err}", self.path.display()))
+    }
+
+    /// The input read again, if the file changed since it was last read.
+    pub fn poll(&mut self) -> Option<Result<FamInput, String>> {
+        if self.last_poll.elapsed() < RELOAD_POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+        let modified = Self::modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(self.read())
+    }
+}
+
+async fn amain<'a>(
+    cpu         : Cpu,
+    input       : FamInput,
+    input_file  : Option<InputFile>,
+    options     : OutputOptions,
+    send_output : impl FnOnce(RunResult) + 'a,
+) {
     let screen_size = size(1600, 900);
 
     let grid_limits = GridLimits::new(u16vec2(screen_size.x as u16 / 5, screen_size.y as u16 / 5));
@@ -80,7 +202,7 @@ async fn amain<'a>(input: FamInput, send_output: impl FnOnce(Vec<Word>) + 'a) {
         Rect::new(0_f32, 0_f32, screen_size.x as f32, screen_size.y as f32),
     );
 
-    let cpu = build_full_cpu(program, data, screen_size, &grid_to_screen_mapper);
+    let cpu = build_full_cpu(cpu, screen_size, &grid_to_screen_mapper);
 
     let mut app = Application {
         step: 0,
@@ -88,197 +210,39 @@ async fn amain<'a>(input: FamInput, send_output: impl FnOnce(Vec<Word>) + 'a) {
         screen_size,
         grid_to_screen_mapper,
         grid_limits,
+        input,
+        input_file,
+        reload_error: None,
+        furthest_instruction: 0,
+        register_value_format: RegisterValueFormat::default(),
     };
 
     set_window_size(screen_size.x as u32, screen_size.y as u32);
 
     'MAIN_LOOP: loop {
         set_window_size(screen_size.x as u32, screen_size.y as u32);
+        app.poll_input_file();
         if get_keys_pressed().contains(&KeyCode::Space) {
+            // A halted program stays on screen, showing why, until the
+            // next step is asked for.
+            if app.cpu.sim.fault().is_some() { break 'MAIN_LOOP; }
             let running = app.step();
-            if !running { break 'MAIN_LOOP; }
+            if !running && app.cpu.sim.fault().is_none() { break 'MAIN_LOOP; }
+        }
+        if get_keys_pressed().contains(&KeyCode::F) {
+            app.register_value_format = app.register_value_format.toggled();
         }
         clear_background(WHITE);
         app.draw();
         next_frame().await;
     }
-    let res = app.cpu.sim.main_memory.0.read().unwrap().clone();
+    let res = match app.cpu.sim.fault() {
+        Some(fault) => Err(RunError::Halted(fault.to_string())),
+        None => Ok(collect_output(&app.cpu.sim, &app.input, &options, app.step)),
+    };
     send_output(res);        
 }
 
-pub fn make_loop_program() -> FamInput{
-    let source_addr = 0;
-    let target_addr = 5;
-
-    let loop_count_reg = 2;
-    let start_reg = 3;
-    let finished_reg = 30;
-    let ix_reg = 5;
-    let ix_lt_loop_reg = 6;
-
-    let start_inner_reg = 8;
-    let source_addr_reg = 9;
-    let target_addr_reg = 10;
-
-    let word_reg = 11;
-    let word_ready_reg = 12;
-    let write_finished_reg = 13;
-    let is_final_reg = 14;
-
-    let program = Vec::from_iter([
-        Instruction::SetLiteral {
-            literal: 0,
-            reg_addr: 0,
-        },
-        Instruction::SetLiteral {
-            literal: 1,
-            reg_addr: 1,
-        },
-        Instruction::SetLiteral {
-            literal: 5,
-            reg_addr: 2,
-        }, // loop_count
-        Instruction::SetLiteral {
-            literal: 0,
-            reg_addr: 3,
-        }, // start
-        Instruction::SetLiteral {
-            literal: 0,
-            reg_addr: 4,
-        }, // finished
-        Instruction::SetLiteral {
-            literal: 0,
-            reg_addr: 5,
-        }, // i
-        Instruction::SetTaluConfig {
-            talu_addr: 0,
-            talu_config: TaluOperation::Cmp {
-                op: CmpOp::LessThan,
-                activation_input: 1,
-                activation_output: None,
-                data_input_0: ix_reg,
-                data_input_1: loop_count_reg,
-                data_output: ix_lt_loop_reg,
-            },
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 1,
-            talu_config: TaluOperation::And {
-                data_input_0: start_reg,
-                data_input_1: ix_lt_loop_reg,
-                activation_input: 1,
-                data_output_0: start_inner_reg,
-                activation_output: None,
-            },
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 2,
-            talu_config: TaluOperation::Add {
-                data_input_0: ix_reg,
-                data_input_1: 1,
-                activation_input: start_inner_reg,
-
-                result_output: ix_reg,
-                flags_output: None,
-                activation_output: None,
-            },
-        },
-        Instruction::SetLiteral {
-            literal: source_addr,
-            reg_addr: source_addr_reg,
-        },
-        Instruction::SetLiteral {
-            literal: target_addr,
-            reg_addr: target_addr_reg,
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 3,
-            talu_config: TaluOperation::ReadFromMem {
-                activation_input: start_inner_reg,
-                address_input: source_addr_reg,
-                data_output: word_reg,
-                activation_output: Some(word_ready_reg),
-            },
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 4,
-            talu_config: TaluOperation::WriteToMem {
-                address_input: target_addr_reg,
-                data_input: word_reg,
-
-                activation_input: word_ready_reg,
-                activation_output: Some(write_finished_reg),
-            },
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 5,
-            talu_config: TaluOperation::Add {
-                activation_input: write_finished_reg,
-                data_input_0: source_addr_reg,
-                data_input_1: 1,
-                result_output: source_addr_reg,
-                flags_output: None,
-                activation_output: None,
-            },
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 6,
-            talu_config: TaluOperation::Add {
-                activation_input: write_finished_reg,
-                data_input_0: target_addr_reg,
-                data_input_1: 1,
-                result_output: target_addr_reg,
-                flags_output: None,
-                activation_output: None,
-            },
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 7,
-            talu_config: TaluOperation::Mov {
-                activation_input: 1,
-                value_input: write_finished_reg,
-                data_output: start_reg,
-                activation_output: None,
-            },
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 8,
-            talu_config: TaluOperation::Cmp {
-                op: CmpOp::Eq,
-                data_input_0: ix_reg,
-                data_input_1: loop_count_reg,
-                activation_input: 1,
-
-                data_output: is_final_reg,
-                activation_output: None,
-            },
-        },
-        Instruction::SetTaluConfig {
-            talu_addr: 9,
-            talu_config: TaluOperation::And {
-                data_input_0: is_final_reg,
-                data_input_1: start_reg,
-                activation_input: 1,
-
-                data_output_0: finished_reg,
-                activation_output: None,
-            },
-        },
-        Instruction::SetLiteral {
-            literal: 1,
-            reg_addr: start_reg,
-        },
-        Instruction::SetLiteral {
-            literal: 0,
-            reg_addr: start_reg,
-        },
-        Instruction::WaitForActivationSignal { register_index: finished_reg }, // 128
-    ]);
-
-    let data = vec![1, 2, 3, 4, 5, 0, 0, 0, 0, 0, 0];
-
-    FamInput { program, main_memory: data}
-}
 pub struct FullCpu {
     pub sim: Cpu,
     pub grid: CpuGridData,
@@ -291,22 +255,70 @@ pub struct Application {
     pub screen_size: Size,
     pub grid_to_screen_mapper: GridScreenTransformer,
     pub grid_limits: GridLimits,
+    /// What `cpu` was built from.
+    pub input: FamInput,
+    pub input_file: Option<InputFile>,
+    /// Why the last change to `input_file` wasn't loaded.
+    pub reload_error: Option<String>,
+    /// The highest instruction address run so far.
+    pub furthest_instruction: usize,
+    /// How register values are shown, kept when the CPU is rebuilt.
+    pub register_value_format: RegisterValueFormat,
 }
 
 impl Application {
     pub fn draw(&self) {
-        draw_full_cpu(&self.cpu, &self.grid_limits, &self.grid_to_screen_mapper);
+        draw_full_cpu(&self.cpu, self.register_value_format, &self.grid_limits, &self.grid_to_screen_mapper);
 
         draw_paths(
             &self.cpu.grid.paths,
             &self.cpu.sim.netlists,
             &self.grid_to_screen_mapper,
         );
+        draw_talu_tooltip(
+            &self.cpu.sim.talu_bank,
+            &self.cpu.grid.talu_bank,
+            &self.grid_to_screen_mapper,
+        );
+        if let Some(combinational_loop) = &self.cpu.sim.combinational_loop {
+            draw_text_pos(
+                &combinational_loop.to_string(),
+                pos(4, self.screen_size.y - 16),
+                TextStyle::Normal,
+                1,
+                RED,
+            );
+        }
+        if let Some(fault) = self.cpu.sim.fault() {
+            draw_text_pos(
+                &format!("halted: {fault}"),
+                pos(4, self.screen_size.y - 48),
+                TextStyle::Normal,
+                1,
+                RED,
+            );
+        }
+        if let Some(reload_error) = &self.reload_error {
+            draw_text_pos(
+                &format!("not reloaded: {reload_error}"),
+                pos(4, self.screen_size.y - 32),
+                TextStyle::Normal,
+                1,
+                RED,
+            );
+        }
         // draw_fps();
     }
 
     pub fn step(&mut self) -> bool {
         let should_continue = self.cpu.sim.step();
+        self.step += 1;
+        self.update_layout();
+        return should_continue;
+    }
+
+    /// Follows the simulation to the state it stepped into.
+    fn update_layout(&mut self) {
         if let Some(instruction_addr) = self
             .cpu
             .sim
@@ -315,6 +327,7 @@ impl Application {
             .get_instruction_pos()
         {
             self.cpu.drawing.instruction_memory.current_pos = instruction_addr;
+            self.furthest_instruction = self.furthest_instruction.max(instruction_addr);
         }
         self.cpu.grid.update_blocked_points();
         self.cpu.grid.calculate_paths(
@@ -322,7 +335,63 @@ impl Application {
             &self.cpu.sim.netlists,
             &self.grid_limits,
         );
-        return should_continue;
+    }
+
+    /// Reloads the input file if it changed, keeping the old program
+    /// running when the new one can't be read or has errors.
+    pub fn poll_input_file(&mut self) {
+        let Some(reloaded) = self.input_file.as_mut().and_then(InputFile::poll) else {
+            return;
+        };
+        let reloaded = reloaded
+            .and_then(|input| check_input(&input).map(|()| input))
+            .and_then(|input| self.reload(input));
+        match reloaded {
+            Ok(()) => self.reload_error = None,
+            Err(err) => {
+                eprintln!("{err}");
+                self.reload_error = Some(err);
+            }
+        }
+    }
+
+    /// Rebuilds the CPU, its layout and routes for `input`. If every
+    /// instruction run so far is unchanged, and nothing else the run
+    /// depends on, the new CPU is run up to the current step. Fails,
+    /// leaving the old CPU, if `input` can't be built.
+    pub fn reload(&mut self, input: FamInput) -> Result<(), String> {
+        // In von Neumann mode all of the program is in memory from the
+        // start, where what has run may read it.
+        let prefix = |input: &FamInput| match input.code_region {
+            Some(_) => input.program.clone(),
+            None => input.program[..input.program.len().min(self.furthest_instruction + 1)].to_vec(),
+        };
+        let setup = |input: &FamInput| FamInput { program: Vec::new(), ..input.clone() };
+        let keep_step = prefix(&input) == prefix(&self.input) && setup(&input) == setup(&self.input);
+
+        let mut sim = input.build_cpu().map_err(|err| err.to_string())?;
+        let mut step = 0;
+        if keep_step {
+            sim.track_connections = false;
+            while step < self.step {
+                // Only the last step's connections are drawn.
+                sim.track_connections = step + 1 == self.step;
+                step += 1;
+                if !sim.step() {
+                    break;
+                }
+            }
+            sim.track_connections = true;
+        }
+
+        self.cpu = build_full_cpu(sim, self.screen_size, &self.grid_to_screen_mapper);
+        self.input = input;
+        self.step = step;
+        if !keep_step {
+            self.furthest_instruction = 0;
+        }
+        self.update_layout();
+        Ok(())
     }
 }
 
@@ -363,6 +432,7 @@ fn draw_paths(
 
 fn draw_full_cpu(
     cpu: &FullCpu,
+    register_value_format: RegisterValueFormat,
     grid_limits: &GridLimits,
     grid_to_screen_mapper: &GridScreenTransformer,
 ) {
@@ -372,15 +442,22 @@ fn draw_full_cpu(
         &cpu.grid.blocked_points
     );
 
+    let code = cpu.sim.controller.instruction_reader.code_region.map(|region| {
+        let words = cpu.sim.main_memory.get_io().read_range(region.base, region.end() - region.base);
+        (region, words)
+    });
     cpu.sim.instruction_memory.draw(
-        &cpu.drawing.instruction_memory.current_pos,
+        &InstructionMemoryDrawingState {
+            current_pos: cpu.drawing.instruction_memory.current_pos,
+            code,
+        },
         &cpu.grid.instruction_memory,
         &cpu.drawing.instruction_memory,
         &cpu.drawing.port,
         &grid_to_screen_mapper,
     );
 
-    let registers_drawing_state = Box::new([(); REGISTER_COUNT]);
+    let registers_drawing_state = Box::new([register_value_format; REGISTER_COUNT]);
 
     cpu.sim.register_bank.draw(
         &registers_drawing_state,
@@ -410,34 +487,10 @@ fn draw_full_cpu(
 }
 
 fn build_full_cpu(
-    program: Vec<Instruction>,
-    data: Vec<Word>,
+    cpu: Cpu,
     screen_size: Size,
     grid_to_screen_mapper: &GridScreenTransformer,
 ) -> FullCpu {
-    let mut main_memory = MainMemory::new(data);
-
-    let registers = CpuRegisterBank::new();
-
-    let instruction_memory = InstructionMemory::new(program.clone());
-
-    let talus = TaluBank::new(&mut main_memory);
-
-    let controller = Controller::new(&instruction_memory);
-
-    let cpu = {
-        Cpu {
-            main_memory,
-            talu_bank: talus,
-            register_bank: registers,
-            controller,
-            instruction_memory,
-            is_done: false,
-            connections: Default::default(),
-            netlists: Default::default(),
-        }
-    };
-
     let port_drawing_data = PortDrawingDefns {
         base: 6,
         line_len: 4,
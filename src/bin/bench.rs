@@ -0,0 +1,173 @@
+use fam::application::input::FamInput;
+use fam::application::simulation::batch::BatchCpu;
+use fam::application::simulation::instruction::Instruction;
+use fam::application::simulation::plan::SimulationBackend;
+use fam::application::simulation::propagation::PropagationModel;
+use fam::application::simulation::scheduler::TaluScheduling;
+use fam::application::simulation::simulation::Cpu;
+use fam::application::simulation::talu::{TALU_COUNT, TaluOperation};
+use itertools::Itertools;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Counts heap allocations, both while the benchmark program configures
+/// its TALUs and after, when stepping without tracking connections must
+/// not allocate: the bench fails if it does.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const WARMUP_STEPS: usize = 1_000;
+const DEFAULT_STEPS: usize = 1_000_000;
+const BATCH_LANES: usize = 64;
+
+/// Every TALU increments its own register each step; the controller then
+/// waits forever on a register nothing writes.
+fn make_bench_program() -> Vec<Instruction> {
+    let one_reg = 1;
+    let never_set_reg = 2 + TALU_COUNT;
+
+    let mut program = vec![Instruction::SetLiteral { literal: 1, reg_addr: one_reg }];
+    program.extend((0..TALU_COUNT).map(|talu_addr| Instruction::SetTaluConfig {
+        talu_addr,
+        talu_config: TaluOperation::Add {
+            activation_input: one_reg,
+            data_input_0: 2 + talu_addr,
+            data_input_1: one_reg,
+            result_output: 2 + talu_addr,
+            flags_output: None,
+            activation_output: None,
+        },
+    }));
+    program.push(Instruction::WaitForActivationSignal { register_index: never_set_reg });
+    program
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Case {
+    propagation: PropagationModel,
+    scheduling: TaluScheduling,
+    backend: SimulationBackend,
+    track_connections: bool,
+}
+
+fn run(case: Case, steps: usize) -> Cpu {
+    let mut cpu = Cpu::new(make_bench_program(), vec![], Default::default());
+    cpu.propagation = case.propagation;
+    cpu.scheduling = case.scheduling;
+    cpu.backend = case.backend;
+    cpu.track_connections = case.track_connections;
+
+    let allocations_at_start = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..WARMUP_STEPS {
+        assert!(cpu.step(), "benchmark program must not finish");
+    }
+
+    let allocations_after_warmup = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..steps {
+        assert!(cpu.step(), "benchmark program must not finish");
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_after_warmup;
+
+    println!(
+        "{:<14} {:<12} {:<10} tracking={:<5} {:>12.0} steps/s  {} allocations in {WARMUP_STEPS} warmup steps, {} after",
+        format!("{:?}", case.propagation),
+        format!("{:?}", case.scheduling),
+        format!("{:?}", case.backend),
+        case.track_connections,
+        steps as f64 / elapsed.as_secs_f64(),
+        allocations_after_warmup - allocations_at_start,
+        allocations,
+    );
+    assert!(
+        case.track_connections || allocations == 0,
+        "{case:?}: stepping without tracking connections allocated {allocations} times",
+    );
+    cpu
+}
+
+/// Steps `BATCH_LANES` machines as one batch, then one by one.
+fn run_batch(steps: usize) {
+    let steps = steps / BATCH_LANES;
+    let inputs = vec![FamInput { program: make_bench_program(), ..Default::default() }; BATCH_LANES];
+
+    let mut batch = BatchCpu::new(&inputs).expect("benchmark program must build");
+    let start = Instant::now();
+    for _ in 0..steps {
+        assert!(batch.step(), "benchmark program must not finish");
+    }
+    let batch_elapsed = start.elapsed();
+
+    let mut cpus = inputs.iter()
+        .map(|input| {
+            let mut cpu = input.build_cpu().expect("benchmark program must build");
+            cpu.track_connections = false;
+            cpu
+        })
+        .collect_vec();
+    let start = Instant::now();
+    for cpu in cpus.iter_mut() {
+        for _ in 0..steps {
+            assert!(cpu.step(), "benchmark program must not finish");
+        }
+    }
+    let single_elapsed = start.elapsed();
+
+    for (lane, cpu) in cpus.iter().enumerate() {
+        assert_eq!(batch.registers(lane).to_vec(), registers(cpu), "lane {lane} differs from its own run");
+    }
+    let lane_steps = (steps * BATCH_LANES) as f64;
+    println!(
+        "Batch of {BATCH_LANES}: {:>12.0} lane steps/s, one by one: {:>12.0} steps/s",
+        lane_steps / batch_elapsed.as_secs_f64(),
+        lane_steps / single_elapsed.as_secs_f64(),
+    );
+}
+
+fn registers(cpu: &Cpu) -> Vec<i32> {
+    cpu.register_bank.components.iter().map(|reg| reg.value).collect()
+}
+
+fn main() {
+    let steps = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("step count must be a number"))
+        .unwrap_or(DEFAULT_STEPS);
+
+    for propagation in [PropagationModel::Registered, PropagationModel::Combinational] {
+        let mut results = Vec::new();
+        for scheduling in [TaluScheduling::FullSweep, TaluScheduling::EventDriven] {
+            for backend in [SimulationBackend::Reference, SimulationBackend::Compiled] {
+                for track_connections in [true, false] {
+                    let case = Case { propagation, scheduling, backend, track_connections };
+                    results.push(registers(&run(case, steps)));
+                }
+            }
+        }
+        assert!(
+            results.iter().all_equal(),
+            "{propagation:?}: configurations disagree on the final register values"
+        );
+    }
+    run_batch(steps);
+}
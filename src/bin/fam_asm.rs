@@ -0,0 +1,60 @@
+use fam::application::asm::assemble;
+use fam::application::format::take_format_arg;
+use fam::application::program_file::write_program_file;
+use std::io::{Read, Write, stdin, stdout};
+use std::process::ExitCode;
+
+/// Assembles a program from the file given, or from stdin, and writes the
+/// resulting input as JSON, ready for `application` or `run_all`, as RON
+/// with `--format ron`, or with `--binary` as a binary program file.
+///
+/// Usage: `fam_asm [--binary | --format json|ron] [source.fasm]`
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let format = match take_format_arg(&mut args) {
+        Ok(format) => format.unwrap_or_default(),
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let binary = args.iter().position(|arg| arg == "--binary").map(|ix| args.remove(ix)).is_some();
+    let path = args.first();
+    let read = match path {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut source = String::new();
+            stdin().read_to_string(&mut source).map(|_| source)
+        }
+    };
+    let source = match read {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("{}: {err}", path.map_or("<stdin>", String::as_str));
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let input = match assemble(&source) {
+        Ok(input) => input,
+        Err(errors) => {
+            let file = path.map_or("<stdin>", String::as_str);
+            for err in errors {
+                eprintln!("{file}:{err}");
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+    if binary {
+        match write_program_file(&input) {
+            Ok(bytes) => stdout().write_all(&bytes).unwrap(),
+            Err(err) => {
+                eprintln!("can't encode program: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        stdout().write_all(format.write(&input).as_bytes()).unwrap();
+    }
+    ExitCode::SUCCESS
+}
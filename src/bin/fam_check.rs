@@ -0,0 +1,55 @@
+use fam::application::check::{check, Severity};
+use fam::application::format::{take_format_arg, DataFormat};
+use fam::application::input::FamInput;
+use std::io::Read;
+use std::path::Path;
+use std::process::ExitCode;
+
+/// Checks an input, from the file given or from stdin, for mistakes that
+/// can be found without running it. Fails if any is an error. The input is
+/// JSON or RON, by `--format` or else by the file's extension.
+///
+/// Usage: `fam_check [--format json|ron] [input]`
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let format = match take_format_arg(&mut args) {
+        Ok(format) => format,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let path = args.first();
+    let read = match path {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).map(|_| text)
+        }
+    };
+    let text = match read {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("{}: {err}", path.map_or("<stdin>", String::as_str));
+            return ExitCode::FAILURE;
+        }
+    };
+    let format = DataFormat::choose(format, path.map(Path::new));
+    let input: FamInput = match format.parse(&text) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("{}:{err}", path.map_or("<stdin>", String::as_str));
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let diagnostics = check(&input);
+    for diagnostic in diagnostics.iter() {
+        eprintln!("{diagnostic}");
+    }
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
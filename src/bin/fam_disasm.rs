@@ -0,0 +1,69 @@
+use fam::application::asm::disassemble;
+use fam::application::format::{take_format_arg, DataFormat};
+use fam::application::input::FamInput;
+use fam::application::program_file::{read_program_file, PROGRAM_FILE_MAGIC};
+use std::io::{Read, Write, stdin, stdout};
+use std::path::Path;
+use std::process::ExitCode;
+
+/// Prints an input or binary program file, from the file given or from
+/// stdin, as an assembler listing that `fam_asm` turns back into the same
+/// input. Inputs are JSON or RON, by `--format` or else by the file's
+/// extension.
+///
+/// Usage: `fam_disasm [--format json|ron] [input]`
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let format = match take_format_arg(&mut args) {
+        Ok(format) => format,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let path = args.first();
+    let file = path.map_or("<stdin>", String::as_str);
+    let read = match path {
+        Some(path) => std::fs::read(path),
+        None => {
+            let mut bytes = Vec::new();
+            stdin().read_to_end(&mut bytes).map(|_| bytes)
+        }
+    };
+    let bytes = match read {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("{file}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let input: FamInput = if bytes.starts_with(&PROGRAM_FILE_MAGIC) {
+        match read_program_file(&bytes) {
+            Ok(input) => input,
+            Err(err) => {
+                eprintln!("{file}: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        let Ok(text) = String::from_utf8(bytes) else {
+            eprintln!("{file}: neither a program file nor UTF-8 text");
+            return ExitCode::FAILURE;
+        };
+        match DataFormat::choose(format, path.map(Path::new)).parse(&text) {
+            Ok(input) => input,
+            Err(err) => {
+                eprintln!("{file}:{err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+    match disassemble(&input) {
+        Ok(listing) => stdout().write_all(listing.as_bytes()).unwrap(),
+        Err(err) => {
+            eprintln!("{file}: {err}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
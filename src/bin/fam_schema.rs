@@ -0,0 +1,76 @@
+use fam::application::format::DataFormat;
+use fam::application::schema::Document;
+use std::io::{Read, Write, stdin, stdout};
+use std::path::Path;
+use std::process::ExitCode;
+
+/// Prints the JSON Schema of a document, `input` unless another is named:
+/// `inputs` and `results` are what `run_all` reads and writes, `output` what
+/// `application` writes. `--write <dir>` writes every schema to
+/// `<dir>/<document>.schema.json`, which is how the ones in `schema/` are
+/// made. `--validate <document>` checks a JSON document, from the file given
+/// or from stdin, against its schema instead, failing if it doesn't fit.
+///
+/// Usage: `fam_schema [document] | --write <dir> | --validate <document> [file]`
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let document = |name: Option<&String>| {
+        let name = name.map_or("input", String::as_str);
+        Document::from_name(name).ok_or_else(|| format!(
+            "unknown document `{name}`, expected one of {}",
+            Document::ALL.map(|document| format!("`{}`", document.name())).join(", "),
+        ))
+    };
+    let result = match args.first().map(String::as_str) {
+        Some("--write") => match args.get(1) {
+            Some(dir) => write_all(Path::new(dir)),
+            None => Err("`--write` needs a directory".to_string()),
+        },
+        Some("--validate") => match args.get(1) {
+            Some(name) => document(Some(name)).and_then(|document| validate(document, args.get(2))),
+            None => Err("`--validate` needs a document".to_string()),
+        },
+        _ => document(args.first()).map(|document| {
+            stdout().write_all(document.schema_text().as_bytes()).unwrap();
+        }),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn write_all(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+    for document in Document::ALL {
+        let path = dir.join(document.file_name());
+        std::fs::write(&path, document.schema_text()).map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn validate(document: Document, path: Option<&String>) -> Result<(), String> {
+    let name = path.map_or("<stdin>", String::as_str);
+    let text = match path {
+        Some(path) => std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?,
+        None => {
+            let mut text = String::new();
+            stdin().read_to_string(&mut text).expect("can't read stdin");
+            text
+        }
+    };
+    let value: serde_json::Value = DataFormat::Json.parse(&text).map_err(|err| format!("{name}:{err}"))?;
+
+    let errors = document.validate(&value);
+    for error in errors.iter() {
+        eprintln!("{name}: {error}");
+    }
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(format!("{name} isn't a valid {} document", document.name())),
+        count => Err(format!("{name} isn't a valid {} document, {count} problems", document.name())),
+    }
+}
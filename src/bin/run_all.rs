@@ -0,0 +1,71 @@
+use fam::application::format::take_format_arg;
+use fam::application::input::FamInput;
+use fam::application::output::take_output_arg;
+use fam::application::runner::run_all;
+use fam::Step;
+use std::io::{Read, Write, stdin, stdout};
+use std::num::NonZeroUsize;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread;
+
+const DEFAULT_MAX_STEPS: Step = 1_000_000;
+
+/// Runs an array of inputs from stdin headless, on all cores, and writes
+/// an array with one result per input, in input order. Both are JSON, or
+/// RON with `--format ron`. A result is the final main memory, or with
+/// `--output` a report that also has the parts listed, e.g.
+/// `--output registers,statistics`, or `--output all`.
+///
+/// Usage: `run_all [--format json|ron] [--output <list>] [threads] [max_steps]`
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let format = match take_format_arg(&mut args) {
+        Ok(format) => format.unwrap_or_default(),
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let options = match take_output_arg(&mut args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut args = args.into_iter();
+    let threads = match parse_arg(args.next(), "thread count must be a positive number") {
+        Ok(threads) => threads.unwrap_or_else(|| thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)),
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let max_steps = match parse_arg(args.next(), "step limit must be a number") {
+        Ok(max_steps) => max_steps.unwrap_or(DEFAULT_MAX_STEPS),
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut text = String::new();
+    stdin().read_to_string(&mut text).expect("can't read stdin");
+    let inputs: Vec<FamInput> = match format.parse(&text) {
+        Ok(inputs) => inputs,
+        Err(err) => {
+            eprintln!("<stdin>:{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let results = run_all(&inputs, threads, max_steps, &options);
+
+    stdout().write_all(format.write(&results).as_bytes()).unwrap();
+    ExitCode::SUCCESS
+}
+
+/// Parses an optional argument, failing with `expected` and the argument.
+fn parse_arg<T: FromStr>(arg: Option<String>, expected: &str) -> Result<Option<T>, String> {
+    arg.map(|arg| arg.parse().map_err(|_| format!("{expected}, not `{arg}`"))).transpose()
+}